@@ -0,0 +1,89 @@
+//! Throughput of the text backends on a large document.
+//!
+//! Run with `cargo bench`. Each edit benchmark applies `OPERATIONS` edits per iteration and
+//! reports them as elements per second, so a regression in piece coalescing or piece lookup
+//! shows up as a drop in ops/sec.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust_text_editor::prelude::*;
+
+/// Number of inserts or deletes applied in one iteration.
+const OPERATIONS: usize = 10_000;
+
+/// Number of lines in the benchmark document, about 1 MB of text.
+const DOCUMENT_LINES: usize = 18_000;
+
+/// A large ASCII document, so every byte offset is a valid position.
+fn document() -> String {
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit.\n".repeat(DOCUMENT_LINES)
+}
+
+/// Small xorshift generator, seeded so every run edits the same positions.
+struct Positions(u64);
+
+impl Positions {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+fn random_inserts<T: TextTrait>(text: &mut T) {
+    let mut positions = Positions(0x2545_f491_4f6c_dd1d);
+    for _ in 0..OPERATIONS {
+        let position = positions.below(text.total_length() + 1);
+        text.add_text("x", position).unwrap();
+    }
+}
+
+fn random_deletes<T: TextTrait>(text: &mut T) {
+    let mut positions = Positions(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..OPERATIONS {
+        let position = positions.below(text.total_length());
+        text.delete_text(position, position + 1).unwrap();
+    }
+}
+
+fn bench_edits(c: &mut Criterion) {
+    let document = document();
+    let mut group = c.benchmark_group("edits");
+    group.throughput(Throughput::Elements(OPERATIONS as u64));
+    group.sample_size(20);
+
+    group.bench_function(BenchmarkId::new("random_inserts", "piece_table"), |b| {
+        b.iter_batched(|| PieceTable::new(&document), |mut pt| random_inserts(&mut pt), BatchSize::LargeInput)
+    });
+    group.bench_function(BenchmarkId::new("random_deletes", "piece_table"), |b| {
+        b.iter_batched(|| PieceTable::new(&document), |mut pt| random_deletes(&mut pt), BatchSize::LargeInput)
+    });
+    group.bench_function(BenchmarkId::new("random_inserts", "gap_buffer"), |b| {
+        b.iter_batched(|| GapBuffer::new(&document), |mut gb| random_inserts(&mut gb), BatchSize::LargeInput)
+    });
+    group.bench_function(BenchmarkId::new("random_deletes", "gap_buffer"), |b| {
+        b.iter_batched(|| GapBuffer::new(&document), |mut gb| random_deletes(&mut gb), BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+fn bench_get_text(c: &mut Criterion) {
+    let document = document();
+    let mut group = c.benchmark_group("get_text");
+    group.throughput(Throughput::Bytes(document.len() as u64));
+
+    // Fragment the table first, so reconstruction walks many pieces like a long editing session
+    let mut pt = PieceTable::new(&document);
+    random_inserts(&mut pt);
+    random_deletes(&mut pt);
+    group.bench_function("piece_table", |b| b.iter(|| black_box(pt.get_text())));
+
+    let mut gb = GapBuffer::new(&document);
+    random_inserts(&mut gb);
+    random_deletes(&mut gb);
+    group.bench_function("gap_buffer", |b| b.iter(|| black_box(gb.get_text())));
+    group.finish();
+}
+
+criterion_group!(benches, bench_edits, bench_get_text);
+criterion_main!(benches);
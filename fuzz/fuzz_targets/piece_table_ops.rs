@@ -0,0 +1,74 @@
+//! Drives a `PieceTable` with a stream of inserts and deletes decoded from the fuzzer input,
+//! mirroring every edit on a `String`.
+//!
+//! Positions are taken as-is, without snapping them to character boundaries, so the error
+//! paths for out-of-range and mid-character positions are exercised too. An edit must succeed
+//! exactly when the same edit is valid on the `String`, and must leave the text untouched
+//! when it fails.
+//!
+//! Run with `cargo fuzz run piece_table_ops` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_text_editor::prelude::*;
+
+/// Reads the operations from `data`: an opcode byte, a two-byte position and a length byte,
+/// followed for inserts by `length` bytes of text (decoded lossily into UTF-8).
+fn run(data: &[u8]) {
+    let mut table = PieceTable::new("");
+    let mut oracle = String::new();
+    let mut rest = data;
+
+    while let [opcode, high, low, length, tail @ ..] = rest {
+        let position = u16::from_be_bytes([*high, *low]) as usize % (oracle.len() + 2);
+        let length = *length as usize;
+
+        if opcode % 2 == 0 {
+            let taken = length.min(tail.len());
+            let text = String::from_utf8_lossy(&tail[..taken]).into_owned();
+            rest = &tail[taken..];
+
+            let valid = position <= oracle.len() && oracle.is_char_boundary(position);
+            let before = table.total_length();
+            match table.add_text(&text, position) {
+                Ok(()) => {
+                    // Inserting nothing succeeds anywhere, without touching the text
+                    assert!(valid || text.is_empty(), "insert at {} should have failed", position);
+                    if !text.is_empty() {
+                        oracle.insert_str(position, &text);
+                    }
+                    assert_eq!(table.total_length(), before + text.len());
+                }
+                Err(_) => {
+                    assert!(!valid, "insert at {} should have succeeded", position);
+                    assert_eq!(table.total_length(), before);
+                }
+            }
+        } else {
+            rest = tail;
+            let end = position + length;
+
+            let valid = end <= oracle.len()
+                && oracle.is_char_boundary(position)
+                && oracle.is_char_boundary(end);
+            let before = table.total_length();
+            match table.delete_text(position, end) {
+                Ok(()) => {
+                    assert!(valid, "delete of {}..{} should have failed", position, end);
+                    oracle.replace_range(position..end, "");
+                    assert_eq!(table.total_length(), before - length);
+                }
+                Err(_) => {
+                    assert!(!valid, "delete of {}..{} should have succeeded", position, end);
+                    assert_eq!(table.total_length(), before);
+                }
+            }
+        }
+
+        assert_eq!(table.get_text(), oracle);
+        assert!(table.validate().is_ok(), "{:?}", table.validate());
+    }
+}
+
+fuzz_target!(|data: &[u8]| run(data));
@@ -16,27 +16,43 @@ impl TemporaryBufferAddText {
         }
     }
 
+    /// Appends `c` to the buffer. `max_length` counts characters, not bytes,
+    /// so multibyte characters do not fill the buffer early.
+    #[allow(clippy::result_unit_err)]
     pub fn add_char(&mut self, c: char) -> Result<EnumAddResult, ()> {
-        if self.buffer.len() >= self.max_length {
+        if self.char_count() >= self.max_length {
             return Err(());
         }
         
         self.buffer.push(c);
         
-        if self.buffer.len() == self.max_length {
+        if self.char_count() == self.max_length {
             Ok(EnumAddResult::MustPersist)
         } else {
             Ok(EnumAddResult::Added)
         }
     }
     
+    /// Returns the number of characters in the buffer.
+    pub fn char_count(&self) -> usize {
+        self.buffer.chars().count()
+    }
+
     pub fn update_position(&mut self, new_position: usize) {
         self.position = new_position;
     }
 
     pub fn delete_char(&mut self) {
-        if self.position > 0 {
-            self.buffer.pop();
+        self.buffer.pop();
+    }
+
+    /// Shifts the buffer's position after the text between `start` and `end` was deleted,
+    /// so the pending text is not inserted at a stale offset.
+    pub fn adjust_position_for_deletion(&mut self, start: usize, end: usize) {
+        if self.position >= end {
+            self.position -= end - start;
+        } else if self.position > start {
+            self.position = start;
         }
     }
 
@@ -49,4 +65,33 @@ impl TemporaryBufferAddText {
         let end = self.position + self.buffer.len();
         cursor_position >= self.position && cursor_position <= end
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_adjust_position_for_deletion() {
+    let mut buffer = TemporaryBufferAddText::new(5, 10);
+
+    buffer.adjust_position_for_deletion(12, 15); // After the buffer
+    assert_eq!(buffer.position, 10);
+
+    buffer.adjust_position_for_deletion(2, 5); // Before the buffer
+    assert_eq!(buffer.position, 7);
+
+    buffer.adjust_position_for_deletion(5, 9); // Around the buffer
+    assert_eq!(buffer.position, 5);
+}
+
+#[test]
+fn test_max_length_counts_characters() {
+    let mut buffer = TemporaryBufferAddText::new(5, 0);
+
+    for c in "áéíó".chars() {
+        assert!(matches!(buffer.add_char(c), Ok(EnumAddResult::Added)));
+    }
+    assert_eq!(buffer.buffer.len(), 8);
+    assert_eq!(buffer.char_count(), 4);
+
+    assert!(matches!(buffer.add_char('ú'), Ok(EnumAddResult::MustPersist)));
+    assert_eq!(buffer.buffer, "áéíóú");
+    assert!(buffer.add_char('x').is_err());
+}
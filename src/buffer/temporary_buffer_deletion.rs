@@ -1,10 +1,14 @@
-use crate::prelude::EnumAddResult;
+use crate::core::word_motion;
+use crate::prelude::{EnumAddResult, WordClassifier};
+#[cfg(test)]
+use crate::prelude::DefaultWordClassifier;
 use crossterm::event::KeyCode; // Add this line or adjust the path to where KeyCode is defined
 
 pub struct TemporaryBufferDeleteText {
     max_length: usize,
     start: Option<usize>,
     end: Option<usize>,
+    origin: Option<usize>,
 }
 
 impl TemporaryBufferDeleteText {
@@ -13,25 +17,35 @@ impl TemporaryBufferDeleteText {
             max_length,
             start: None,
             end: None,
+            origin: None,
         }
     }
 
-    pub fn add_char(&mut self, position: usize, key: KeyCode) -> Result<EnumAddResult, ()> {
+    /// Extends the pending deletion by one character of `text`, the text in the piece table.
+    /// Positions are byte offsets, so a multibyte character is removed as a whole.
+    #[allow(clippy::result_unit_err)]
+    pub fn add_char(&mut self, text: &str, position: usize, key: KeyCode) -> Result<EnumAddResult, ()> {
         if self.start.is_none() {
             if key != KeyCode::Backspace && key != KeyCode::Delete {
                 return Err(());
             }
 
+            self.origin = Some(position);
+
             if key == KeyCode::Backspace {
-                if position == 0 {
+                let Some(width) = char_width_before(text, position) else {
                     return Ok(EnumAddResult::NoChange);
-                }
+                };
 
-                self.start = Some(position - 1);
+                self.start = Some(position - width);
                 self.end = Some(position);
             } else {
+                let Some(width) = char_width_at(text, position) else {
+                    return Ok(EnumAddResult::NoChange);
+                };
+
                 self.start = Some(position);
-                self.end = Some(position + 1);
+                self.end = Some(position + width);
             }
 
             return Ok(EnumAddResult::Added);
@@ -42,84 +56,69 @@ impl TemporaryBufferDeleteText {
 
         if key == KeyCode::Delete {
             // If the delete key is pressed at the end of the current range, extend the end
-            self.end = Some(end + 1);
+            match char_width_at(text, end) {
+                Some(width) => self.end = Some(end + width),
+                None => return Ok(EnumAddResult::NoChange),
+            }
         } else if key == KeyCode::Backspace {
             // If the backspace key is pressed at the start of the current range, extend the start
-            self.start = Some(start - 1);
+            match char_width_before(text, start) {
+                Some(width) => self.start = Some(start - width),
+                None => return Ok(EnumAddResult::NoChange),
+            }
         } else {
             // If neither key is pressed at the correct position, return no change
             return Ok(EnumAddResult::NoChange);
         }
 
-        if self.end.unwrap() - self.start.unwrap() == self.max_length {
+        if self.end.unwrap() - self.start.unwrap() >= self.max_length {
             Ok(EnumAddResult::MustPersist)
         } else {
             Ok(EnumAddResult::Added)
         }
     }
 
+    /// Extends the pending deletion by one word of `text`, the text in the piece table.
+    /// The first call opens a range at `position`; while the range is open, each further call
+    /// extends it by another word, backward from its start or forward from its end.
+    /// `classifier` decides where words end, like it does for the word motions.
+    #[allow(clippy::result_unit_err)]
     pub fn delete_word(
         &mut self,
         text: &str,
         position: usize,
         key: KeyCode,
+        classifier: &dyn WordClassifier,
     ) -> Result<EnumAddResult, ()> {
         if key != KeyCode::Backspace && key != KeyCode::Delete {
             return Err(());
         }
 
+        let (start, end) = self.get_deletion_range().unwrap_or((position, position));
+
         if key == KeyCode::Backspace {
-            if position == 0 {
+            if start == 0 {
                 return Ok(EnumAddResult::NoChange);
             }
-
-            // Find first space before the position
-            let mut start = position;
-            for (i, ch) in text[..position].char_indices().rev() {
-                if ch.is_whitespace() {
-                    start = i;
-                    break;
-                }
-
-                if i == 0 {
-                    start = 0;
-                    break;
-                }
-            }
-
-            self.start = Some(start);
-
-            if self.end.is_none() {
-                self.end = Some(position);
-            }
+            self.start = Some(word_start_before(text, start, classifier));
+            self.end = Some(end);
         } else {
-            if position >= text.len() {
+            if end >= text.len() {
                 return Ok(EnumAddResult::NoChange);
             }
+            self.start = Some(start);
+            self.end = Some(word_end_after(text, end, classifier));
+        }
 
-            // Find first space after the position
-            let mut end = position;
-
-            for (i, ch) in text[..position].char_indices() {
-                if ch.is_whitespace() {
-                    end = i + 1; // Move to the next character after the space because end is exclusive
-                    break;
-                }
-
-                if i == text.len() - 1 {
-                    end = i + 1;
-                    break;
-                }
-            }
-
-            self.end = Some(end);
-
-            if self.start.is_none() {
-                self.start = Some(position);
-            }
+        if self.origin.is_none() {
+            self.origin = Some(position);
         }
 
-        Ok(EnumAddResult::Added)
+        if self.end.unwrap() - self.start.unwrap() >= self.max_length {
+            Ok(EnumAddResult::MustPersist)
+        } else {
+            Ok(EnumAddResult::Added)
+        }
     }
 
     pub fn get_deletion_range(&self) -> Option<(usize, usize)> {
@@ -130,6 +129,11 @@ impl TemporaryBufferDeleteText {
         }
     }
 
+    /// Returns the cursor position at which the current deletion started.
+    pub fn get_origin(&self) -> Option<usize> {
+        self.origin
+    }
+
     pub fn is_empty(&self) -> bool {
         self.start.is_none() && self.end.is_none()
     }
@@ -137,5 +141,137 @@ impl TemporaryBufferDeleteText {
     pub fn clear(&mut self) {
         self.start = None;
         self.end = None;
+        self.origin = None;
+    }
+}
+
+/// Returns the start of the word before `position`, including the whitespace character in front of it,
+/// so the word is deleted together with that whitespace. Right after whitespace, only that character goes.
+fn word_start_before(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let word_start = match text[..position].chars().next_back() {
+        Some(ch) if !ch.is_whitespace() => word_motion::run_start(text, position, classifier),
+        _ => position,
+    };
+    match text[..word_start].chars().next_back() {
+        Some(ch) if ch.is_whitespace() => word_start - ch.len_utf8(),
+        _ => word_start,
     }
 }
+
+/// Returns the position just after the next word and the whitespace character following it, or the end of the text.
+/// Whitespace before the next word is skipped, so it is deleted together with the word.
+fn word_end_after(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let after = &text[position..];
+    let word_start = position + after.len() - after.trim_start().len();
+    let word_end = word_motion::run_end(text, word_start, classifier);
+    match text[word_end..].chars().next() {
+        Some(ch) if ch.is_whitespace() => word_end + ch.len_utf8(),
+        _ => word_end,
+    }
+}
+
+/// Returns the byte length of the character ending at `position`, if any.
+fn char_width_before(text: &str, position: usize) -> Option<usize> {
+    text.get(..position)
+        .and_then(|before| before.chars().next_back())
+        .map(char::len_utf8)
+}
+
+/// Returns the byte length of the character starting at `position`, if any.
+fn char_width_at(text: &str, position: usize) -> Option<usize> {
+    text.get(position..)
+        .and_then(|after| after.chars().next())
+        .map(char::len_utf8)
+}
+
+#[test]
+fn test_add_char_removes_whole_multibyte_characters() {
+    let text = "añb€";
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+
+    buffer.add_char(text, 4, KeyCode::Backspace).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((3, 4)));
+    buffer.add_char(text, 3, KeyCode::Backspace).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((1, 4)));
+
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+    buffer.add_char(text, 4, KeyCode::Delete).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((4, 7)));
+    assert!(matches!(
+        buffer.add_char(text, 4, KeyCode::Delete),
+        Ok(EnumAddResult::NoChange)
+    ));
+}
+
+#[test]
+fn test_delete_word_forward_scans_to_the_right() {
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+    buffer.delete_word("foo bar", 0, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((0, 4)));
+
+    // The last word is deleted up to the end of the text
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+    buffer.delete_word("foo bar", 4, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((4, 7)));
+
+    // Leading whitespace goes along with the next word
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+    buffer.delete_word("foo  bar baz", 3, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((3, 9)));
+
+    let mut buffer = TemporaryBufferDeleteText::new(10);
+    assert!(matches!(
+        buffer.delete_word("foo", 3, KeyCode::Delete, &DefaultWordClassifier),
+        Ok(EnumAddResult::NoChange)
+    ));
+}
+
+#[test]
+fn test_delete_word_stops_at_punctuation() {
+    let text = "call(foo.bar) next";
+    let mut buffer = TemporaryBufferDeleteText::new(100);
+    buffer.delete_word(text, 12, KeyCode::Backspace, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((9, 12)));
+    buffer.delete_word(text, 9, KeyCode::Backspace, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((8, 12)));
+
+    let mut buffer = TemporaryBufferDeleteText::new(100);
+    buffer.delete_word(text, 0, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((0, 4)));
+}
+
+#[test]
+fn test_repeated_delete_word_extends_the_open_range() {
+    let text = "one two three four";
+    let mut buffer = TemporaryBufferDeleteText::new(100);
+    buffer.delete_word(text, 13, KeyCode::Backspace, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((7, 13)));
+    buffer.delete_word(text, 7, KeyCode::Backspace, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((3, 13)));
+    buffer.delete_word(text, 3, KeyCode::Backspace, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((0, 13)));
+    assert!(matches!(
+        buffer.delete_word(text, 0, KeyCode::Backspace, &DefaultWordClassifier),
+        Ok(EnumAddResult::NoChange)
+    ));
+
+    let mut buffer = TemporaryBufferDeleteText::new(100);
+    buffer.delete_word(text, 0, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    buffer.delete_word(text, 0, KeyCode::Delete, &DefaultWordClassifier).unwrap();
+    assert_eq!(buffer.get_deletion_range(), Some((0, 8)));
+    assert_eq!(buffer.get_origin(), Some(0));
+}
+
+#[test]
+fn test_delete_word_persists_at_max_length() {
+    let text = "one two three four";
+    let mut buffer = TemporaryBufferDeleteText::new(8);
+    assert!(matches!(
+        buffer.delete_word(text, 18, KeyCode::Backspace, &DefaultWordClassifier),
+        Ok(EnumAddResult::Added)
+    ));
+    assert!(matches!(
+        buffer.delete_word(text, 13, KeyCode::Backspace, &DefaultWordClassifier),
+        Ok(EnumAddResult::MustPersist)
+    ));
+}
@@ -0,0 +1,65 @@
+/// Text clipboard the editor copies to and pastes from.
+pub trait Clipboard {
+    fn get_text(&mut self) -> Result<String, String>;
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// The system clipboard, through `arboard`.
+/// The connection is opened on first use and kept open, because on X11 the copied text
+/// is only served for as long as the clipboard that set it is alive.
+#[derive(Default)]
+pub struct SystemClipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl SystemClipboard {
+    fn inner(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.inner.is_none() {
+            let clipboard = arboard::Clipboard::new()
+                .map_err(|error| format!("Could not open the clipboard: {}", error))?;
+            self.inner = Some(clipboard);
+        }
+
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        self.inner()?
+            .get_text()
+            .map_err(|error| format!("Could not read the clipboard: {}", error))
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.inner()?
+            .set_text(text)
+            .map_err(|error| format!("Could not write to the clipboard: {}", error))
+    }
+}
+
+/// A clipboard that only lives inside the editor, for tests and terminals without a system clipboard.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    pub text: String,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Ok(self.text.clone())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.text = text.to_string();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_memory_clipboard() {
+    let mut clipboard = MemoryClipboard::default();
+    assert_eq!(clipboard.get_text(), Ok(String::new()));
+
+    clipboard.set_text("copied").unwrap();
+    assert_eq!(clipboard.get_text(), Ok("copied".to_string()));
+}
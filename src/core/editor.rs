@@ -1,13 +1,44 @@
+use crate::core::{tab_stops, word_motion};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use crate::prelude::{
-    EnumAddResult, PieceTable, Position, TemporaryBufferAddText, TemporaryBufferDeleteText, TextAction, TextTrait
+    Clipboard, DefaultHighlighter, EditOp, EditorStats, StatsCounter, EnumAddResult, Highlighter, LineEnding, LineNumberMode, MacroAction, PieceTable, Position, TextEdit, Register, Settings, SplitView, SystemClipboard, Theme, TemporaryBufferAddText, TemporaryBufferDeleteText, TextAction, TextTrait, DefaultWordClassifier, WordClassifier,
+    WordMotion,
 };
 use crossterm::event::KeyCode;
+#[cfg(test)]
+use crate::prelude::{GapBuffer, MemoryClipboard};
+
+/// How long a message set with `set_status` stays in the status area.
+const STATUS_MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Opening and closing character pairs removed together by Backspace when the cursor sits between them.
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// Bracket pairs that `find_matching_bracket` jumps between.
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Marker drawn on the rows below the end of the document, like vim.
+const DEFAULT_EOB_MARKER: char = '~';
+
+/// Number of columns between tab stops used until `set_tab_width` is called.
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Callback for `Editor::on_change`, run with every change committed to the text, in the order the changes are applied.
+/// Each call gets a single `Add` or `Delete`, never a `Group`. Typing and deleting are batched in the
+/// temporary buffers and only reported once those are persisted, on a cursor move, another kind of edit,
+/// a full buffer or `auto_persist_ms` of idle time. Call `Editor::persist_changes` to report them right away.
+pub type ChangeCallback = Box<dyn FnMut(&TextAction)>;
 
 /// Represents a text editor that manages text content using a piece table and temporary buffers.
 /// The editor allows adding, deleting characters, and managing cursor position.
 /// It also maintains a map of lines to their lengths for efficient text rendering.
-pub struct Editor {
-    content: PieceTable,
+pub struct Editor<T: TextTrait = PieceTable> {
+    content: T,
     pub text_position: usize,
     pub temporary_add_buffer: TemporaryBufferAddText,
     pub temporary_delete_buffer: TemporaryBufferDeleteText,
@@ -15,12 +46,102 @@ pub struct Editor {
     right_most_column: u16,
     pub lines_map: Vec<usize>,
     text_actions: Vec<TextAction>,
+    undone_text_actions: Vec<TextAction>,
+    pub search_matches: Vec<(usize, usize)>,
+    pub current_match: Option<usize>,
+    pub search_query: String,
+    pub search_prompt: Option<String>,
+    pub last_search: Option<String>,
+    pub goto_prompt: Option<String>,
+    pub last_match_position: Option<usize>,
+    search_origin: usize,
+    last_edit: Option<EditOp>,
+    pub recording: bool,
+    pub recorded: Vec<MacroAction>,
+    pub eob_marker: Option<char>,
+    pub line_number_mode: LineNumberMode,
+    tab_width: usize,
+    pub expand_tabs: bool,
+    pub auto_indent: bool,
+    pub insert_final_newline: bool,
+    pub trim_trailing_whitespace_on_save: bool,
+    pub max_line_length: Option<usize>,
+    pub debug_buffers: bool,
+    pub read_only: bool,
+    pub selection_anchor: Option<usize>,
+    marks: HashMap<char, usize>,
+    pub register: Option<Register>,
+    pub clipboard: Box<dyn Clipboard>,
+    pub status_message: Option<(String, Instant)>,
+    pub theme: Theme,
+    pub show_whitespace: bool,
+    pub highlighter: Option<Box<dyn Highlighter>>,
+    pub on_change: Option<ChangeCallback>,
+    pub split_view: Option<SplitView>,
+    pub viewport_height: usize,
+    pub row_offset: usize,
+    pub file_path: Option<PathBuf>,
+    pub modified: bool,
+    pub line_ending: LineEnding,
+    pub word_classifier: Box<dyn WordClassifier>,
+    /// How long `auto_persist_if_idle` waits without input before flushing the temporary buffers, `None` to never flush.
+    pub auto_persist_ms: Option<u64>,
+    last_keystroke: Option<Instant>,
 }
 
 impl Editor {
-    /// Creates a new Editor instance with the given initial text and temporary buffer size.
-    /// Initializes the piece table, buffers, cursor position, and line map.
+    /// Creates a new Editor instance with the given initial text and temporary buffer size,
+    /// storing the text in a piece table. See `with_backend` for other storage.
     pub fn new(text: String, temporary_buffer_max_length: usize) -> Self {
+        Self::with_backend(text, temporary_buffer_max_length)
+    }
+
+    /// Creates an editor with the contents of the file at `path`, which becomes the `file_path` to save to.
+    ///
+    /// # Returns
+    /// * `Err` with the IO error if the file cannot be read, e.g. `NotFound` for a missing file.
+    pub fn from_file(path: &Path, temporary_buffer_max_length: usize) -> io::Result<Editor> {
+        let text = fs::read_to_string(path)?;
+        let mut editor = Editor::new(text, temporary_buffer_max_length);
+        editor.file_path = Some(path.to_path_buf());
+        Ok(editor)
+    }
+
+    /// Returns the edits that turn the text the editor was created with into the current text,
+    /// including any typing or deletion still pending in the temporary buffers.
+    /// The loaded text is the one after `\r\n` was normalized to `\n`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut editor = Editor::new("ab".to_string(), 8);
+    /// editor.add_char('c');
+    /// assert_eq!(
+    ///     editor.diff_since_load(),
+    ///     vec![TextEdit { offset: 2, removed_len: 0, inserted: "c".to_string() }]
+    /// );
+    /// ```
+    pub fn diff_since_load(&self) -> Vec<TextEdit> {
+        self.snapshot().diff_from_original()
+    }
+}
+
+impl<T: TextTrait> Editor<T> {
+    /// Creates a new Editor instance with the given initial text and temporary buffer size,
+    /// storing the text in the backend `T`.
+    /// Initializes the backend, buffers, cursor position, and line map.
+    /// `\r\n` line breaks are stored as `\n`, and the dominant line ending is restored on save.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let editor: Editor<GapBuffer> = Editor::with_backend("hello".to_string(), 8);
+    /// assert_eq!(editor.get_text(), "hello");
+    /// ```
+    pub fn with_backend(text: String, temporary_buffer_max_length: usize) -> Self {
+        let line_ending = LineEnding::detect(&text);
+        // Lines are split on '\n' only, so a '\r' left before it would be edited as part of the line
+        let text = text.replace("\r\n", "\n");
         let mut text_position = 0; // Start at the end of the text
 
         if !text.is_empty() {
@@ -29,7 +150,7 @@ impl Editor {
         }
 
         let mut editor = Self {
-            content: PieceTable::new(&text.clone()),
+            content: T::new(&text),
             temporary_add_buffer: TemporaryBufferAddText::new(
                 temporary_buffer_max_length,
                 text_position,
@@ -43,14 +164,55 @@ impl Editor {
             lines_map: Vec::new(),
             right_most_column: 0,
             text_actions: Vec::new(),
+            undone_text_actions: Vec::new(),
+            search_matches: Vec::new(),
+            current_match: None,
+            search_query: String::new(),
+            search_prompt: None,
+            last_search: None,
+            goto_prompt: None,
+            last_match_position: None,
+            search_origin: 0,
+            last_edit: None,
+            recording: false,
+            recorded: Vec::new(),
+            eob_marker: Some(DEFAULT_EOB_MARKER),
+            line_number_mode: LineNumberMode::Off,
+            tab_width: DEFAULT_TAB_WIDTH,
+            expand_tabs: false,
+            auto_indent: true,
+            insert_final_newline: false,
+            trim_trailing_whitespace_on_save: false,
+            max_line_length: None,
+            debug_buffers: false,
+            read_only: false,
+            selection_anchor: None,
+            marks: HashMap::new(),
+            register: None,
+            clipboard: Box::new(SystemClipboard::default()),
+            status_message: None,
+            theme: Theme::default(),
+            show_whitespace: false,
+            highlighter: Some(Box::new(DefaultHighlighter)),
+            on_change: None,
+            split_view: None,
+            viewport_height: 0,
+            row_offset: 0,
+            file_path: None,
+            modified: false,
+            line_ending,
+            word_classifier: Box::new(DefaultWordClassifier),
+            auto_persist_ms: Some(1000),
+            last_keystroke: None,
         };
 
         editor.update_lines_map();
 
+        // An empty document still has one empty line, so the cursor starts at (0, 0)
         let last_line_length = editor.lines_map.last().cloned().unwrap_or(0);
         editor.cursor = Position {
             x: last_line_length as u16,
-            y: editor.lines_map.len() as u16 - 1, // Set cursor to the last line
+            y: editor.lines_map.len().saturating_sub(1) as u16, // Set cursor to the last line
         };
 
         editor
@@ -61,6 +223,27 @@ impl Editor {
     pub fn add_char(&mut self, c: char) {
         use crate::prelude::EnumAddResult;
 
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        // Typing over a selection replaces it
+        self.delete_selection();
+
+        if c == '\n' {
+            self.add_new_line();
+            return;
+        }
+        self.record(MacroAction::InsertChar(c));
+
+        if c == '}' && self.outdent_on_closing_brace() {
+            return;
+        }
+
+        self.clear_search_matches();
+        // Typing is a new edit even before it is persisted, so nothing undone can be redone anymore
+        self.undone_text_actions.clear();
+
         if !self.temporary_delete_buffer.is_empty() {
             self.persist_delete_buffer();
         }
@@ -72,9 +255,12 @@ impl Editor {
         }
 
         let add_result = self.temporary_add_buffer.add_char(c);
+        self.modified = true;
+        self.insert_into_lines_map(self.cursor.y as usize, self.cursor.x as usize, &c.to_string());
 
-        self.text_position += 1;
-        self.cursor.move_right();
+        // Positions are byte offsets, so a multibyte character moves the cursor by its UTF-8 length
+        self.text_position += c.len_utf8();
+        self.cursor.x += c.len_utf8() as u16;
         self.set_right_most_column(self.cursor.x);
 
         // Persist the buffer if AddResult::MustPersist is returned
@@ -83,274 +269,4740 @@ impl Editor {
         }
     }
 
-    /// Returns the current text in the editor, including any temporary buffers.
-    pub fn get_text(&self) -> String {
-        let mut content = self.content.get_text();
+    /// Types a closing brace that is the first non-whitespace character of the line, replacing the
+    /// whitespace before it with the indentation of the line that opened the block, so they line up.
+    /// The whitespace and the brace are replaced in one change, undone in a single step.
+    /// Returns `false` without changing anything when the brace follows other text, there is no open
+    /// block or the indentation already matches, and the brace is then typed as usual.
+    fn outdent_on_closing_brace(&mut self) -> bool {
+        let line = self.get_line(self.cursor.y as usize).unwrap_or_default();
+        let indentation = match line.get(..self.cursor.x as usize) {
+            Some(before_cursor) if before_cursor.chars().all(|c| c == ' ' || c == '\t') => before_cursor,
+            _ => return false,
+        };
 
-        // Insert the temporary buffer at its position if it's not empty
-        if !self.temporary_add_buffer.buffer.is_empty() {
-            let pos = self.temporary_add_buffer.position;
-            content.insert_str(pos, &self.temporary_add_buffer.buffer);
-        } else if !self.temporary_delete_buffer.is_empty() {
-            // If the delete buffer is not empty, we should not show the deleted text
-            if let Some((start, end)) = self.temporary_delete_buffer.get_deletion_range() {
-                content.replace_range(start..end, "");
-            }
+        let block_indentation = match self.open_block_indentation() {
+            Some(block_indentation) if block_indentation != indentation => block_indentation,
+            _ => return false,
+        };
+
+        self.clear_search_matches();
+        self.persist_changes();
+
+        let line_start = self.text_position - indentation.len();
+        let text = format!("{}}}", block_indentation);
+        if self.delete_from_content(line_start, self.text_position).is_err() {
+            return false;
         }
+        let _ = self.add_to_content(&text, line_start);
 
-        content
-    }
+        let line_number = self.cursor.y as usize;
+        self.remove_from_lines_map(line_number, 0, indentation);
+        self.insert_into_lines_map(line_number, 0, &text);
 
-    /// Returns the current text in the editor as a vector of lines.
-    pub fn get_text_lines(&self) -> Vec<String> {
-        self.get_text()
-            .split("\n")
-            .map(|line| line.to_string())
-            .collect::<Vec<String>>()
+        self.text_actions.push(TextAction::Group(vec![
+            TextAction::Delete {
+                text: indentation.to_string(),
+                position: line_start,
+                cursor: self.text_position,
+            },
+            TextAction::Add {
+                text: text.clone(),
+                position: line_start,
+                cursor: line_start,
+            },
+        ]));
+        self.undone_text_actions.clear();
+        self.record_insert_edit("}", line_start + block_indentation.len());
+
+        self.text_position = line_start + text.len();
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+        true
     }
 
-    /// Deletes a character at the current cursor position.
-    /// Handles both the temporary add buffer and the delete buffer, and updates the cursor.
-    pub fn delete_char(&mut self, key: KeyCode) {
-        if self.text_position > 0 {
-            let deleted_position = self.text_position;
+    /// Returns the leading whitespace of the line holding the `{` that is still open at the start
+    /// of the cursor's line, found by matching braces backwards, or `None` if every block is closed.
+    fn open_block_indentation(&self) -> Option<String> {
+        let lines = self.get_text_lines();
+        let mut depth = 0;
 
-            // If the cursor is on the temporary buffer add, remove the character from it at the end
-            if !self.temporary_add_buffer.buffer.is_empty()
-                && self
-                    .temporary_add_buffer
-                    .is_cursor_on_buffer(self.text_position)
-            {
-                self.temporary_add_buffer.delete_char();
-            } else {
-                if let Ok(EnumAddResult::MustPersist) =
-                    self.temporary_delete_buffer.add_char(deleted_position, key)
-                {
-                    // If the delete buffer is full, then delete the text range from the piece table
-                    self.persist_delete_buffer();
+        for line in lines[..(self.cursor.y as usize).min(lines.len())].iter().rev() {
+            for c in line.chars().rev() {
+                match c {
+                    '}' => depth += 1,
+                    '{' if depth == 0 => {
+                        let indentation_length = line.len() - line.trim_start_matches([' ', '\t']).len();
+                        return Some(line[..indentation_length].to_string());
+                    }
+                    '{' => depth -= 1,
+                    _ => {}
                 }
             }
-
-            if key == KeyCode::Backspace {
-                self.text_position -= 1; // Move cursor back before deleting with backspace
-                self.cursor.move_left();
-                self.set_right_most_column(self.cursor.x);
-            }
         }
+
+        None
     }
 
-    /// Deletes a word at the current cursor position.
-    /// Persists the add buffer if needed and updates the cursor and buffers accordingly.
-    pub fn delete_word(&mut self, key: KeyCode) {
-        if !self.temporary_add_buffer.buffer.is_empty() {
-            self.persist_add_buffer(true);
+    /// Inserts a character at the given text position without going through the
+    /// interactive cursor and temporary buffers, e.g. for scripted edits.
+    /// Pending buffers are persisted first, and the cursor only shifts when it is at or after `position`.
+    pub fn insert_char_at(&mut self, c: char, position: usize) {
+        if self.reject_if_read_only() {
+            return;
         }
 
-        let delete_result =
-            self.temporary_delete_buffer
-                .delete_word(&self.get_text(), self.text_position, key);
+        self.clear_search_matches();
+        self.persist_changes();
 
-        if key == KeyCode::Backspace {
-            if let Some((start, _end)) = self.temporary_delete_buffer.get_deletion_range() {
-                self.text_position = start; // Update cursor position to the start of the deletion range
-                self.temporary_add_buffer
-                    .update_position(self.text_position);
-            }
+        let text = c.to_string();
+        if self.add_to_content(&text, position).is_err() {
+            return;
         }
 
-        if let Ok(EnumAddResult::MustPersist) = delete_result {
-            self.persist_delete_buffer();
+        let (line, column) = self.line_and_column_of(position);
+        self.insert_into_lines_map(line, column, &text);
+
+        self.text_actions.push(TextAction::Add {
+            text: text.clone(),
+            position,
+            cursor: self.text_position,
+        });
+        self.undone_text_actions.clear();
+
+        if self.text_position >= position {
+            self.text_position += text.len();
         }
+
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
     }
 
-    /// Moves the cursor one position to the left, updating the text position and line map.
-    pub fn move_cursor_left(&mut self) {
-        if self.text_position > 0 {
-            self.text_position -= 1;
-            self.cursor.move_left();
-            self.set_right_most_column(self.cursor.x);
-            self.do_after_move_cursor();
+    /// Returns the current text in the editor, including any temporary buffers.
+    /// Both buffers hold offsets into the piece table text, so the pending deletion is removed
+    /// first and the add buffer position is shifted to match before its text is spliced in.
+    pub fn get_text(&self) -> String {
+        let mut content = self.content.get_text();
+
+        // Hide the text of a pending deletion
+        if let Some((start, end)) = self.temporary_delete_buffer.get_deletion_range() {
+            content.replace_range(start..end, "");
+        }
+
+        // Insert the temporary buffer at its position if it's not empty
+        if !self.temporary_add_buffer.buffer.is_empty() {
+            content.insert_str(self.pending_add_position(), &self.temporary_add_buffer.buffer);
         }
+
+        content
     }
 
-    /// Moves the cursor one position to the right, updating the text position and line map.
-    pub fn move_cursor_right(&mut self) {
-        if self.text_position < self.content.total_length() {
-            self.text_position += 1;
-            self.cursor.move_right();
-            self.set_right_most_column(self.cursor.x);
-            self.do_after_move_cursor();
+    /// Returns where the pending typing goes in the text once the pending deletion is hidden.
+    fn pending_add_position(&self) -> usize {
+        let add_position = self.temporary_add_buffer.position;
+        match self.temporary_delete_buffer.get_deletion_range() {
+            Some((start, end)) if add_position >= end => add_position - (end - start),
+            Some((start, _)) if add_position > start => start,
+            _ => add_position,
         }
     }
 
-    /// Moves the cursor up by one line, adjusting the x position if necessary.
-    /// Updates the text position and line map.
-    pub fn move_cursor_up(&mut self) {
-        self.cursor.move_up();
-        self.handle_change_of_cursor_y_position();
-        self.do_after_move_cursor();
-        // TODO: Implement logic to move the cursor up in the content by updating the text_position value
+    /// Returns the length in bytes of the text `get_text` returns, without building it.
+    pub fn text_length(&self) -> usize {
+        let deleted = self
+            .temporary_delete_buffer
+            .get_deletion_range()
+            .map_or(0, |(start, end)| end - start);
+        self.content.total_length() - deleted + self.temporary_add_buffer.buffer.len()
     }
 
-    /// Moves the cursor down by one line, adjusting the x position if necessary.
-    /// Updates the text position and line map.
-    pub fn move_cursor_down(&mut self) {
-        self.cursor.move_down();
-        self.handle_change_of_cursor_y_position();
-        self.do_after_move_cursor();
-        // TODO: Implement logic to move the cursor down in the content by updating the text_position value
+    /// Writes the lines in `lines` (0-based) to `w`, each followed by its `\n` except the last line,
+    /// with the pending typing and deletion applied like `get_text`. Only those lines are read from
+    /// the content, so drawing the visible part of a large document does not copy all of it.
+    pub fn write_lines_to<W: io::Write>(&self, w: &mut W, lines: Range<usize>) -> io::Result<()> {
+        let end = if lines.end < self.lines_map.len() {
+            self.line_start_position(lines.end)
+        } else {
+            self.text_length()
+        };
+        let start = self.line_start_position(lines.start).min(end);
+        self.write_range_to(w, start, end)
     }
 
-    /// Ensures the cursor's x position is valid for the current line after moving up or down.
-    /// Adjusts x to the last character if it exceeds the line length.
-    fn handle_change_of_cursor_y_position(&mut self) {
-        let line_index = self.cursor.y as usize;
-        let line_length = self.lines_map.get(line_index).cloned().unwrap_or(0);
-        if line_length < self.cursor.x as usize {
-            // If the cursor x position is greater than the line length, we need to adjust it
-            self.cursor.x = line_length as u16; // Set to the last character of the line
+    /// Writes `start..end` of the text `get_text` returns to `w`: the content before the pending
+    /// typing, the typing itself, then the content after it.
+    fn write_range_to<W: io::Write>(&self, w: &mut W, start: usize, end: usize) -> io::Result<()> {
+        let typed = &self.temporary_add_buffer.buffer;
+        let typed_start = self.pending_add_position();
+        let typed_end = typed_start + typed.len();
+
+        self.write_committed_range_to(w, start.min(typed_start), end.min(typed_start))?;
+        let typed_range = start.clamp(typed_start, typed_end) - typed_start..end.clamp(typed_start, typed_end) - typed_start;
+        w.write_all(typed[typed_range].as_bytes())?;
+        self.write_committed_range_to(w, start.max(typed_end) - typed.len(), end.max(typed_end) - typed.len())
+    }
+
+    /// Writes `start..end` of the content with the pending deletion hidden, skipping over the deleted text.
+    fn write_committed_range_to<W: io::Write>(&self, w: &mut W, start: usize, end: usize) -> io::Result<()> {
+        if start >= end {
+            return Ok(());
+        }
+
+        let (deleted_start, deleted_end) = self
+            .temporary_delete_buffer
+            .get_deletion_range()
+            .unwrap_or((usize::MAX, usize::MAX));
+        let from = if start < deleted_start { start } else { start + (deleted_end - deleted_start) };
+        let to = if end <= deleted_start { end } else { end + (deleted_end - deleted_start) };
+
+        if from < deleted_start && to > deleted_end {
+            self.content.write_range_to(w, from, deleted_start)?;
+            self.content.write_range_to(w, deleted_end, to)
         } else {
-            self.cursor.x = self.right_most_column;
+            self.content.write_range_to(w, from, to)
         }
-        
-        self.update_text_position_after_cursor_move();
     }
 
-    /// Updates the text position after moving the cursor.
-    /// This function recalculates the text position based on the current cursor position.
-    /// It sums the lengths of all lines up to the current line and adds the x position
-    /// of the cursor to get the total character count up to the cursor.
-    fn update_text_position_after_cursor_move(&mut self) {
-        let chars_count_up_to_previous_line: usize = self
-            .lines_map
-            .iter()
-            .take(self.cursor.y as usize)
-            .fold(0, |acc, &line_length| {
-                acc + line_length + 1 // +1 for the newline character
-            });
-        self.text_position = chars_count_up_to_previous_line + self.cursor.x as usize;
-    }
+    /// Returns a copy of the document as a piece table, for versioning or diffing.
+    /// The pending deletion and typing are applied to the copy the same way `get_text` does,
+    /// so the editor's own buffers and undo history are left untouched.
+    pub fn snapshot(&self) -> T
+    where
+        T: Clone,
+    {
+        let mut snapshot = self.content.clone();
 
-    /// Adds a new line at the current cursor position.
-    /// Persists any changes, inserts a newline, updates buffers, and resets the rightmost column.
-    pub fn add_new_line(&mut self) {
-        self.persist_changes();
+        if let Some((start, end)) = self.temporary_delete_buffer.get_deletion_range() {
+            let _ = snapshot.delete_text(start, end);
+        }
 
-        let _ = self.content.add_text(&format!("\n"), self.text_position);
-        self.cursor.move_to_new_line();
-        self.text_position += 1;
-        self.temporary_add_buffer
-            .update_position(self.text_position);
-        self.update_lines_map();
-        self.set_right_most_column(0);
+        if !self.temporary_add_buffer.buffer.is_empty() {
+            let _ = snapshot.add_text(&self.temporary_add_buffer.buffer, self.pending_add_position());
+        }
+
+        snapshot
     }
 
-    /// Persists the contents of the temporary buffer to the piece table.
-    ///
-    /// This function is responsible for flushing the temporary buffer into the main
-    /// piece table storage. If the buffer is empty, it does nothing.
-    ///
-    /// # Parameters
-    /// - `force_save`: If `true`, the buffer will be persisted regardless of its current length.
-    ///   If `false`, the buffer will only be persisted if its length exceeds half of the
-    ///   maximum allowed buffer size. This allows for more efficient batching of edits,
-    ///   reducing the number of write operations to the piece table.
+    /// Returns the current text in the editor as a vector of lines.
     ///
-    /// After persisting, the buffer is cleared and the buffer position is updated to the
-    /// current cursor position.
-    pub fn persist_add_buffer(&mut self, force_save: bool) {
-        if self.temporary_add_buffer.buffer.is_empty() {
-            return;
-        }
+    /// The text is split on `\n`, so a document ending with a newline yields a trailing
+    /// empty line. That line is kept because the cursor can be placed on it; use
+    /// `get_text_lines_with` to drop it.
+    pub fn get_text_lines(&self) -> Vec<String> {
+        self.get_text_lines_with(true)
+    }
 
-        // If the buffer is not empty, we need to persist its content to the piece table
-        if force_save
-            || self.temporary_add_buffer.buffer.len() > self.temporary_add_buffer.max_length / 2
-        {
-            let _ = self.content.add_text(
-                &self.temporary_add_buffer.buffer.clone(),
-                self.temporary_add_buffer.position,
-            );
+    /// Returns the current text in the editor as a vector of lines.
+    /// If `include_trailing_empty_line` is `false` and the text ends with a newline,
+    /// the empty element after that final newline is omitted.
+    pub fn get_text_lines_with(&self, include_trailing_empty_line: bool) -> Vec<String> {
+        let text = self.get_text();
+        let text = match text.strip_suffix('\n') {
+            Some(stripped) if !include_trailing_empty_line => stripped,
+            _ => &text,
+        };
 
-            self.text_actions.push(TextAction::Add {
-                text: self.temporary_add_buffer.buffer.clone(),
-                position: self.temporary_add_buffer.position,
-            });
+        text.split("\n")
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>()
+    }
 
-            self.temporary_add_buffer.clear(self.text_position);
-        }
+    /// Returns the line at the given index without its newline, or `None` if it is out of range.
+    pub fn get_line(&self, line: usize) -> Option<String> {
+        self.get_text_lines().get(line).cloned()
     }
-    
-    /// Undoes the last text change made in the editor.
-    pub fn undo_change(&mut self) {
-        // Persist any changes in the temporary buffers before undoing
-        self.persist_changes();
 
-        if let Some(last_action) = self.text_actions.pop() {
-            match last_action {
-                TextAction::Add { text, position } => {
-                    let _ = self.content.delete_text(position, position + text.len());
-                    self.text_position = position;
-                    self.cursor.x = self.cursor.x.saturating_sub(text.len() as u16);
-                }
-                TextAction::Delete { text, position } => {
-                    let _ = self.content.add_text(&text, position);
-                    self.text_position = position + text.len();
-                    self.cursor.x = self.cursor.x.saturating_add(text.len() as u16);
-                }
-            }
+    /// Returns `true` if the line is empty or contains only whitespace.
+    /// Lines past the end of the document are not considered blank.
+    pub fn is_blank_line(&self, line: usize) -> bool {
+        self.get_line(line)
+            .is_some_and(|text| text.trim().is_empty())
+    }
 
-            self.temporary_add_buffer.update_position(self.text_position);
-            self.set_right_most_column(self.cursor.x);
+    /// Returns `true` if the line under the cursor is blank.
+    pub fn current_line_is_blank(&self) -> bool {
+        self.is_blank_line(self.cursor.y as usize)
+    }
+
+    /// Moves the cursor to the start of the next line that is not blank.
+    /// The cursor stays put when there is no such line below it.
+    pub fn move_to_next_nonblank(&mut self) {
+        let line_count = self.get_text_lines().len();
+        let next_line = (self.cursor.y as usize + 1..line_count).find(|&line| !self.is_blank_line(line));
+
+        if let Some(line) = next_line {
+            self.move_cursor_to(line, 0);
         }
     }
 
-    /// Persists the contents of the temporary delete buffer to the piece table.
-    /// Deletes the text range from the piece table and clears the delete buffer.
-    fn persist_delete_buffer(&mut self) {
-        if let Some((start, end)) = self.temporary_delete_buffer.get_deletion_range() {
-            let text_to_delete = self.content.get_text().get(start..end).unwrap_or_default().to_string();
+    /// Moves the cursor to the start of the previous line that is not blank.
+    /// The cursor stays put when there is no such line above it.
+    pub fn move_to_previous_nonblank(&mut self) {
+        let previous_line = (0..self.cursor.y as usize)
+            .rev()
+            .find(|&line| !self.is_blank_line(line));
 
-            let _ = self.content.delete_text(start, end);
-            
-            self.text_actions.push(TextAction::Delete {
-                text: text_to_delete,
-                position: start,
-            });
+        if let Some(line) = previous_line {
+            self.move_cursor_to(line, 0);
+        }
+    }
 
-            self.temporary_delete_buffer.clear();
+    /// Moves the cursor just past the last character of the current line, ready to append, like vim's `A`.
+    /// On a line followed by a newline this is the position of the newline, on the last line it is the end of the text.
+    pub fn move_to_line_end_for_insert(&mut self) {
+        self.move_cursor_to_line_end();
+    }
+
+    /// Moves the cursor to column 0 of the current line (Home).
+    /// Does nothing when the cursor is already there.
+    pub fn move_cursor_to_line_start(&mut self) {
+        if self.cursor.x == 0 {
+            return;
         }
+
+        self.move_cursor_to(self.cursor.y as usize, 0);
     }
 
-    /// Persists both the add and delete buffers to the piece table.
-    /// Used to flush all temporary changes before certain operations.
-    fn persist_changes(&mut self) {
-        self.persist_add_buffer(true);
-        self.persist_delete_buffer();
+    /// Moves the cursor after the last character of the current line, before its newline (End).
+    pub fn move_cursor_to_line_end(&mut self) {
+        let line = self.cursor.y as usize;
+        let line_length = self.lines_map.get(line).copied().unwrap_or(0);
+        self.move_cursor_to(line, line_length);
     }
 
-    /// Called after every cursor movement.
-    /// Persists any changes in the temporary buffers, updates buffer positions, and updates the line map.
-    fn do_after_move_cursor(&mut self) {
+    /// Returns the number of lines in the document, not counting the empty
+    /// line after a trailing newline, so "a\nb\n" has 2 lines.
+    pub fn line_count(&self) -> usize {
+        self.get_text_lines_with(false).len()
+    }
+
+    /// Removes trailing spaces and tabs from the line under the cursor as a single undoable change.
+    /// A cursor inside the removed whitespace is clamped to the new end of the line.
+    pub fn trim_current_line(&mut self) {
         self.persist_changes();
-        self.temporary_add_buffer
-            .update_position(self.text_position);
-        self.update_lines_map();
+
+        let line_index = self.cursor.y as usize;
+        let line = match self.get_line(line_index) {
+            Some(line) => line,
+            None => return,
+        };
+        let trimmed_length = line.trim_end_matches([' ', '\t']).len();
+        if trimmed_length == line.len() {
+            return;
+        }
+
+        let column = (self.cursor.x as usize).min(trimmed_length);
+        let line_start = self.line_start_position(line_index);
+        self.delete_range(line_start + trimmed_length, line_start + line.len());
+        self.move_cursor_to(line_index, column);
     }
 
-    /// Generates a map of line numbers to their lengths based on the current text.
-    /// Updates the internal lines_map field.
-    fn update_lines_map(&mut self) {
-        // This function updates the lines map based on the current content
-        let mut lines_map: Vec<usize> = Vec::new();
-        for line in self.get_text_lines().into_iter() {
-            lines_map.push(line.len());
+    /// Inserts a copy of the current line below it as one undoable change.
+    /// The cursor moves to the copy, keeping its column.
+    pub fn duplicate_line(&mut self) {
+        if self.reject_if_read_only() {
+            return;
         }
-        self.lines_map = lines_map;
+
+        self.persist_changes();
+        let line_index = self.cursor.y as usize;
+        let column = self.cursor.x as usize;
+        let line = match self.get_line(line_index) {
+            Some(line) => line,
+            None => return,
+        };
+
+        // Inserting "\n" + line at the end of the line also works for a last line without a newline
+        self.move_cursor_to(line_index, line.len());
+        self.insert_str(&format!("\n{}", line));
+        self.move_cursor_to(line_index + 1, column);
     }
 
-    /// Sets the rightmost column value for the cursor.
-    fn set_right_most_column(&mut self, column: u16) {
-        self.right_most_column = column;
+    /// Deletes the current line together with its newline as one undoable change.
+    /// The last line has no newline after it, so the newline before it is removed instead.
+    /// The cursor stays on the same line index, with its column clamped to the new line.
+    pub fn delete_line(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.persist_changes();
+        let line_index = self.cursor.y as usize;
+        let column = self.cursor.x as usize;
+        let line_start = self.line_start_position(line_index);
+        let line_end = line_start + self.lines_map[line_index];
+
+        let (start, end) = if line_index + 1 < self.lines_map.len() {
+            (line_start, line_end + 1)
+        } else {
+            (line_start.saturating_sub(1), line_end)
+        };
+        self.delete_range(start, end);
+
+        let line_index = line_index.min(self.lines_map.len() - 1);
+        self.move_cursor_to(line_index, column.min(self.lines_map[line_index]));
     }
+
+    /// Swaps the current line with the one above it, keeping the cursor on the moved line.
+    /// Does nothing on the first line.
+    pub fn move_line_up(&mut self) {
+        let line_index = self.cursor.y as usize;
+        if line_index == 0 {
+            return;
+        }
+
+        self.swap_with_next_line(line_index - 1, line_index - 1);
+    }
+
+    /// Swaps the current line with the one below it, keeping the cursor on the moved line.
+    /// Does nothing on the last line.
+    pub fn move_line_down(&mut self) {
+        let line_index = self.cursor.y as usize;
+        if line_index + 1 >= self.lines_map.len() {
+            return;
+        }
+
+        self.swap_with_next_line(line_index, line_index + 1);
+    }
+
+    /// Swaps `first_line` with the line after it and moves the cursor to `target_line`, keeping its column.
+    /// Only the text between the start of the first line and the end of the second is replaced,
+    /// so the newline after the second line, or its absence on the last line, stays where it is.
+    /// The swap is recorded as one group, so a single undo puts the lines back.
+    fn swap_with_next_line(&mut self, first_line: usize, target_line: usize) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.clear_search_matches();
+        self.persist_changes();
+        let column = self.cursor.x as usize;
+        let (first, second) = match (self.get_line(first_line), self.get_line(first_line + 1)) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return,
+        };
+
+        let start = self.line_start_position(first_line);
+        let old_text = format!("{}\n{}", first, second);
+        let new_text = format!("{}\n{}", second, first);
+        if self.delete_from_content(start, start + old_text.len()).is_err() {
+            return;
+        }
+        let _ = self.add_to_content(&new_text, start);
+
+        self.remove_from_lines_map(first_line, 0, &old_text);
+        self.insert_into_lines_map(first_line, 0, &new_text);
+
+        self.text_actions.push(TextAction::Group(vec![
+            TextAction::Delete {
+                text: old_text,
+                position: start,
+                cursor: self.text_position,
+            },
+            TextAction::Add {
+                text: new_text,
+                position: start,
+                cursor: start,
+            },
+        ]));
+        self.undone_text_actions.clear();
+
+        self.move_cursor_to(target_line, column);
+    }
+
+    /// Moves the cursor to the very start of the document (Ctrl+Home).
+    pub fn move_to_document_start(&mut self) {
+        self.move_cursor_to(0, 0);
+    }
+
+    /// Moves the cursor past the last character of the document (Ctrl+End).
+    pub fn move_to_document_end(&mut self) {
+        let last_line = self.lines_map.len().saturating_sub(1);
+        let last_line_length = self.lines_map.last().copied().unwrap_or(0);
+        self.move_cursor_to(last_line, last_line_length);
+    }
+
+    /// Returns the number of rows the document renders as, matching `get_text_lines().len()`.
+    /// A trailing newline adds an empty last row and an empty document still has one row.
+    /// The gutter width and the rendered rows use this count.
+    pub fn visual_line_count(&self) -> usize {
+        self.lines_map.len().max(1)
+    }
+
+    /// Returns the number of newlines plus one, or 0 for an empty document,
+    /// so "a\nb" has 2 lines and "a\nb\n" has 3. The status bar shows `stats().lines` instead,
+    /// which is the same count except that an empty document has one line there.
+    pub fn logical_line_count(&self) -> usize {
+        let text = self.get_text();
+        if text.is_empty() {
+            return 0;
+        }
+
+        text.matches('\n').count() + 1
+    }
+
+    /// Deletes a character at the current cursor position.
+    /// Handles both the temporary add buffer and the delete buffer, and updates the cursor.
+    ///
+    /// # Returns
+    /// * `false` if nothing was deleted, e.g. Backspace at the start of the document, leaving the
+    ///   cursor where it was so the screen does not need to be redrawn.
+    pub fn delete_char(&mut self, key: KeyCode) -> bool {
+        if self.reject_if_read_only() {
+            return false;
+        }
+
+        // With a selection, Backspace and Delete remove just the selected text
+        if self.delete_selection() {
+            return true;
+        }
+
+        // Nothing to delete: the redo history, the macro and the search matches stay as they are
+        if key == KeyCode::Delete && self.text_position >= self.get_text().len() {
+            return false;
+        }
+
+        if key == KeyCode::Backspace && self.text_position == 0 {
+            return false;
+        }
+
+        if key == KeyCode::Backspace && self.is_cursor_between_pair() {
+            self.record(MacroAction::Backspace);
+            self.clear_search_matches();
+            self.undone_text_actions.clear();
+            self.delete_pair_around_cursor();
+            return true;
+        }
+
+        if key == KeyCode::Delete && !self.temporary_add_buffer.buffer.is_empty() {
+            // Forward deletes go through the delete buffer, so pending typing must be flushed first
+            self.persist_add_buffer(true);
+        }
+
+        if key == KeyCode::Backspace
+            && !self.temporary_add_buffer.buffer.is_empty()
+            && !self.temporary_add_buffer.is_cursor_on_buffer(self.text_position)
+        {
+            // The add and delete buffers are never pending at the same time
+            self.persist_add_buffer(true);
+        }
+
+        let deleted_position = self.text_position;
+        let width = if key == KeyCode::Backspace {
+            self.char_width_before_cursor()
+        } else {
+            self.char_width_at_cursor()
+        };
+
+        // If the cursor is on the temporary buffer add, remove the character from it at the end
+        if key == KeyCode::Backspace
+            && !self.temporary_add_buffer.buffer.is_empty()
+            && self
+                .temporary_add_buffer
+                .is_cursor_on_buffer(self.text_position)
+        {
+            self.temporary_add_buffer.delete_char();
+        } else {
+            match self
+                .temporary_delete_buffer
+                .add_char(&self.content.get_text(), deleted_position, key)
+            {
+                // If the delete buffer is full, then delete the text range from the piece table
+                Ok(EnumAddResult::MustPersist) => self.persist_delete_buffer(),
+                Ok(EnumAddResult::Added) => {}
+                // Nothing was deleted, so the cursor and the line map must not move either
+                Ok(EnumAddResult::NoChange) | Err(_) => return false,
+            }
+        }
+        self.record(if key == KeyCode::Backspace { MacroAction::Backspace } else { MacroAction::Delete });
+        self.clear_search_matches();
+        self.undone_text_actions.clear();
+        self.modified = true;
+
+        let line = self.cursor.y as usize;
+        let column = self.cursor.x as usize;
+
+        if key == KeyCode::Backspace {
+            self.text_position -= width; // Move cursor back before deleting with backspace
+
+            if column == 0 && line > 0 {
+                // The newline before the cursor was deleted, so the cursor joins the previous line
+                let previous_line_length = self.lines_map[line - 1];
+                self.remove_from_lines_map(line - 1, previous_line_length, "\n");
+                self.cursor.y -= 1;
+                self.cursor.x = previous_line_length as u16;
+            } else {
+                self.lines_map[line] -= width;
+                self.cursor.x -= width as u16;
+            }
+            self.set_right_most_column(self.cursor.x);
+        } else if column >= self.lines_map[line] {
+            // Deleting at the end of the line removes the newline and joins the next line
+            self.remove_from_lines_map(line, column, "\n");
+        } else {
+            self.lines_map[line] -= width;
+        }
+
+        true
+    }
+
+    /// Returns the byte length of the character before the cursor, or 0 at the start of the text.
+    fn char_width_before_cursor(&self) -> usize {
+        let text = self.get_text();
+        text.get(..self.text_position)
+            .and_then(|before| before.chars().next_back())
+            .map_or(0, char::len_utf8)
+    }
+
+    /// Returns the byte length of the character under the cursor, or 0 at the end of the text.
+    fn char_width_at_cursor(&self) -> usize {
+        let text = self.get_text();
+        text.get(self.text_position..)
+            .and_then(|after| after.chars().next())
+            .map_or(0, char::len_utf8)
+    }
+
+    /// Returns `true` if the characters immediately before and after the cursor
+    /// form one of the recognized `PAIRS`, e.g. the cursor is inside `()`.
+    fn is_cursor_between_pair(&self) -> bool {
+        let text = self.get_text();
+        let before = text.get(..self.text_position).and_then(|t| t.chars().next_back());
+        let after = text.get(self.text_position..).and_then(|t| t.chars().next());
+
+        match (before, after) {
+            (Some(open), Some(close)) => PAIRS.contains(&(open, close)),
+            _ => false,
+        }
+    }
+
+    /// Deletes the pair of characters surrounding the cursor in a single change.
+    /// Persists any pending buffers first so the deletion is recorded as one undoable action.
+    fn delete_pair_around_cursor(&mut self) {
+        self.persist_changes();
+
+        let start = self.text_position - 1;
+        let end = self.text_position + 1;
+        let deleted_text = self.content.get_text_range(start, end).unwrap_or_default();
+
+        let _ = self.delete_from_content(start, end);
+        self.text_actions.push(TextAction::Delete {
+            text: deleted_text,
+            position: start,
+            cursor: self.text_position,
+        });
+        self.undone_text_actions.clear();
+        self.record_delete_edit(start, end, self.text_position);
+
+        self.text_position = start;
+        self.cursor.move_left();
+        self.lines_map[self.cursor.y as usize] -= end - start;
+        self.set_right_most_column(self.cursor.x);
+        self.temporary_add_buffer.update_position(self.text_position);
+    }
+
+    /// Deletes a word at the current cursor position.
+    /// Persists the add buffer if needed and updates the cursor and buffers accordingly.
+    pub fn delete_word(&mut self, key: KeyCode) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.clear_search_matches();
+        self.undone_text_actions.clear();
+
+        if !self.temporary_add_buffer.buffer.is_empty() {
+            self.persist_add_buffer(true);
+        }
+
+        // Word deletes keep extending the open range only while the cursor still sits at its start
+        if let Some((start, _)) = self.temporary_delete_buffer.get_deletion_range() {
+            if start != self.text_position {
+                self.persist_delete_buffer();
+            }
+        }
+
+        let delete_result =
+            self.temporary_delete_buffer
+                .delete_word(&self.content.get_text(), self.text_position, key, self.word_classifier.as_ref());
+
+        if key == KeyCode::Backspace {
+            if let Some((start, _end)) = self.temporary_delete_buffer.get_deletion_range() {
+                self.text_position = start; // Update cursor position to the start of the deletion range
+                self.temporary_add_buffer
+                    .update_position(self.text_position);
+            }
+        }
+
+        if let Ok(EnumAddResult::Added | EnumAddResult::MustPersist) = delete_result {
+            self.modified = true;
+        }
+        if let Ok(EnumAddResult::MustPersist) = delete_result {
+            self.persist_delete_buffer();
+        }
+
+        // A word deletion can span several lines, so the line map is rebuilt
+        self.update_lines_map();
+        self.update_cursor_after_text_position_change();
+    }
+
+    /// Returns the text position the given word motion would move the cursor to.
+    pub fn word_motion_target(&self, motion: WordMotion) -> usize {
+        let text = self.get_text();
+        let classifier = self.word_classifier.as_ref();
+        match motion {
+            WordMotion::NextWordStart => {
+                word_motion::next_word_start(&text, self.text_position, classifier)
+            }
+            WordMotion::PreviousWordStart => {
+                word_motion::previous_word_start(&text, self.text_position, classifier)
+            }
+            WordMotion::WordEnd => word_motion::word_end(&text, self.text_position, classifier),
+        }
+    }
+
+    /// Returns the `(start, end)` range an operator combined with the given word motion acts on.
+    /// The range is exclusive at the end, except that `WordEnd` includes the last character of the word.
+    pub fn word_motion_range(&self, motion: WordMotion) -> (usize, usize) {
+        let target = self.word_motion_target(motion);
+        match motion {
+            WordMotion::NextWordStart => (self.text_position, target),
+            WordMotion::PreviousWordStart => (target, self.text_position),
+            WordMotion::WordEnd => {
+                let text = self.get_text();
+                let end = target + text[target..].chars().next().map_or(0, |c| c.len_utf8());
+                (self.text_position, end)
+            }
+        }
+    }
+
+    /// Moves the cursor by the given word motion (vim `w`, `b` or `e`).
+    pub fn move_word_motion(&mut self, motion: WordMotion) {
+        let target = self.word_motion_target(motion);
+        self.persist_changes();
+        self.text_position = target;
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+    }
+
+    /// Deletes the text covered by the given word motion, e.g. `dw` or `db`.
+    pub fn delete_word_motion(&mut self, motion: WordMotion) {
+        let (start, end) = self.word_motion_range(motion);
+        self.delete_range(start, end);
+    }
+
+    /// Deletes from the cursor to the end of the current word and leaves the cursor ready to
+    /// type the replacement, like vim's `cw`. On whitespace the whitespace run is deleted instead.
+    pub fn change_word(&mut self) {
+        self.persist_changes();
+        let end = word_motion::run_end(&self.get_text(), self.text_position, self.word_classifier.as_ref());
+        self.delete_range(self.text_position, end);
+    }
+
+    /// Deletes the text between `start` and `end` (exclusive) as a single undoable change
+    /// and places the cursor at `start`.
+    pub fn delete_range(&mut self, start: usize, end: usize) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.clear_search_matches();
+        self.persist_changes();
+
+        if start >= end {
+            return;
+        }
+
+        let deleted_text = match self.content.get_text_range(start, end) {
+            Ok(text) => text,
+            Err(_) => return,
+        };
+
+        if self.delete_from_content(start, end).is_err() {
+            return;
+        }
+
+        let (line, column) = self.line_and_column_of(start);
+        self.remove_from_lines_map(line, column, &deleted_text);
+
+        self.text_actions.push(TextAction::Delete {
+            text: deleted_text,
+            position: start,
+            cursor: self.text_position,
+        });
+        self.undone_text_actions.clear();
+        self.record_delete_edit(start, end, self.text_position);
+
+        self.text_position = start;
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+    }
+
+    /// Inserts `text` at the cursor as a single undoable change and moves the cursor after it.
+    /// Pending buffers are flushed and the text goes into the piece table in one piece, so pasting a
+    /// large block does not persist the add buffer over and over like typing it through `add_char` would.
+    pub fn insert_str(&mut self, text: &str) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.clear_search_matches();
+        self.persist_changes();
+
+        if text.is_empty() || self.add_to_content(text, self.text_position).is_err() {
+            return;
+        }
+
+        self.text_actions.push(TextAction::Add {
+            text: text.to_string(),
+            position: self.text_position,
+            cursor: self.text_position,
+        });
+        self.undone_text_actions.clear();
+        self.record_insert_edit(text, self.text_position);
+        self.insert_into_lines_map(self.cursor.y as usize, self.cursor.x as usize, text);
+
+        self.text_position += text.len();
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+    }
+
+    /// Replaces every occurrence of `find` with `replace` and returns the number of replacements.
+    /// The replacements are recorded as one group, so a single undo reverts all of them.
+    pub fn replace_all(&mut self, find: &str, replace: &str) -> usize {
+        if self.reject_if_read_only() {
+            return 0;
+        }
+
+        self.clear_search_matches();
+        self.persist_changes();
+
+        if find.is_empty() {
+            return 0;
+        }
+
+        let text = self.content.get_text();
+        let positions: Vec<usize> = text.match_indices(find).map(|(position, _)| position).collect();
+
+        let mut actions = Vec::new();
+
+        // Right to left, so the offsets of the remaining matches are not shifted by earlier replacements
+        for &position in positions.iter().rev() {
+            let cursor = self.text_position;
+            if self.delete_from_content(position, position + find.len()).is_err() {
+                continue;
+            }
+            actions.push(TextAction::Delete {
+                text: find.to_string(),
+                position,
+                cursor,
+            });
+
+            if !replace.is_empty() && self.add_to_content(replace, position).is_ok() {
+                actions.push(TextAction::Add {
+                    text: replace.to_string(),
+                    position,
+                    cursor,
+                });
+            }
+
+            // Keep the cursor on the same text, or at the start of a replacement it was inside of
+            if self.text_position >= position + find.len() {
+                self.text_position = self.text_position - find.len() + replace.len();
+            } else if self.text_position > position {
+                self.text_position = position;
+            }
+        }
+
+        if !actions.is_empty() {
+            self.text_actions.push(TextAction::Group(actions));
+            self.undone_text_actions.clear();
+            self.update_lines_map();
+            self.update_cursor_after_text_position_change();
+            self.temporary_add_buffer
+                .update_position(self.text_position);
+        }
+
+        positions.len()
+    }
+
+    /// Repeats the most recent insertion or deletion at the current cursor position (vim `.`).
+    pub fn repeat_last_edit(&mut self) {
+        self.persist_changes();
+
+        match self.last_edit.clone() {
+            Some(EditOp::Insert { text, .. }) => self.insert_str(&text),
+            Some(EditOp::Delete {
+                before_cursor,
+                after_cursor,
+            }) => {
+                let start = self.text_position.saturating_sub(before_cursor);
+                let end = (self.text_position + after_cursor).min(self.content.total_length());
+                self.delete_range(start, end);
+            }
+            None => {}
+        }
+    }
+
+    /// Records an insertion as the last edit, extending the previous one when the
+    /// text continues right where the last insertion ended (e.g. a typing session
+    /// persisted in several chunks).
+    fn record_insert_edit(&mut self, text: &str, position: usize) {
+        if let Some(EditOp::Insert {
+            text: previous_text,
+            position: previous_position,
+        }) = &mut self.last_edit
+        {
+            if *previous_position + previous_text.len() == position {
+                previous_text.push_str(text);
+                return;
+            }
+        }
+
+        self.last_edit = Some(EditOp::Insert {
+            text: text.to_string(),
+            position,
+        });
+    }
+
+    /// Records a deletion of `start..end` as the last edit, relative to the cursor
+    /// position `origin` where the deletion was made.
+    fn record_delete_edit(&mut self, start: usize, end: usize, origin: usize) {
+        let origin = origin.clamp(start, end);
+        self.last_edit = Some(EditOp::Delete {
+            before_cursor: origin - start,
+            after_cursor: end - origin,
+        });
+    }
+
+    /// Starts a selection anchored at the cursor, unless one is already active.
+    /// Moving the cursor afterwards extends the selection from the anchor.
+    pub fn start_selection(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_position);
+        }
+    }
+
+    /// Selects the whole document, anchoring the selection at the start and moving the cursor to the end.
+    pub fn select_all(&mut self) {
+        // Flush first so the length includes text still in the temporary buffers
+        self.persist_changes();
+        self.move_cursor_to_position(self.content.total_length());
+        self.selection_anchor = Some(0);
+    }
+
+    /// Clears the active selection without changing the text.
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Returns the selected `(start, end)` range, or `None` if nothing is selected.
+    pub fn selected_range(&self) -> Option<(usize, usize)> {
+        match self.selection_anchor {
+            Some(anchor) if anchor != self.text_position => {
+                Some((anchor.min(self.text_position), anchor.max(self.text_position)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Deletes the selected text as one undoable change, leaving the cursor at the start of the selection.
+    /// Returns `false` when nothing is selected. The selection is cleared either way.
+    pub fn delete_selection(&mut self) -> bool {
+        let selected_range = self.selected_range();
+        self.clear_selection();
+
+        match selected_range {
+            Some((start, end)) => {
+                self.delete_range(start, end);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copies the selected text into the register and the clipboard so it can be pasted inline.
+    /// Sets a status hint and returns `false` when nothing is selected.
+    /// A clipboard error is reported in the status line, the register still gets the text.
+    pub fn copy_selection(&mut self) -> bool {
+        self.persist_changes();
+
+        let selected_text = self
+            .selected_range()
+            .and_then(|(start, end)| self.content.get_text_range(start, end).ok());
+
+        match selected_text {
+            Some(text) => {
+                if let Err(error) = self.clipboard.set_text(&text) {
+                    self.set_status(error);
+                }
+                self.register = Some(Register::Inline(text));
+                true
+            }
+            None => {
+                self.set_status("Nothing selected to copy");
+                false
+            }
+        }
+    }
+
+    /// Joins every line touched by the selection into a single line.
+    /// Each join trims the whitespace around the line break and replaces it with one space.
+    /// The cursor is placed at the end of the joined line and the selection is cleared.
+    pub fn join_selected_lines(&mut self) {
+        let (start, end) = match self.selected_range() {
+            Some(range) => range,
+            None => return,
+        };
+
+        self.persist_changes();
+        let (first_line, _) = self.line_and_column_of(start);
+        let (last_line, _) = self.line_and_column_of(end);
+        if first_line == last_line {
+            return;
+        }
+
+        let lines = self.get_text_lines();
+        let mut joined = lines[first_line].trim_end().to_string();
+        for line in &lines[first_line + 1..=last_line] {
+            let line = line.trim();
+            if !line.is_empty() {
+                if !joined.is_empty() {
+                    joined.push(' ');
+                }
+                joined.push_str(line);
+            }
+        }
+
+        let range_start = self.line_start_position(first_line);
+        let range_end = self.line_start_position(last_line) + self.lines_map[last_line];
+        self.replace_range(range_start, range_end, &joined);
+        self.clear_selection();
+    }
+
+    /// Joins the current line with the next one, replacing the newline and the next line's leading
+    /// whitespace with a single space (or nothing when the next line is blank).
+    /// The cursor lands at the join point. Does nothing on the last line.
+    pub fn join_lines(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.persist_changes();
+        let line_index = self.cursor.y as usize;
+        if line_index + 1 >= self.lines_map.len() {
+            return;
+        }
+
+        let next_line = self.get_line(line_index + 1).unwrap_or_default();
+        let indentation = next_line.len() - next_line.trim_start_matches([' ', '\t']).len();
+        let separator = if indentation == next_line.len() { "" } else { " " };
+
+        let join_column = self.lines_map[line_index];
+        let newline = self.line_start_position(line_index) + join_column;
+        self.replace_range(newline, newline + 1 + indentation, separator);
+        self.move_cursor_to(line_index, join_column);
+    }
+
+    /// Replaces the selected text with `text`, or inserts it at the cursor when nothing is selected.
+    /// The cursor ends up after the inserted text and the selection is cleared.
+    /// This is the primitive that pasting and other programmatic edits build on.
+    pub fn replace_selection_with(&mut self, text: &str) {
+        match self.selected_range() {
+            Some((start, end)) => self.replace_range(start, end, text),
+            None => self.insert_str(text),
+        }
+        self.clear_selection();
+    }
+
+    /// Replaces the text between `start` and `end` with `text`, leaving the cursor after it.
+    fn replace_range(&mut self, start: usize, end: usize, text: &str) {
+        self.delete_range(start, end);
+        self.insert_str(text);
+    }
+
+    /// Copies the line under the cursor into the register as a whole line (vim `yy`).
+    pub fn yank_line(&mut self) {
+        self.persist_changes();
+        if let Some(line) = self.get_line(self.cursor.y as usize) {
+            self.register = Some(Register::Linewise(line));
+        }
+    }
+
+    /// Pastes the register after the cursor (vim `p`).
+    /// Line-wise text becomes its own line below the current one, inline text is inserted at the cursor.
+    pub fn paste(&mut self) {
+        match self.register.clone() {
+            Some(Register::Linewise(text)) => {
+                let line = self.cursor.y as usize;
+                let line_length = self.lines_map.get(line).copied().unwrap_or(0);
+                self.move_cursor_to(line, line_length);
+                self.insert_str(&format!("\n{}", text));
+                self.move_cursor_to(line + 1, 0);
+            }
+            Some(Register::Inline(text)) => self.replace_selection_with(&text),
+            None => {}
+        }
+    }
+
+    /// Pastes the clipboard text over the selection, or at the cursor, as a single insertion.
+    /// Windows line endings are converted, since the document uses `\n` only.
+    pub fn paste_from_clipboard(&mut self) {
+        match self.clipboard.get_text() {
+            Ok(text) => self.replace_selection_with(&text.replace("\r\n", "\n")),
+            Err(error) => self.set_status(error),
+        }
+    }
+
+    /// Pastes the register before the cursor (vim `P`).
+    /// Line-wise text becomes its own line above the current one, inline text is inserted at the cursor.
+    pub fn paste_above(&mut self) {
+        match self.register.clone() {
+            Some(Register::Linewise(text)) => {
+                let line = self.cursor.y as usize;
+                self.move_cursor_to(line, 0);
+                self.insert_str(&format!("{}\n", text));
+                self.move_cursor_to(line, 0);
+            }
+            Some(Register::Inline(text)) => self.replace_selection_with(&text),
+            None => {}
+        }
+    }
+
+    /// Returns the text position where the given line starts.
+    pub(crate) fn line_start_position(&self, line: usize) -> usize {
+        self.lines_map
+            .iter()
+            .take(line)
+            .fold(0, |acc, &line_length| acc + line_length + 1) // +1 for the newline character
+    }
+
+    /// Moves the cursor one position to the left, updating the text position and line map.
+    pub fn move_cursor_left(&mut self) {
+        self.record(MacroAction::MoveLeft);
+        if self.text_position > 0 {
+            if self.cursor.x == 0 {
+                // At the start of a line, step back over the newline to the end of the previous line
+                self.text_position -= 1;
+                self.cursor.y -= 1;
+                self.cursor.x = self.lines_map[self.cursor.y as usize] as u16;
+            } else {
+                let width = self.char_width_before_cursor();
+                self.text_position -= width;
+                self.cursor.x = self.cursor.x.saturating_sub(width as u16);
+            }
+            self.set_right_most_column(self.cursor.x);
+            self.do_after_move_cursor();
+        }
+    }
+
+    /// Moves the cursor one position to the right, updating the text position and line map.
+    pub fn move_cursor_right(&mut self) {
+        self.record(MacroAction::MoveRight);
+        if self.text_position < self.get_text().len() {
+            let line_length = self.lines_map[self.cursor.y as usize];
+            if self.cursor.x as usize >= line_length {
+                // At the end of a line, step over the newline to the start of the next line
+                self.text_position += 1;
+                self.cursor.y += 1;
+                self.cursor.x = 0;
+            } else {
+                let width = self.char_width_at_cursor();
+                self.text_position += width;
+                self.cursor.x += width as u16;
+            }
+            self.set_right_most_column(self.cursor.x);
+            self.do_after_move_cursor();
+        }
+    }
+
+    /// Moves the cursor up by one line, keeping the column it was moved to last
+    /// when the line is long enough. Does nothing on the first line.
+    pub fn move_cursor_up(&mut self) {
+        self.record(MacroAction::MoveUp);
+        if self.cursor.y > 0 {
+            self.move_cursor_to_line(self.cursor.y as usize - 1);
+        }
+    }
+
+    /// Moves the cursor down by one line, keeping the column it was moved to last
+    /// when the line is long enough. Does nothing on the last line.
+    pub fn move_cursor_down(&mut self) {
+        self.record(MacroAction::MoveDown);
+        if (self.cursor.y as usize) + 1 < self.lines_map.len() {
+            self.move_cursor_to_line(self.cursor.y as usize + 1);
+        }
+    }
+
+    /// Moves the cursor up by one screen of `viewport_height` lines, stopping at the first line.
+    pub fn page_up(&mut self) {
+        let line = (self.cursor.y as usize).saturating_sub(self.viewport_height.max(1));
+        self.move_cursor_to_line(line);
+    }
+
+    /// Moves the cursor down by one screen of `viewport_height` lines, stopping at the last line.
+    pub fn page_down(&mut self) {
+        let last_line = self.lines_map.len().saturating_sub(1);
+        let line = (self.cursor.y as usize + self.viewport_height.max(1)).min(last_line);
+        self.move_cursor_to_line(line);
+    }
+
+    /// Moves the cursor to the character drawn at `visual_column` on `line`, e.g. for a mouse click.
+    /// The line is clamped to the last line and the column to the end of the line.
+    pub fn move_cursor_to_visual_position(&mut self, line: usize, visual_column: usize) {
+        let line = line.min(self.lines_map.len().saturating_sub(1));
+        let text = self.get_line(line).unwrap_or_default();
+        let column = tab_stops::column_at_visual(&text, visual_column, self.tab_width);
+        self.move_cursor_to(line, column);
+    }
+
+    /// Returns the position of the bracket matching the one at `position`, skipping nested pairs
+    /// of the same kind. Returns `None` if there is no bracket at `position` or it is unmatched.
+    /// Reads the piece table, so pending buffers should be persisted first.
+    pub fn find_matching_bracket(&self, position: usize) -> Option<usize> {
+        let bracket = self.content.char_at(position)?;
+        let mut depth = 0;
+
+        if let Some(&(open, close)) = BRACKETS.iter().find(|(open, _)| *open == bracket) {
+            let mut current = position;
+            while let Some(c) = self.content.char_at(current) {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(current);
+                    }
+                }
+                current += c.len_utf8();
+            }
+        } else if let Some(&(open, close)) = BRACKETS.iter().find(|(_, close)| *close == bracket) {
+            // Walk back byte by byte, `char_at` skips the inside of multibyte characters
+            for current in (0..=position).rev() {
+                match self.content.char_at(current) {
+                    Some(c) if c == close => depth += 1,
+                    Some(c) if c == open => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(current);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Moves the cursor to the bracket matching the one under it, like vim's `%`.
+    /// The cursor stays put when there is no matching bracket.
+    pub fn jump_to_matching_bracket(&mut self) {
+        self.persist_changes();
+        if let Some(position) = self.find_matching_bracket(self.text_position) {
+            self.move_cursor_to_position(position);
+        }
+    }
+
+    /// Moves the cursor to the 1-based line number `line`, clamped to the first and last line.
+    /// The column is kept where possible, like vertical movement.
+    pub fn goto_line(&mut self, line: usize) {
+        let last_line = self.lines_map.len().saturating_sub(1);
+        self.move_cursor_to_line(line.saturating_sub(1).min(last_line));
+    }
+
+    /// Opens the go-to-line prompt with an empty line number.
+    pub fn start_goto_line(&mut self) {
+        self.goto_prompt = Some(String::new());
+    }
+
+    /// Appends a digit to the line number in the go-to-line prompt. Anything else is ignored.
+    pub fn push_goto_digit(&mut self, c: char) {
+        if let Some(number) = self.goto_prompt.as_mut().filter(|_| c.is_ascii_digit()) {
+            number.push(c);
+        }
+    }
+
+    /// Removes the last digit from the go-to-line prompt.
+    pub fn pop_goto_digit(&mut self) {
+        if let Some(number) = self.goto_prompt.as_mut() {
+            number.pop();
+        }
+    }
+
+    /// Closes the go-to-line prompt and jumps to the entered line, if any.
+    pub fn confirm_goto_line(&mut self) {
+        if let Some(Ok(line)) = self.goto_prompt.take().map(|number| number.parse::<usize>()) {
+            self.goto_line(line);
+        }
+    }
+
+    /// Closes the go-to-line prompt without moving the cursor.
+    pub fn cancel_goto_line(&mut self) {
+        self.goto_prompt = None;
+    }
+
+    /// Scrolls the viewport so the cursor line is inside `row_offset..row_offset + viewport_height`.
+    pub fn scroll_to_cursor(&mut self) {
+        let cursor_line = self.cursor.y as usize;
+        let viewport_height = self.viewport_height.max(1);
+
+        if cursor_line < self.row_offset {
+            self.row_offset = cursor_line;
+        } else if cursor_line >= self.row_offset + viewport_height {
+            self.row_offset = cursor_line + 1 - viewport_height;
+        }
+    }
+
+    /// Updates the height of the text area after the terminal was resized and scrolls so the
+    /// cursor line stays visible. A split view scrolls its panes separately.
+    pub fn resize(&mut self, viewport_height: usize) {
+        self.viewport_height = viewport_height;
+        self.scroll_to_cursor();
+    }
+
+    /// Moves the cursor to `line` for vertical movement, recomputing the text position from the line map.
+    /// Unlike `move_cursor_to`, the column comes from `right_most_column`, so passing through
+    /// shorter lines does not lose the column the cursor started in.
+    fn move_cursor_to_line(&mut self, line: usize) {
+        self.cursor.y = line as u16;
+        self.handle_change_of_cursor_y_position();
+        self.do_after_move_cursor();
+    }
+
+    /// Places the cursor on `right_most_column`, clamped to the length of the current line,
+    /// and updates the text position. `right_most_column` itself is left unchanged.
+    fn handle_change_of_cursor_y_position(&mut self) {
+        let line_index = self.cursor.y as usize;
+        let line_length = self.lines_map.get(line_index).cloned().unwrap_or(0);
+        self.cursor.x = self.right_most_column.min(line_length as u16);
+
+        // Columns are byte offsets, so step back out of a multibyte character
+        let line = self.get_line(line_index).unwrap_or_default();
+        while !line.is_char_boundary(self.cursor.x as usize) {
+            self.cursor.x -= 1;
+        }
+
+        self.update_text_position_after_cursor_move();
+    }
+
+    /// Updates the text position after moving the cursor.
+    /// This function recalculates the text position based on the current cursor position.
+    /// It sums the lengths of all lines up to the current line and adds the x position
+    /// of the cursor to get the total character count up to the cursor.
+    fn update_text_position_after_cursor_move(&mut self) {
+        let chars_count_up_to_previous_line: usize = self
+            .lines_map
+            .iter()
+            .take(self.cursor.y as usize)
+            .fold(0, |acc, &line_length| {
+                acc + line_length + 1 // +1 for the newline character
+            });
+        self.text_position = chars_count_up_to_previous_line + self.cursor.x as usize;
+    }
+
+    /// Updates the cursor after the text position changed.
+    /// This is the inverse of `update_text_position_after_cursor_move`: it walks the
+    /// line map to find the line containing the text position and the column within it.
+    fn update_cursor_after_text_position_change(&mut self) {
+        let (line, column) = self.line_and_column_of(self.text_position);
+        self.cursor.y = line as u16;
+        self.cursor.x = column as u16;
+        self.set_right_most_column(self.cursor.x);
+    }
+
+    /// Returns the line and column of a text position according to the line map.
+    fn line_and_column_of(&self, position: usize) -> (usize, usize) {
+        let mut line_start = 0;
+        for (line_index, &line_length) in self.lines_map.iter().enumerate() {
+            let is_last_line = line_index == self.lines_map.len() - 1;
+            if position <= line_start + line_length || is_last_line {
+                return (line_index, position - line_start);
+            }
+            line_start += line_length + 1; // +1 for the newline character
+        }
+
+        (0, position)
+    }
+
+    /// Adds a new line at the current cursor position.
+    /// Persists any changes, inserts a newline, updates buffers, and resets the rightmost column.
+    /// With `auto_indent`, the new line starts with the leading whitespace of the current line.
+    pub fn add_new_line(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+        self.record(MacroAction::NewLine);
+
+        self.clear_search_matches();
+        self.persist_changes();
+
+        let indentation = if self.auto_indent {
+            self.current_line_indentation()
+        } else {
+            String::new()
+        };
+        let text = format!("\n{}", indentation);
+
+        if self.add_to_content(&text, self.text_position).is_err() {
+            return;
+        }
+        self.text_actions.push(TextAction::Add {
+            text: text.clone(),
+            position: self.text_position,
+            cursor: self.text_position,
+        });
+        self.undone_text_actions.clear();
+        self.record_insert_edit(&text, self.text_position);
+        self.insert_into_lines_map(self.cursor.y as usize, self.cursor.x as usize, &text);
+        self.cursor.move_to_new_line();
+        self.cursor.x = indentation.len() as u16;
+        self.text_position += text.len();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+        self.set_right_most_column(self.cursor.x);
+    }
+
+    /// Returns the leading whitespace of the cursor line, up to the cursor.
+    fn current_line_indentation(&self) -> String {
+        let line_start = self.line_start_position(self.cursor.y as usize);
+        let line_end = line_start + self.cursor.x as usize;
+        let before_cursor = self.content.get_text_range(line_start, line_end).unwrap_or_default();
+        let indentation_length = before_cursor.len() - before_cursor.trim_start().len();
+        before_cursor[..indentation_length].to_string()
+    }
+
+    /// Shows `message` in the status area until `STATUS_MESSAGE_TIMEOUT` has passed.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), Instant::now()));
+    }
+
+    /// Returns the status message, or `None` once it has been shown for `STATUS_MESSAGE_TIMEOUT`.
+    pub fn status_text(&self, now: Instant) -> Option<&str> {
+        self.status_message
+            .as_ref()
+            .filter(|(_, set_at)| now.saturating_duration_since(*set_at) < STATUS_MESSAGE_TIMEOUT)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// Clears the status message once it has expired.
+    /// Returns `true` if a message was cleared, so the screen needs a redraw.
+    pub fn expire_status_message(&mut self, now: Instant) -> bool {
+        if self.status_message.is_some() && self.status_text(now).is_none() {
+            self.status_message = None;
+            return true;
+        }
+        false
+    }
+
+    /// Writes the document to `file_path`, flushing the temporary buffers first.
+    /// Adds a final newline when `insert_final_newline` is set. On success `modified` is cleared
+    /// and the status area reports how many bytes were written.
+    ///
+    /// # Returns
+    /// * `Err` if the editor has no file path or the file cannot be written.
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = self.file_path.clone().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No file name, start the editor with --file")
+        })?;
+
+        self.persist_changes();
+        let mut text = self.content.get_text();
+        if self.trim_trailing_whitespace_on_save {
+            // Only the written text is trimmed, the document keeps the whitespace until it is edited
+            text = trim_trailing_whitespace(&text);
+        }
+        if self.insert_final_newline && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        if self.line_ending == LineEnding::CrLf {
+            text = text.replace('\n', LineEnding::CrLf.as_str());
+        }
+
+        fs::write(path, &text)?;
+        self.modified = false;
+        self.set_status(format!("Saved {} bytes", text.len()));
+        Ok(())
+    }
+
+    /// Returns the number of columns between tab stops.
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Changes the number of columns between tab stops.
+    /// Rendering and the cursor's screen column use the new width from the next refresh.
+    ///
+    /// # Returns
+    /// * `Err(String)` if `width` is 0, leaving the current width unchanged.
+    pub fn set_tab_width(&mut self, width: usize) -> Result<(), String> {
+        if width == 0 {
+            return Err("Tab width must be at least 1".to_string());
+        }
+
+        self.tab_width = width;
+        Ok(())
+    }
+
+    /// Applies the settings read from a config file, keeping the current value of every unset key.
+    pub fn apply_settings(&mut self, settings: &Settings) -> Result<(), String> {
+        if let Some(tab_width) = settings.tab_width {
+            self.set_tab_width(tab_width)?;
+        }
+        if let Some(expand_tabs) = settings.expand_tabs {
+            self.expand_tabs = expand_tabs;
+        }
+        if let Some(insert_final_newline) = settings.insert_final_newline {
+            self.insert_final_newline = insert_final_newline;
+        }
+        if settings.max_line_length.is_some() {
+            self.max_line_length = settings.max_line_length;
+        }
+        if let Some(auto_persist_ms) = settings.auto_persist_ms {
+            // A timeout of 0 turns idle persistence off
+            self.auto_persist_ms = Some(auto_persist_ms).filter(|&ms| ms > 0);
+        }
+        if let Some(auto_indent) = settings.auto_indent {
+            self.auto_indent = auto_indent;
+        }
+        if let Some(trim_trailing_whitespace) = settings.trim_trailing_whitespace {
+            self.trim_trailing_whitespace_on_save = trim_trailing_whitespace;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the character, word, line and byte counts of the document, including pending typing.
+    /// The text is counted as it is written out piece by piece, without building it in a `String`.
+    pub fn stats(&self) -> EditorStats {
+        let mut counter = StatsCounter::default();
+        // Writing to the counter cannot fail, and the range is the whole text
+        let _ = self.write_range_to(&mut counter, 0, self.text_length());
+        counter.finish()
+    }
+
+    /// Returns the on-screen column of the cursor, with tabs expanded to the next tab stop.
+    pub fn cursor_screen_x(&self) -> u16 {
+        let line = self
+            .get_text_lines()
+            .get(self.cursor.y as usize)
+            .cloned()
+            .unwrap_or_default();
+        tab_stops::visual_column(&line, self.cursor.x as usize, self.tab_width) as u16
+    }
+
+    /// Inserts a tab at the cursor. With `expand_tabs` it inserts spaces up to the next tab stop instead.
+    pub fn insert_tab(&mut self) {
+        if !self.expand_tabs {
+            self.add_char('\t');
+            return;
+        }
+
+        let spaces = self.tab_width - self.cursor_screen_x() as usize % self.tab_width;
+        for _ in 0..spaces {
+            self.add_char(' ');
+        }
+    }
+
+    /// Indents every line touched by the selection by `tab_width` spaces, or the cursor line when
+    /// nothing is selected. Empty lines are left alone so they do not gain trailing whitespace.
+    /// Afterwards the selection covers the whole indented lines.
+    pub fn indent_selection(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.persist_changes();
+        let has_selection = self.selected_range().is_some();
+        let column = self.cursor.x as usize;
+        let (first_line, last_line) = self.selected_lines();
+        let indentation = " ".repeat(self.tab_width);
+
+        // Bottom to top, so the offsets of the lines still to indent do not move
+        for line_index in (first_line..=last_line).rev() {
+            if self.lines_map[line_index] > 0 {
+                self.move_cursor_to(line_index, 0);
+                self.insert_str(&indentation);
+            }
+        }
+
+        if has_selection {
+            self.select_lines(first_line, last_line);
+        } else {
+            let indented = if self.lines_map[first_line] > 0 { self.tab_width } else { 0 };
+            self.move_cursor_to(first_line, column + indented);
+        }
+    }
+
+    /// Removes one level of indentation from every line touched by the selection, or the cursor line
+    /// when nothing is selected: a leading tab, or up to `tab_width` leading spaces.
+    /// Lines without leading whitespace are unchanged.
+    pub fn unindent_selection(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.persist_changes();
+        let has_selection = self.selected_range().is_some();
+        let column = self.cursor.x as usize;
+        let (first_line, last_line) = self.selected_lines();
+        let mut removed = 0;
+
+        // Bottom to top, so the offsets of the lines still to unindent do not move
+        for line_index in (first_line..=last_line).rev() {
+            let line = self.get_line(line_index).unwrap_or_default();
+            removed = if line.starts_with('\t') {
+                1
+            } else {
+                line.len() - line.trim_start_matches(' ').len()
+            }
+            .min(self.tab_width);
+
+            if removed > 0 {
+                let line_start = self.line_start_position(line_index);
+                self.delete_range(line_start, line_start + removed);
+            }
+        }
+
+        if has_selection {
+            self.select_lines(first_line, last_line);
+        } else {
+            self.move_cursor_to(first_line, column.saturating_sub(removed));
+        }
+    }
+
+    /// Returns the first and last line touched by the selection, or the cursor line when nothing
+    /// is selected. A selection ending at the start of a line does not include that line.
+    fn selected_lines(&self) -> (usize, usize) {
+        match self.selected_range() {
+            Some((start, end)) => {
+                let (first_line, _) = self.line_and_column_of(start);
+                let (last_line, column) = self.line_and_column_of(end);
+                if column == 0 && last_line > first_line {
+                    (first_line, last_line - 1)
+                } else {
+                    (first_line, last_line)
+                }
+            }
+            None => (self.cursor.y as usize, self.cursor.y as usize),
+        }
+    }
+
+    /// Selects from the start of `first_line` to the end of `last_line`, with the cursor at the end.
+    fn select_lines(&mut self, first_line: usize, last_line: usize) {
+        self.move_cursor_to(last_line, self.lines_map[last_line]);
+        self.selection_anchor = Some(self.line_start_position(first_line));
+    }
+
+    /// Starts recording a macro, replacing the previously recorded one.
+    pub fn start_recording(&mut self) {
+        self.recorded.clear();
+        self.recording = true;
+        self.set_status("Recording macro");
+    }
+
+    /// Stops recording, keeping the recorded actions for `replay`.
+    pub fn stop_recording(&mut self) {
+        self.recording = false;
+        self.set_status(format!("Recorded {} actions", self.recorded.len()));
+    }
+
+    /// Applies the recorded actions in order, starting from the current cursor position.
+    /// Replaying while recording stops the recording first, so a macro never replays into itself.
+    pub fn replay(&mut self) {
+        if self.recording {
+            self.stop_recording();
+        }
+
+        for action in self.recorded.clone() {
+            match action {
+                MacroAction::InsertChar(c) => self.add_char(c),
+                MacroAction::NewLine => self.add_new_line(),
+                MacroAction::Backspace => {
+                    self.delete_char(KeyCode::Backspace);
+                }
+                MacroAction::Delete => {
+                    self.delete_char(KeyCode::Delete);
+                }
+                MacroAction::MoveLeft => self.move_cursor_left(),
+                MacroAction::MoveRight => self.move_cursor_right(),
+                MacroAction::MoveUp => self.move_cursor_up(),
+                MacroAction::MoveDown => self.move_cursor_down(),
+            }
+        }
+    }
+
+    /// Appends `action` to the macro being recorded, if any.
+    fn record(&mut self, action: MacroAction) {
+        if self.recording {
+            self.recorded.push(action);
+        }
+    }
+
+    /// Shows or hides spaces and tabs as `·` and `→` glyphs. Only the rendering changes.
+    pub fn toggle_show_whitespace(&mut self) {
+        self.show_whitespace = !self.show_whitespace;
+    }
+
+    /// Switches to the next line-number mode: off, absolute, relative, hybrid.
+    pub fn toggle_line_number_mode(&mut self) {
+        self.line_number_mode = self.line_number_mode.next();
+    }
+
+    /// Switches the line ending the document is saved with between LF and CRLF.
+    pub fn toggle_line_ending(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.line_ending = self.line_ending.toggle();
+        self.modified = true;
+        self.set_status(format!("Line endings: {}", self.line_ending.name()));
+    }
+
+    /// Opens a horizontal split of the document, or closes it when it is already open.
+    pub fn toggle_split_view(&mut self) {
+        self.split_view = match self.split_view {
+            Some(_) => None,
+            None => Some(SplitView::new()),
+        };
+    }
+
+    /// Persists the contents of the temporary buffer to the piece table.
+    ///
+    /// This function is responsible for flushing the temporary buffer into the main
+    /// piece table storage. If the buffer is empty, it does nothing.
+    ///
+    /// # Parameters
+    /// - `force_save`: If `true`, the buffer will be persisted regardless of its current length.
+    ///   If `false`, the buffer will only be persisted if its length exceeds half of the
+    ///   maximum allowed buffer size. This allows for more efficient batching of edits,
+    ///   reducing the number of write operations to the piece table.
+    ///
+    /// After persisting, the buffer is cleared and the buffer position is updated to the
+    /// current cursor position.
+    pub fn persist_add_buffer(&mut self, force_save: bool) {
+        if self.temporary_add_buffer.buffer.is_empty() {
+            return;
+        }
+
+        // If the buffer is not empty, we need to persist its content to the piece table
+        if force_save
+            || self.temporary_add_buffer.char_count() > self.temporary_add_buffer.max_length / 2
+        {
+            let _ = self.add_to_content(
+                &self.temporary_add_buffer.buffer.clone(),
+                self.temporary_add_buffer.position,
+            );
+
+            // Typing started where the buffer starts, so that is where undo puts the cursor back
+            self.text_actions.push(TextAction::Add {
+                text: self.temporary_add_buffer.buffer.clone(),
+                position: self.temporary_add_buffer.position,
+                cursor: self.temporary_add_buffer.position,
+            });
+            self.undone_text_actions.clear();
+            self.record_insert_edit(
+                &self.temporary_add_buffer.buffer.clone(),
+                self.temporary_add_buffer.position,
+            );
+
+            self.temporary_add_buffer.clear(self.text_position);
+        }
+    }
+    
+    /// Records the time of the last keystroke, used by `auto_persist_if_idle`.
+    /// The time is passed in so tests can drive the clock.
+    pub fn register_keystroke(&mut self, now: Instant) {
+        self.last_keystroke = Some(now);
+    }
+
+    /// Flushes both temporary buffers into the piece table once no key was pressed for `auto_persist_ms`,
+    /// so less typing is lost if the editor crashes. The cursor and text position do not move.
+    /// Returns `true` if anything was flushed.
+    pub fn auto_persist_if_idle(&mut self, now: Instant) -> bool {
+        let (Some(timeout_ms), Some(last_keystroke)) = (self.auto_persist_ms, self.last_keystroke) else {
+            return false;
+        };
+
+        if (self.temporary_add_buffer.buffer.is_empty() && self.temporary_delete_buffer.is_empty())
+            || now.saturating_duration_since(last_keystroke) < Duration::from_millis(timeout_ms)
+        {
+            return false;
+        }
+
+        self.persist_changes();
+        true
+    }
+
+    /// Returns `true` when `read_only` is set, telling the user in the status line why nothing happened.
+    /// Every public edit checks this first, while navigation and search keep working.
+    fn reject_if_read_only(&mut self) -> bool {
+        if self.read_only {
+            self.set_status("Read-only: the document cannot be edited");
+        }
+        self.read_only
+    }
+
+    /// Undoes the last text change made in the editor.
+    pub fn undo_change(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        // Persist any changes in the temporary buffers before undoing
+        self.persist_changes();
+        self.clear_search_matches();
+
+        if let Some(last_action) = self.text_actions.pop() {
+            let cursor = self.revert_action(&last_action);
+
+            // Put the cursor back where it was before the undone edit
+            self.text_position = cursor.min(self.content.total_length());
+
+            self.undone_text_actions.push(last_action);
+            self.temporary_add_buffer.update_position(self.text_position);
+            self.update_cursor_after_text_position_change();
+        }
+    }
+
+    /// Redoes the last text change undone by `undo_change`.
+    pub fn redo_change(&mut self) {
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        // Pending buffer content is a new edit, so there is nothing left to redo
+        if !self.temporary_add_buffer.buffer.is_empty() || !self.temporary_delete_buffer.is_empty() {
+            return;
+        }
+
+        if let Some(last_undone_action) = self.undone_text_actions.pop() {
+            self.clear_search_matches();
+            self.reapply_action(&last_undone_action);
+
+            self.text_actions.push(last_undone_action);
+            self.temporary_add_buffer.update_position(self.text_position);
+            self.update_cursor_after_text_position_change();
+        }
+    }
+
+    /// Reverts a recorded change in the content and the line map, the changes of a group in
+    /// reverse order, and returns the text position the cursor had before the change.
+    fn revert_action(&mut self, action: &TextAction) -> usize {
+        match action {
+            TextAction::Add { text, position, cursor } => {
+                let _ = self.delete_from_content(*position, position + text.len());
+                let (line, column) = self.line_and_column_of(*position);
+                self.remove_from_lines_map(line, column, text);
+                *cursor
+            }
+            TextAction::Delete { text, position, cursor } => {
+                let _ = self.add_to_content(text, *position);
+                let (line, column) = self.line_and_column_of(*position);
+                self.insert_into_lines_map(line, column, text);
+                *cursor
+            }
+            TextAction::Group(actions) => actions
+                .iter()
+                .rev()
+                .map(|action| self.revert_action(action))
+                .last()
+                .unwrap_or(self.text_position),
+        }
+    }
+
+    /// Applies a recorded change again and leaves the text position after it.
+    fn reapply_action(&mut self, action: &TextAction) {
+        match action {
+            TextAction::Add { text, position, .. } => {
+                let _ = self.add_to_content(text, *position);
+                let (line, column) = self.line_and_column_of(*position);
+                self.insert_into_lines_map(line, column, text);
+                self.text_position = position + text.len();
+            }
+            TextAction::Delete { text, position, .. } => {
+                let _ = self.delete_from_content(*position, position + text.len());
+                let (line, column) = self.line_and_column_of(*position);
+                self.remove_from_lines_map(line, column, text);
+                self.text_position = *position;
+            }
+            TextAction::Group(actions) => {
+                for action in actions {
+                    self.reapply_action(action);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if there is a change that `undo_change` can revert,
+    /// including text still pending in the temporary buffers.
+    pub fn can_undo(&self) -> bool {
+        !self.text_actions.is_empty()
+            || !self.temporary_add_buffer.buffer.is_empty()
+            || !self.temporary_delete_buffer.is_empty()
+    }
+
+    /// Returns `true` if there is an undone change that `redo_change` can re-apply.
+    /// Pending buffer content counts as a new edit, which discards the redo history.
+    pub fn can_redo(&self) -> bool {
+        !self.undone_text_actions.is_empty()
+            && self.temporary_add_buffer.buffer.is_empty()
+            && self.temporary_delete_buffer.is_empty()
+    }
+
+    /// Finds every occurrence of `query` in the document and stores them as
+    /// (line, offset) pairs, replacing any previous results.
+    /// Returns the number of matches found.
+    pub fn find_all(&mut self, query: &str) -> usize {
+        self.clear_search_matches();
+
+        if query.is_empty() {
+            return 0;
+        }
+
+        self.search_query = query.to_string();
+        for (line_index, line) in self.get_text_lines().iter().enumerate() {
+            for (offset, _) in line.match_indices(query) {
+                self.search_matches.push((line_index, offset));
+            }
+        }
+
+        self.search_matches.len()
+    }
+
+    /// Moves the cursor to the next search match after the current one, wrapping to the first match.
+    pub fn next_match(&mut self) {
+        if self.search_matches.is_empty() {
+            return;
+        }
+
+        let next_index = match self.current_match {
+            Some(index) => (index + 1) % self.search_matches.len(),
+            None => 0,
+        };
+        self.jump_to_match(next_index);
+    }
+
+    /// Moves the cursor to the search match before the current one, wrapping to the last match.
+    pub fn previous_match(&mut self) {
+        if self.search_matches.is_empty() {
+            return;
+        }
+
+        let previous_index = match self.current_match {
+            Some(0) | None => self.search_matches.len() - 1,
+            Some(index) => index - 1,
+        };
+        self.jump_to_match(previous_index);
+    }
+
+    /// Discards the search results, e.g. because the document was edited and the offsets are stale.
+    pub fn clear_search_matches(&mut self) {
+        self.search_matches.clear();
+        self.current_match = None;
+        self.search_query.clear();
+    }
+
+    /// Returns the byte offset of the first match of `query` at or after `from`,
+    /// wrapping around to the top of the document when there is none after it.
+    pub fn find(&self, query: &str, from: usize) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+
+        let text = self.get_text();
+        text.get(from..)
+            .and_then(|after| after.find(query))
+            .map(|offset| from + offset)
+            .or_else(|| text.find(query))
+    }
+
+    /// Opens the search prompt with an empty query. The search starts from the current cursor position.
+    pub fn start_search(&mut self) {
+        self.persist_changes();
+        self.search_prompt = Some(String::new());
+        self.search_origin = self.text_position;
+    }
+
+    /// Appends a character to the search query and jumps to the next match.
+    pub fn push_search_char(&mut self, c: char) {
+        if let Some(query) = self.search_prompt.as_mut() {
+            query.push(c);
+            self.update_incremental_search();
+        }
+    }
+
+    /// Removes the last character of the search query and jumps to the next match of what is left.
+    pub fn pop_search_char(&mut self) {
+        if let Some(query) = self.search_prompt.as_mut() {
+            query.pop();
+            self.update_incremental_search();
+        }
+    }
+
+    /// Closes the search prompt, leaving the cursor on the match and the matches highlighted.
+    /// The query is kept for `find_next` and `find_prev`.
+    pub fn confirm_search(&mut self) {
+        if let Some(query) = self.search_prompt.take().filter(|query| !query.is_empty()) {
+            if self.find(&query, self.text_position) == Some(self.text_position) {
+                self.last_match_position = Some(self.text_position);
+            } else {
+                self.set_status("Pattern not found");
+            }
+            self.last_search = Some(query);
+        }
+    }
+
+    /// Moves the cursor to the next match of the last search after the cursor, wrapping to the top.
+    /// Returns `false` when there is no last search or it no longer matches.
+    pub fn find_next(&mut self) -> bool {
+        let Some(query) = self.last_search.clone() else {
+            return false;
+        };
+
+        let text = self.get_text();
+        let from = self.text_position + text[self.text_position..].chars().next().map_or(0, char::len_utf8);
+        match self.find(&query, from) {
+            Some(position) => {
+                self.jump_to_search_result(&query, position);
+                true
+            }
+            None => {
+                self.set_status("Pattern not found");
+                false
+            }
+        }
+    }
+
+    /// Moves the cursor to the previous match of the last search before the cursor, wrapping to the bottom.
+    /// Returns `false` when there is no last search or it no longer matches.
+    pub fn find_prev(&mut self) -> bool {
+        let Some(query) = self.last_search.clone() else {
+            return false;
+        };
+
+        let text = self.get_text();
+        let position = text[..self.text_position]
+            .rfind(query.as_str())
+            .or_else(|| text.rfind(query.as_str()));
+        match position {
+            Some(position) => {
+                self.jump_to_search_result(&query, position);
+                true
+            }
+            None => {
+                self.set_status("Pattern not found");
+                false
+            }
+        }
+    }
+
+    /// Moves the cursor to a match of `query` and highlights every match, marking this one as current.
+    fn jump_to_search_result(&mut self, query: &str, position: usize) {
+        self.find_all(query);
+        self.move_cursor_to_position(position);
+        self.last_match_position = Some(position);
+        let cursor = (self.cursor.y as usize, self.cursor.x as usize);
+        self.current_match = self.search_matches.iter().position(|&found| found == cursor);
+    }
+
+    /// Closes the search prompt and puts the cursor back where the search started.
+    pub fn cancel_search(&mut self) {
+        self.search_prompt = None;
+        self.clear_search_matches();
+        self.move_cursor_to_position(self.search_origin);
+    }
+
+    /// Highlights every match of the search query and moves the cursor to the first one
+    /// at or after the search origin, or back to the origin when nothing matches.
+    fn update_incremental_search(&mut self) {
+        let query = self.search_prompt.clone().unwrap_or_default();
+        self.find_all(&query);
+
+        match self.find(&query, self.search_origin) {
+            Some(position) => {
+                self.move_cursor_to_position(position);
+                let cursor = (self.cursor.y as usize, self.cursor.x as usize);
+                self.current_match = self.search_matches.iter().position(|&found| found == cursor);
+            }
+            None => self.move_cursor_to_position(self.search_origin),
+        }
+    }
+
+    /// Moves the cursor to the given text position, flushing pending changes first.
+    fn move_cursor_to_position(&mut self, position: usize) {
+        self.persist_changes();
+
+        self.text_position = position.min(self.content.total_length());
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer.update_position(self.text_position);
+    }
+
+    /// Moves the cursor to the search match at the given index of `search_matches`.
+    fn jump_to_match(&mut self, match_index: usize) {
+        let (line, offset) = self.search_matches[match_index];
+        self.current_match = Some(match_index);
+        self.move_cursor_to(line, offset);
+    }
+
+    /// Moves the cursor to the given line and column, flushing pending changes first.
+    fn move_cursor_to(&mut self, line: usize, column: usize) {
+        self.persist_changes();
+
+        self.cursor.y = line as u16;
+        self.cursor.x = column as u16;
+        self.update_text_position_after_cursor_move();
+        self.set_right_most_column(self.cursor.x);
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+    }
+
+    /// Stores the cursor position under `name`, like vim's `ma`.
+    pub fn set_mark(&mut self, name: char) {
+        self.persist_changes();
+        self.marks.insert(name, self.text_position);
+    }
+
+    /// Returns the text position stored under `name`, if the mark exists.
+    pub fn get_mark(&self, name: char) -> Option<usize> {
+        self.marks.get(&name).copied()
+    }
+
+    /// Moves the cursor to the position stored under `name`, like vim's `` `a ``.
+    /// Does nothing if the mark does not exist or is no longer in range.
+    pub fn goto_mark(&mut self, name: char) {
+        self.persist_changes();
+
+        let position = match self.marks.get(&name) {
+            Some(&position) if position <= self.content.total_length() => position,
+            _ => return,
+        };
+
+        self.text_position = position;
+        self.update_cursor_after_text_position_change();
+        self.temporary_add_buffer.update_position(self.text_position);
+    }
+
+    /// Inserts text into the piece table and shifts the marks after the insertion point.
+    /// Every insertion into the content goes through here so the marks stay in sync
+    /// and `on_change` sees every committed insertion.
+    fn add_to_content(&mut self, text: &str, position: usize) -> Result<(), String> {
+        self.content.add_text(text, position)?;
+        self.modified = true;
+        if !text.is_empty() {
+            self.notify_change(TextAction::Add {
+                text: text.to_string(),
+                position,
+                cursor: self.text_position,
+            });
+        }
+
+        for mark in self.marks.values_mut() {
+            if *mark >= position {
+                *mark += text.len();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes text from the piece table, shifting the marks after the deleted range
+    /// and dropping the marks inside it.
+    /// Every deletion from the content goes through here so the marks stay in sync.
+    fn delete_from_content(&mut self, start: usize, end: usize) -> Result<(), String> {
+        // The deleted text is only needed for the callback, so it is not read otherwise
+        let deleted_text = match self.on_change {
+            Some(_) if start < end => self.content.get_text_range(start, end).ok(),
+            _ => None,
+        };
+        self.content.delete_text(start, end)?;
+        if start < end {
+            self.modified = true;
+        }
+        if let Some(text) = deleted_text {
+            self.notify_change(TextAction::Delete {
+                text,
+                position: start,
+                cursor: self.text_position,
+            });
+        }
+
+        self.marks.retain(|_, mark| *mark < start || *mark >= end);
+        for mark in self.marks.values_mut() {
+            if *mark >= end {
+                *mark -= end - start;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Passes a change that was just applied to the piece table to `on_change`, if one is set.
+    /// Typing is reported when the temporary add buffer is persisted, and deletions batched in the
+    /// delete buffer when it is, so the positions are always offsets into the committed text.
+    fn notify_change(&mut self, action: TextAction) {
+        if let Some(on_change) = self.on_change.as_mut() {
+            on_change(&action);
+        }
+    }
+
+    /// Persists the contents of the temporary delete buffer to the piece table.
+    /// Deletes the text range from the piece table and clears the delete buffer.
+    fn persist_delete_buffer(&mut self) {
+        if let Some((start, end)) = self.temporary_delete_buffer.get_deletion_range() {
+            let text_to_delete = self.content.get_text_range(start, end).unwrap_or_default();
+
+            let _ = self.delete_from_content(start, end);
+            self.temporary_add_buffer.adjust_position_for_deletion(start, end);
+
+            let origin = self.temporary_delete_buffer.get_origin().unwrap_or(start);
+            self.text_actions.push(TextAction::Delete {
+                text: text_to_delete,
+                position: start,
+                cursor: origin,
+            });
+            self.undone_text_actions.clear();
+            self.record_delete_edit(start, end, origin);
+
+            self.temporary_delete_buffer.clear();
+        }
+    }
+
+    /// Persists both the add and delete buffers to the piece table.
+    /// Used to flush all temporary changes before certain operations, and by integrations
+    /// that need `on_change` to see the pending typing before the editor persists it itself.
+    pub fn persist_changes(&mut self) {
+        self.persist_add_buffer(true);
+        self.persist_delete_buffer();
+    }
+
+    /// Called after every cursor movement.
+    /// Persists any changes in the temporary buffers and updates buffer positions.
+    /// The line map is kept up to date by the edits themselves, so it is not rebuilt here.
+    fn do_after_move_cursor(&mut self) {
+        self.persist_changes();
+        self.temporary_add_buffer
+            .update_position(self.text_position);
+    }
+
+    /// Updates the line map for `text` inserted at the given line and column.
+    /// Only the line receiving the text changes, unless the text contains newlines,
+    /// in which case that line is split and the new lines are inserted after it.
+    fn insert_into_lines_map(&mut self, line: usize, column: usize, text: &str) {
+        let line_length = self.lines_map[line];
+        let mut inserted_lines = text.split('\n').map(|part| part.len());
+        let first_part = inserted_lines.next().unwrap_or(0);
+        let new_lines: Vec<usize> = inserted_lines.collect();
+
+        match new_lines.split_last() {
+            None => self.lines_map[line] += first_part,
+            Some((last_part, middle_parts)) => {
+                self.lines_map[line] = column + first_part;
+                let mut following = middle_parts.to_vec();
+                following.push(last_part + line_length - column);
+                self.lines_map.splice(line + 1..line + 1, following);
+            }
+        }
+    }
+
+    /// Updates the line map for `removed_text` deleted starting at the given line and column.
+    /// Every newline in the removed text merges the following line into the first one.
+    fn remove_from_lines_map(&mut self, line: usize, column: usize, removed_text: &str) {
+        let removed_newlines = removed_text.matches('\n').count();
+        let end_line = line + removed_newlines;
+        let end_column = match removed_text.rfind('\n') {
+            Some(last_newline) => removed_text.len() - last_newline - 1,
+            None => column + removed_text.len(),
+        };
+
+        self.lines_map[line] = column + (self.lines_map[end_line] - end_column);
+        self.lines_map.drain(line + 1..=end_line);
+    }
+
+    /// Generates a map of line numbers to their lengths based on the current text.
+    /// Updates the internal lines_map field.
+    /// Edits maintain the map incrementally; this full rebuild is the fallback for complex edits.
+    fn update_lines_map(&mut self) {
+        // This function updates the lines map based on the current content
+        let mut lines_map: Vec<usize> = Vec::new();
+        for line in self.get_text_lines().into_iter() {
+            lines_map.push(line.len());
+        }
+
+        // Even an empty document is rendered as a single empty line
+        if lines_map.is_empty() {
+            lines_map.push(0);
+        }
+        self.lines_map = lines_map;
+    }
+
+    /// Sets the rightmost column value for the cursor.
+    fn set_right_most_column(&mut self, column: u16) {
+        self.right_most_column = column;
+    }
+}
+
+/// Removes the spaces and tabs at the end of every line, leaving the line breaks in place.
+fn trim_trailing_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.trim_end_matches([' ', '\t']))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_can_undo_and_can_redo_on_fresh_editor() {
+    let editor = Editor::new("Hello".to_string(), 5);
+
+    assert!(!editor.can_undo());
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_can_undo_after_typing_with_pending_buffer() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.add_char('!');
+
+    // The character is still in the temporary add buffer but can be undone
+    assert!(editor.can_undo());
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_can_undo_and_can_redo_after_undo() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.add_char('!');
+    editor.undo_change();
+
+    assert_eq!(editor.get_text(), "Hello");
+    assert!(!editor.can_undo());
+    assert!(editor.can_redo());
+}
+
+#[test]
+fn test_can_undo_and_can_redo_with_both_stacks_filled() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.add_char('!');
+    editor.move_cursor_left();
+    editor.add_char('?');
+    editor.undo_change();
+
+    assert_eq!(editor.get_text(), "Hello!");
+    assert!(editor.can_undo());
+    assert!(editor.can_redo());
+}
+
+#[test]
+fn test_can_undo_and_can_redo_after_redo() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.add_char('!');
+    editor.undo_change();
+    editor.redo_change();
+
+    assert_eq!(editor.get_text(), "Hello!");
+    assert!(editor.can_undo());
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_typing_after_undo_discards_redo() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.add_char('!');
+    editor.undo_change();
+    editor.add_char('?');
+
+    assert!(editor.can_undo());
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_backspace_inside_parentheses_removes_both() {
+    let mut editor = Editor::new("foo()".to_string(), 5);
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+
+    assert_eq!(editor.get_text(), "foo");
+    assert_eq!(editor.text_position, 3);
+    assert_eq!(editor.cursor.x, 3);
+}
+
+#[test]
+fn test_backspace_inside_brackets_removes_both() {
+    let mut editor = Editor::new("a[]".to_string(), 5);
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+
+    assert_eq!(editor.get_text(), "a");
+    assert_eq!(editor.text_position, 1);
+}
+
+#[test]
+fn test_backspace_inside_quotes_removes_both() {
+    let mut editor = Editor::new("let s = \"\"".to_string(), 5);
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+
+    assert_eq!(editor.get_text(), "let s = ");
+}
+
+#[test]
+fn test_backspace_inside_typed_pair_removes_both() {
+    let mut editor = Editor::new("".to_string(), 5);
+    editor.add_char('(');
+    editor.add_char(')');
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+
+    assert_eq!(editor.get_text(), "");
+    assert_eq!(editor.text_position, 0);
+}
+
+#[test]
+fn test_backspace_outside_pair_removes_single_char() {
+    // Cursor after the closing parenthesis
+    let mut editor = Editor::new("foo()".to_string(), 5);
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "foo(");
+
+    // Cursor between characters that are not a pair
+    let mut editor = Editor::new("(a)".to_string(), 5);
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "()");
+
+    // Mismatched brackets are not a pair
+    let mut editor = Editor::new("(]".to_string(), 5);
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "]");
+}
+
+#[test]
+fn test_next_match_visits_every_match_in_order() {
+    let mut editor = Editor::new("foo bar\nbaz foo\nfoo".to_string(), 5);
+    assert_eq!(editor.find_all("foo"), 3);
+    assert_eq!(editor.search_matches, vec![(0, 0), (1, 4), (2, 0)]);
+
+    editor.next_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 0));
+    assert_eq!(editor.text_position, 0);
+
+    editor.next_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 4));
+    assert_eq!(editor.text_position, 12);
+
+    editor.next_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 0));
+    assert_eq!(editor.text_position, 16);
+
+    // Wraps around to the first match
+    editor.next_match();
+    assert_eq!(editor.current_match, Some(0));
+    assert_eq!(editor.text_position, 0);
+}
+
+#[test]
+fn test_previous_match_visits_matches_backwards() {
+    let mut editor = Editor::new("foo bar\nbaz foo\nfoo".to_string(), 5);
+    editor.find_all("foo");
+
+    // Starts from the last match when no match is selected yet
+    editor.previous_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 0));
+
+    editor.previous_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 4));
+    assert_eq!(editor.text_position, 12);
+
+    editor.previous_match();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 0));
+
+    editor.previous_match();
+    assert_eq!(editor.current_match, Some(2));
+}
+
+#[test]
+fn test_editing_invalidates_search_matches() {
+    let mut editor = Editor::new("foo foo".to_string(), 5);
+    editor.find_all("foo");
+    editor.next_match();
+
+    editor.add_char('x');
+
+    assert!(editor.search_matches.is_empty());
+    assert_eq!(editor.current_match, None);
+}
+
+#[test]
+fn test_insert_char_at_before_cursor_shifts_cursor() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.insert_char_at('>', 0);
+
+    assert_eq!(editor.get_text(), ">Hello");
+    assert_eq!(editor.text_position, 6);
+    assert_eq!(editor.cursor.x, 6);
+}
+
+#[test]
+fn test_insert_char_at_after_cursor_keeps_cursor() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.insert_char_at('!', 5);
+
+    assert_eq!(editor.get_text(), "Hello!");
+    assert_eq!(editor.text_position, 3);
+    assert_eq!(editor.cursor.x, 3);
+}
+
+#[test]
+fn test_insert_char_at_persists_pending_typing() {
+    let mut editor = Editor::new("ab".to_string(), 5);
+    editor.add_char('c');
+    editor.insert_char_at('\n', 0);
+
+    assert_eq!(editor.get_text(), "\nabc");
+    assert_eq!(editor.text_position, 4);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 3));
+
+    // Typing continues at the adjusted cursor position
+    editor.add_char('d');
+    assert_eq!(editor.get_text(), "\nabcd");
+}
+
+#[test]
+fn test_get_text_lines_without_trailing_newline() {
+    let editor = Editor::new("a\nb".to_string(), 5);
+
+    assert_eq!(editor.get_text_lines(), vec!["a", "b"]);
+    assert_eq!(editor.get_text_lines_with(false), vec!["a", "b"]);
+    assert_eq!(editor.line_count(), 2);
+}
+
+#[test]
+fn test_get_text_lines_with_trailing_newline() {
+    let editor = Editor::new("a\nb\n".to_string(), 5);
+
+    assert_eq!(editor.get_text_lines(), vec!["a", "b", ""]);
+    assert_eq!(editor.get_text_lines_with(false), vec!["a", "b"]);
+    assert_eq!(editor.line_count(), 2);
+}
+
+#[test]
+fn test_get_text_lines_of_empty_text() {
+    let editor = Editor::new("".to_string(), 5);
+
+    assert_eq!(editor.get_text_lines_with(false), vec![""]);
+    assert_eq!(editor.line_count(), 1);
+
+    let editor = Editor::new("\n".to_string(), 5);
+    assert_eq!(editor.get_text_lines(), vec!["", ""]);
+    assert_eq!(editor.get_text_lines_with(false), vec![""]);
+    assert_eq!(editor.line_count(), 1);
+}
+
+#[test]
+fn test_word_motions_move_the_cursor() {
+    let mut editor = Editor::new("foo, bar  baz".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+
+    editor.move_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.text_position, 3); // ","
+    editor.move_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.text_position, 5); // "bar"
+    editor.move_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.text_position, 10); // "baz"
+    assert_eq!(editor.cursor.x, 10);
+
+    editor.move_word_motion(WordMotion::PreviousWordStart);
+    assert_eq!(editor.text_position, 5);
+    editor.move_word_motion(WordMotion::PreviousWordStart);
+    assert_eq!(editor.text_position, 3);
+
+    editor.move_word_motion(WordMotion::WordEnd);
+    assert_eq!(editor.text_position, 7); // Last "r" of "bar"
+    assert_eq!(editor.cursor.x, 7);
+}
+
+#[test]
+fn test_delete_word_motion_dw() {
+    let mut editor = Editor::new("foo, bar  baz".to_string(), 5);
+    editor.move_cursor_to(0, 5);
+    editor.delete_word_motion(WordMotion::NextWordStart);
+
+    assert_eq!(editor.get_text(), "foo, baz");
+    assert_eq!(editor.text_position, 5);
+}
+
+#[test]
+fn test_delete_word_motion_de_and_db() {
+    let mut editor = Editor::new("foo, bar  baz".to_string(), 5);
+    editor.move_cursor_to(0, 5);
+    editor.delete_word_motion(WordMotion::WordEnd);
+    assert_eq!(editor.get_text(), "foo,   baz");
+
+    let mut editor = Editor::new("foo, bar  baz".to_string(), 5);
+    editor.move_cursor_to(0, 10);
+    editor.delete_word_motion(WordMotion::PreviousWordStart);
+    assert_eq!(editor.get_text(), "foo, baz");
+    assert_eq!(editor.cursor.x, 5);
+}
+
+#[test]
+fn test_repeat_last_edit_repeats_an_insertion() {
+    let mut editor = Editor::new("one two".to_string(), 5);
+    editor.move_cursor_to(0, 3);
+    for c in " and a half".chars() {
+        editor.add_char(c);
+    }
+
+    editor.move_cursor_to(0, "one and a half two".len());
+    editor.repeat_last_edit();
+
+    assert_eq!(editor.get_text(), "one and a half two and a half");
+    assert_eq!(editor.text_position, 29);
+}
+
+#[test]
+fn test_repeat_last_edit_repeats_a_word_delete() {
+    let mut editor = Editor::new("foo bar baz qux".to_string(), 5);
+    editor.move_cursor_to(0, 4);
+    editor.delete_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.get_text(), "foo baz qux");
+
+    editor.move_cursor_to(0, 0);
+    editor.repeat_last_edit();
+    assert_eq!(editor.get_text(), "baz qux");
+    assert_eq!(editor.text_position, 0);
+}
+
+#[test]
+fn test_repeat_last_edit_repeats_backspaces_before_the_cursor() {
+    let mut editor = Editor::new("abcdef".to_string(), 5);
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "abcd");
+
+    editor.repeat_last_edit();
+    assert_eq!(editor.get_text(), "ab");
+    assert_eq!(editor.text_position, 2);
+}
+
+#[test]
+fn test_incremental_lines_map_matches_full_rebuild() {
+    fn full_rebuild(editor: &Editor) -> Vec<usize> {
+        editor.get_text_lines().iter().map(|line| line.len()).collect()
+    }
+
+    let mut editor = Editor::new("Hello\nworld".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+    assert_eq!(editor.lines_map, vec![5, 5]);
+
+    for c in "xyz".chars() {
+        editor.add_char(c);
+        assert_eq!(editor.lines_map, full_rebuild(&editor));
+    }
+
+    editor.add_new_line();
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+    assert_eq!(editor.lines_map, vec![5, 3, 5]);
+
+    editor.add_char('!');
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+
+    // Backspace at the start of a line joins it with the previous one
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+    assert_eq!(editor.lines_map, vec![8, 5]);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 5));
+
+    // Delete at the end of a line joins the next one
+    editor.move_cursor_to(0, 8);
+    editor.delete_char(KeyCode::Delete);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+    assert_eq!(editor.lines_map, vec![13]);
+
+    editor.move_cursor_to(0, 0);
+    editor.delete_char(KeyCode::Delete);
+    editor.delete_char(KeyCode::Delete);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+
+    editor.insert_str("a\nb\nc");
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+    assert_eq!(editor.lines_map, vec![1, 1, 12]);
+
+    editor.delete_range(0, 5);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+
+    editor.undo_change();
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+    editor.redo_change();
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+
+    editor.delete_word(KeyCode::Backspace);
+    assert_eq!(editor.lines_map, full_rebuild(&editor));
+}
+
+#[test]
+fn test_set_tab_width_updates_cursor_screen_x() {
+    let mut editor = Editor::new("\tfoo\tbar".to_string(), 5);
+    editor.move_cursor_to(0, 5);
+    assert_eq!(editor.cursor.x, 5);
+    assert_eq!(editor.cursor_screen_x(), 8);
+
+    editor.set_tab_width(8).unwrap();
+    assert_eq!(editor.cursor_screen_x(), 16);
+
+    editor.set_tab_width(2).unwrap();
+    assert_eq!(editor.cursor_screen_x(), 6);
+    assert_eq!(editor.cursor.x, 5);
+}
+
+#[test]
+fn test_set_tab_width_rejects_zero() {
+    let mut editor = Editor::new("\tfoo".to_string(), 5);
+
+    assert!(editor.set_tab_width(0).is_err());
+    assert_eq!(editor.tab_width(), DEFAULT_TAB_WIDTH);
+}
+
+#[test]
+fn test_is_blank_line() {
+    let editor = Editor::new("foo\n\n  \t\nbar".to_string(), 5);
+
+    assert!(!editor.is_blank_line(0));
+    assert!(editor.is_blank_line(1));
+    assert!(editor.is_blank_line(2));
+    assert!(!editor.is_blank_line(3));
+    assert!(!editor.is_blank_line(4));
+    assert!(!editor.current_line_is_blank());
+}
+
+#[test]
+fn test_move_to_next_and_previous_nonblank_skip_blank_lines() {
+    let mut editor = Editor::new("foo\n\n   \nbar\n\t\nbaz\n".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+
+    editor.move_to_next_nonblank();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (3, 0));
+    assert_eq!(editor.text_position, 9);
+
+    editor.move_to_next_nonblank();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (5, 0));
+
+    // Only blank lines remain below, so the cursor stays put
+    editor.move_to_next_nonblank();
+    assert_eq!(editor.cursor.y, 5);
+
+    editor.move_to_previous_nonblank();
+    assert_eq!(editor.cursor.y, 3);
+    editor.move_to_previous_nonblank();
+    assert_eq!(editor.cursor.y, 0);
+    editor.move_to_previous_nonblank();
+    assert_eq!(editor.cursor.y, 0);
+}
+
+#[test]
+fn test_apply_settings_keeps_unset_values() {
+    let mut editor = Editor::new("".to_string(), 5);
+    let settings = Settings::parse("tab_width = 2\nexpand_tabs = true").unwrap();
+    editor.apply_settings(&settings).unwrap();
+
+    assert_eq!(editor.tab_width(), 2);
+    assert!(editor.expand_tabs);
+    assert!(!editor.insert_final_newline);
+    assert_eq!(editor.max_line_length, None);
+
+    let settings = Settings::parse("tab_width = 0").unwrap();
+    assert!(editor.apply_settings(&settings).is_err());
+    assert_eq!(editor.tab_width(), 2);
+}
+
+#[test]
+fn test_auto_persist_setting_of_zero_disables_it() {
+    let mut editor = Editor::new("".to_string(), 5);
+    assert_eq!(editor.auto_persist_ms, Some(1000));
+
+    editor.apply_settings(&Settings::parse("auto_persist_ms = 250").unwrap()).unwrap();
+    assert_eq!(editor.auto_persist_ms, Some(250));
+
+    editor.apply_settings(&Settings::parse("auto_persist_ms = 0").unwrap()).unwrap();
+    assert_eq!(editor.auto_persist_ms, None);
+}
+
+#[test]
+fn test_closing_brace_outdents_leading_whitespace() {
+    let mut editor = Editor::new("fn main() {\n        ".to_string(), 5);
+    editor.add_char('}');
+
+    assert_eq!(editor.get_text(), "fn main() {\n}");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 1));
+
+    let mut editor = Editor::new("if x {\n\t\t".to_string(), 5);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "if x {\n}");
+
+    let mut editor = Editor::new("{\n  ".to_string(), 5);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "{\n}");
+}
+
+#[test]
+fn test_closing_brace_lines_up_with_the_line_that_opened_the_block() {
+    // Auto-indent copies the indentation of the `if` line, so the brace already lines up
+    let mut editor = Editor::new("    if x {".to_string(), 5);
+    editor.move_cursor_to(0, 10);
+    editor.add_new_line();
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "    if x {\n    }");
+
+    // Nested blocks that were closed already are skipped
+    let mut editor = Editor::new("fn f() {\n\tif x {\n\t\ty();\n\t}\n\t\t".to_string(), 5);
+    editor.move_cursor_to(4, 2);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "fn f() {\n\tif x {\n\t\ty();\n\t}\n}");
+    assert_eq!(editor.lines_map, vec![8, 7, 6, 2, 1]);
+
+    // Without an open block the brace is typed where it is
+    let mut editor = Editor::new("x\n  ".to_string(), 5);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "x\n  }");
+}
+
+#[test]
+fn test_undo_reverts_the_outdent_and_the_brace_in_one_step() {
+    let mut editor = Editor::new("if x {\n        ".to_string(), 5);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "if x {\n}");
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "if x {\n        ");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 8));
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "if x {\n}");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 1));
+
+    // The brace, not the removed whitespace, is what `.` repeats
+    editor.repeat_last_edit();
+    assert_eq!(editor.get_text(), "if x {\n}}");
+}
+
+#[test]
+fn test_closing_brace_after_text_does_not_outdent() {
+    let mut editor = Editor::new("    let x = {".to_string(), 5);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "    let x = {}");
+
+    let mut editor = Editor::new("}".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+    editor.add_char('}');
+    assert_eq!(editor.get_text(), "}}");
+}
+
+#[test]
+fn test_join_selected_lines_joins_three_lines() {
+    let mut editor = Editor::new("first  \n   second\n\tthird\nfourth".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+    editor.start_selection();
+    editor.move_cursor_to(2, 3);
+    editor.join_selected_lines();
+
+    assert_eq!(editor.get_text(), "first second third\nfourth");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 18));
+    assert_eq!(editor.text_position, 18);
+    assert_eq!(editor.selected_range(), None);
+    assert_eq!(editor.lines_map, vec![18, 6]);
+}
+
+#[test]
+fn test_join_selected_lines_normalizes_blank_lines_and_indentation() {
+    let mut editor = Editor::new("    if x {\n\n      y();\n    }".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+    editor.start_selection();
+    editor.move_cursor_to(3, 5);
+    editor.join_selected_lines();
+
+    // The first line keeps its indentation
+    assert_eq!(editor.get_text(), "    if x { y(); }");
+}
+
+#[test]
+fn test_join_selected_lines_without_multiline_selection_is_noop() {
+    let mut editor = Editor::new("foo\nbar".to_string(), 5);
+    editor.join_selected_lines();
+    assert_eq!(editor.get_text(), "foo\nbar");
+
+    editor.move_cursor_to(0, 0);
+    editor.start_selection();
+    editor.move_cursor_to(0, 2);
+    editor.join_selected_lines();
+    assert_eq!(editor.get_text(), "foo\nbar");
+}
+
+#[test]
+fn test_empty_editor_starts_with_one_empty_line() {
+    let editor = Editor::new(String::new(), 5);
+
+    assert_eq!(editor.get_text(), "");
+    assert_eq!(editor.get_text_lines(), vec![""]);
+    assert_eq!(editor.lines_map, vec![0]);
+    assert_eq!(editor.text_position, 0);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 0));
+}
+
+#[test]
+fn test_first_insert_into_empty_editor() {
+    let mut editor = Editor::new(String::new(), 5);
+    editor.add_char('a');
+
+    assert_eq!(editor.get_text(), "a");
+    assert_eq!(editor.text_position, 1);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 1));
+
+    editor.persist_add_buffer(true);
+    assert_eq!(editor.get_text(), "a");
+    assert_eq!(editor.lines_map, vec![1]);
+}
+
+#[test]
+fn test_deleting_last_remaining_character() {
+    let mut editor = Editor::new("a".to_string(), 5);
+    editor.delete_char(KeyCode::Backspace);
+
+    assert_eq!(editor.get_text(), "");
+    assert_eq!(editor.get_text_lines(), vec![""]);
+    assert_eq!(editor.text_position, 0);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 0));
+
+    // Further deletes on the empty document are no-ops
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Delete);
+    editor.move_cursor_left();
+    assert_eq!(editor.get_text(), "");
+    assert_eq!(editor.text_position, 0);
+    assert_eq!(editor.lines_map, vec![0]);
+}
+
+#[test]
+fn test_mark_follows_edits_before_it() {
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    editor.move_cursor_to(0, 6);
+    editor.set_mark('a');
+
+    // Insert before the mark
+    editor.move_cursor_to(0, 0);
+    for c in ">> ".chars() {
+        editor.add_char(c);
+    }
+    editor.goto_mark('a');
+    assert_eq!(editor.get_mark('a'), Some(9));
+    assert_eq!(editor.text_position, 9);
+    assert_eq!(editor.cursor.x, 9);
+
+    // Delete before the mark
+    editor.delete_range(0, 3);
+    assert_eq!(editor.get_mark('a'), Some(6));
+
+    // Edits after the mark do not move it
+    editor.move_cursor_to(0, 11);
+    editor.add_char('!');
+    editor.goto_mark('a');
+    assert_eq!(editor.get_mark('a'), Some(6));
+    assert_eq!(&editor.get_text()[6..], "world!");
+}
+
+#[test]
+fn test_mark_inside_deleted_range_is_dropped() {
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    editor.move_cursor_to(0, 8);
+    editor.set_mark('b');
+
+    editor.delete_range(6, 11);
+    assert_eq!(editor.get_mark('b'), None);
+
+    // Jumping to a missing mark leaves the cursor in place
+    editor.move_cursor_to(0, 2);
+    editor.goto_mark('b');
+    assert_eq!(editor.text_position, 2);
+}
+
+#[test]
+fn test_move_to_line_end_for_insert_on_middle_line() {
+    let mut editor = Editor::new("first\nsecond\nthird".to_string(), 5);
+    editor.move_cursor_to(1, 2);
+
+    editor.move_to_line_end_for_insert();
+    assert_eq!(editor.cursor.y, 1);
+    assert_eq!(editor.cursor.x, 6);
+    assert_eq!(editor.text_position, 12);
+
+    editor.add_char('!');
+    assert_eq!(editor.get_text(), "first\nsecond!\nthird");
+}
+
+#[test]
+fn test_move_to_line_end_for_insert_on_last_line() {
+    let mut editor = Editor::new("first\nlast".to_string(), 5);
+    editor.move_cursor_to(1, 0);
+
+    editor.move_to_line_end_for_insert();
+    assert_eq!(editor.cursor.x, 4);
+    assert_eq!(editor.text_position, 10);
+
+    editor.add_char('!');
+    assert_eq!(editor.get_text(), "first\nlast!");
+}
+
+#[test]
+fn test_change_word_from_its_start() {
+    let mut editor = Editor::new("let foo = bar;".to_string(), 5);
+    editor.move_cursor_to(0, 4);
+
+    editor.change_word();
+    assert_eq!(editor.get_text(), "let  = bar;");
+    assert_eq!(editor.text_position, 4);
+
+    for c in "baz".chars() {
+        editor.add_char(c);
+    }
+    assert_eq!(editor.get_text(), "let baz = bar;");
+
+    // The deletion is a single undoable change
+    editor.persist_changes();
+    editor.undo_change();
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "let foo = bar;");
+}
+
+#[test]
+fn test_change_word_from_its_middle() {
+    let mut editor = Editor::new("let foobar = 1;".to_string(), 5);
+    editor.move_cursor_to(0, 7);
+
+    editor.change_word();
+    assert_eq!(editor.get_text(), "let foo = 1;");
+    assert_eq!(editor.cursor.x, 7);
+}
+
+#[test]
+fn test_change_word_on_whitespace() {
+    let mut editor = Editor::new("foo   bar".to_string(), 5);
+    editor.move_cursor_to(0, 3);
+
+    editor.change_word();
+    assert_eq!(editor.get_text(), "foobar");
+}
+
+#[test]
+fn test_auto_persist_after_idle_timeout() {
+    let mut editor = Editor::new("Hello".to_string(), 10);
+    editor.auto_persist_ms = Some(500);
+    let start = Instant::now();
+
+    editor.add_char('!');
+    editor.register_keystroke(start);
+
+    // Not idle long enough yet
+    assert!(!editor.auto_persist_if_idle(start + Duration::from_millis(499)));
+    assert_eq!(editor.temporary_add_buffer.buffer, "!");
+
+    assert!(editor.auto_persist_if_idle(start + Duration::from_millis(500)));
+    assert!(editor.temporary_add_buffer.buffer.is_empty());
+    assert_eq!(editor.get_text(), "Hello!");
+
+    // Nothing left to persist
+    assert!(!editor.auto_persist_if_idle(start + Duration::from_millis(2000)));
+}
+
+#[test]
+fn test_auto_persist_can_be_disabled() {
+    let mut editor = Editor::new("Hello".to_string(), 10);
+    editor.auto_persist_ms = None;
+    let start = Instant::now();
+
+    editor.add_char('!');
+    editor.register_keystroke(start);
+
+    assert!(!editor.auto_persist_if_idle(start + Duration::from_secs(60)));
+    assert_eq!(editor.temporary_add_buffer.buffer, "!");
+}
+
+#[test]
+fn test_yank_line_pastes_as_its_own_line() {
+    let mut editor = Editor::new("first\nsecond\nthird".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+    editor.yank_line();
+    assert_eq!(editor.register, Some(Register::Linewise("first".to_string())));
+
+    // Pasting from the middle of a line does not splice the text into it
+    editor.move_cursor_to(1, 3);
+    editor.paste();
+    assert_eq!(editor.get_text(), "first\nsecond\nfirst\nthird");
+    assert_eq!(editor.cursor.y, 2);
+    assert_eq!(editor.cursor.x, 0);
+    assert_eq!(editor.lines_map, vec![5, 6, 5, 5]);
+
+    // Pasting below the last line
+    editor.move_cursor_to(3, 1);
+    editor.paste();
+    assert_eq!(editor.get_text(), "first\nsecond\nfirst\nthird\nfirst");
+    assert_eq!(editor.cursor.y, 4);
+}
+
+#[test]
+fn test_paste_above_and_inline_paste() {
+    let mut editor = Editor::new("first\nsecond".to_string(), 5);
+    editor.move_cursor_to(1, 0);
+    editor.yank_line();
+
+    editor.move_cursor_to(0, 3);
+    editor.paste_above();
+    assert_eq!(editor.get_text(), "second\nfirst\nsecond");
+    assert_eq!(editor.cursor.y, 0);
+
+    editor.register = Some(Register::Inline("xy".to_string()));
+    editor.move_cursor_to(1, 2);
+    editor.paste();
+    assert_eq!(editor.get_text(), "second\nfixyrst\nsecond");
+    assert_eq!(editor.cursor.x, 4);
+}
+
+#[test]
+fn test_copy_selection_fills_the_register() {
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    editor.clipboard = Box::new(MemoryClipboard::default());
+    editor.move_cursor_to(0, 6);
+    editor.start_selection();
+    for _ in 0..5 {
+        editor.move_cursor_right();
+    }
+
+    assert!(editor.copy_selection());
+    assert_eq!(editor.register, Some(Register::Inline("world".to_string())));
+    assert_eq!(editor.status_text(Instant::now()), None);
+
+    // Copying does not change the text or the selection
+    assert_eq!(editor.get_text(), "Hello world");
+    assert_eq!(editor.selected_range(), Some((6, 11)));
+}
+
+#[test]
+fn test_copy_without_selection_sets_a_hint() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+
+    assert!(!editor.copy_selection());
+    assert_eq!(editor.register, None);
+    assert_eq!(editor.status_text(Instant::now()), Some("Nothing selected to copy"));
+}
+
+#[test]
+fn test_visual_and_logical_line_counts() {
+    let editor = Editor::new("a\nb".to_string(), 5);
+    assert_eq!(editor.visual_line_count(), 2);
+    assert_eq!(editor.logical_line_count(), 2);
+    assert_eq!(editor.visual_line_count(), editor.get_text_lines().len());
+
+    let editor = Editor::new("a\nb\n".to_string(), 5);
+    assert_eq!(editor.visual_line_count(), 3);
+    assert_eq!(editor.logical_line_count(), 3);
+    assert_eq!(editor.visual_line_count(), editor.get_text_lines().len());
+
+    let editor = Editor::new(String::new(), 5);
+    assert_eq!(editor.visual_line_count(), 1);
+    assert_eq!(editor.logical_line_count(), 0);
+    assert_eq!(editor.visual_line_count(), editor.get_text_lines().len());
+    assert_eq!(editor.stats().lines, 1);
+}
+
+#[test]
+fn test_move_to_document_start_and_end() {
+    let text = "Hello World\nThis is a text editor\nIt supports multiple lines\nAnd basic editing features";
+    let mut editor = Editor::new(text.to_string(), 5);
+    editor.move_cursor_to(1, 4);
+
+    editor.move_to_document_start();
+    assert_eq!(editor.text_position, 0);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 0));
+
+    editor.move_to_document_end();
+    assert_eq!(editor.text_position, text.len());
+    assert_eq!((editor.cursor.y, editor.cursor.x), (3, 26));
+
+    // The remembered column follows the jump
+    editor.move_cursor_up();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 26));
+}
+
+#[test]
+fn test_replace_selection_with_shorter_and_longer_text() {
+    let mut editor = Editor::new("let value = 1;".to_string(), 5);
+
+    // Select "value" and replace it with shorter text
+    editor.move_cursor_to(0, 4);
+    editor.start_selection();
+    editor.move_word_motion(WordMotion::NextWordStart);
+    editor.move_cursor_left();
+    editor.replace_selection_with("x");
+    assert_eq!(editor.get_text(), "let x = 1;");
+    assert_eq!(editor.text_position, 5);
+    assert_eq!(editor.cursor.x, 5);
+    assert_eq!(editor.selection_anchor, None);
+
+    // Select "1" and replace it with longer text
+    editor.move_cursor_to(0, 8);
+    editor.start_selection();
+    editor.move_cursor_right();
+    editor.replace_selection_with("1000");
+    assert_eq!(editor.get_text(), "let x = 1000;");
+    assert_eq!(editor.cursor.x, 12);
+}
+
+#[test]
+fn test_replace_selection_with_inserts_without_selection() {
+    let mut editor = Editor::new("ab".to_string(), 5);
+    editor.move_cursor_to(0, 1);
+
+    editor.replace_selection_with("\n");
+    assert_eq!(editor.get_text(), "a\nb");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 0));
+}
+
+#[test]
+fn test_backspace_over_text_typed_at_the_start() {
+    let mut editor = Editor::new(String::new(), 5);
+    editor.add_char('a');
+    editor.add_char('b');
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "a");
+
+    editor.add_char('c');
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "");
+
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.move_to_document_start();
+    editor.add_char('x');
+    editor.add_char('y');
+    editor.delete_char(KeyCode::Backspace);
+    editor.add_char('z');
+    editor.persist_changes();
+    assert_eq!(editor.get_text(), "xzHello");
+}
+
+#[test]
+fn test_interleaved_typing_and_backspacing() {
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    for key in "ab".chars() {
+        editor.add_char(key);
+    }
+    for _ in 0..4 {
+        editor.delete_char(KeyCode::Backspace);
+    }
+    for key in "XY".chars() {
+        editor.add_char(key);
+    }
+    editor.delete_char(KeyCode::Backspace);
+    editor.add_char('Z');
+    editor.persist_changes();
+
+    assert_eq!(editor.get_text(), "Hello worXZ");
+    assert_eq!(editor.text_position, 11);
+}
+
+#[test]
+fn test_add_buffer_position_follows_a_deletion_before_it() {
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    editor.add_char('!');
+
+    // A deletion before the pending text persists while the text is still buffered
+    editor.temporary_delete_buffer.add_char("Hello world", 5, KeyCode::Backspace).unwrap();
+    editor.persist_delete_buffer();
+    assert_eq!(editor.temporary_add_buffer.position, 10);
+
+    editor.persist_add_buffer(true);
+    assert_eq!(editor.get_text(), "Hell world!");
+}
+
+#[test]
+fn test_word_deletion_with_a_custom_classifier() {
+    struct UnderscoreSeparates;
+
+    impl WordClassifier for UnderscoreSeparates {
+        fn is_word_char(&self, c: char) -> bool {
+            c.is_alphanumeric()
+        }
+    }
+
+    // The default classifier keeps `_` inside the word
+    let mut editor = Editor::new("snake_case value".to_string(), 5);
+    editor.move_to_document_start();
+    editor.delete_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.get_text(), "value");
+
+    // Treating `_` as a separator stops the deletion before it
+    let mut editor = Editor::new("snake_case value".to_string(), 5);
+    editor.word_classifier = Box::new(UnderscoreSeparates);
+    editor.move_to_document_start();
+    editor.delete_word_motion(WordMotion::NextWordStart);
+    assert_eq!(editor.get_text(), "_case value");
+
+    editor.change_word();
+    assert_eq!(editor.get_text(), "case value");
+
+    // Ctrl+Backspace uses the same word boundaries
+    let mut editor = Editor::new("value snake_case".to_string(), 5);
+    editor.delete_word(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "value");
+
+    let mut editor = Editor::new("value snake_case".to_string(), 5);
+    editor.word_classifier = Box::new(UnderscoreSeparates);
+    editor.delete_word(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "value snake_");
+    editor.delete_word(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "value snake");
+}
+
+#[test]
+fn test_trim_current_line() {
+    let mut editor = Editor::new("first  \nsecond \t \nthird".to_string(), 5);
+
+    // The cursor inside the trailing whitespace clamps to the new line end
+    editor.move_cursor_to(1, 8);
+    editor.trim_current_line();
+    assert_eq!(editor.get_text(), "first  \nsecond\nthird");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 6));
+    assert_eq!(editor.lines_map, vec![7, 6, 5]);
+
+    // A cursor before the whitespace does not move
+    editor.move_cursor_to(0, 2);
+    editor.trim_current_line();
+    assert_eq!(editor.get_text(), "first\nsecond\nthird");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 2));
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "first  \nsecond\nthird");
+}
+
+#[test]
+fn test_undo_restores_the_cursor_from_before_the_edit() {
+    // Forward deletes leave the cursor in place, so undo must not move it past the restored text
+    let mut editor = Editor::new("Hello world".to_string(), 5);
+    editor.move_cursor_to(0, 5);
+    editor.delete_char(KeyCode::Delete);
+    editor.delete_char(KeyCode::Delete);
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "Hello world");
+    assert_eq!(editor.text_position, 5);
+    assert_eq!(editor.cursor.x, 5);
+
+    // Backspace moved the cursor left, so undo puts it back at the end of the restored text
+    editor.move_cursor_to(0, 11);
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "Hello world");
+    assert_eq!(editor.text_position, 11);
+
+    // A change on another line restores the line as well as the column
+    let mut editor = Editor::new("first\nsecond".to_string(), 5);
+    editor.move_cursor_to(1, 3);
+    editor.delete_char(KeyCode::Delete);
+    editor.move_cursor_to(0, 0);
+    editor.undo_change();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 3));
+}
+
+#[test]
+fn test_undo_reverses_a_whole_typed_chunk() {
+    let mut editor = Editor::new("Hello".to_string(), 10);
+    for c in " you".chars() {
+        editor.add_char(c);
+    }
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "Hello");
+    assert_eq!(editor.text_position, 5);
+    assert_eq!(editor.cursor.x, 5);
+}
+
+#[test]
+fn test_new_edit_after_three_undos_empties_the_redo_stack() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    for text in [" one", " two", " six"] {
+        editor.insert_str(text);
+    }
+    editor.undo_change();
+    editor.undo_change();
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "Hello");
+    assert!(editor.can_redo());
+
+    // Typing and erasing the character still counts as a new edit
+    editor.add_char('!');
+    editor.delete_char(KeyCode::Backspace);
+    assert!(!editor.can_redo());
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "Hello");
+}
+
+#[test]
+fn test_redo_reapplies_undone_edits_in_order() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.insert_str(" one");
+    editor.insert_str(" two");
+    editor.undo_change();
+    editor.undo_change();
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "Hello one");
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "Hello one two");
+    assert_eq!(editor.text_position, 13);
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_typing_after_multibyte_characters() {
+    let mut editor = Editor::new("café".to_string(), 5);
+    assert_eq!(editor.text_position, 5);
+
+    editor.add_char('x');
+    assert_eq!(editor.get_text(), "caféx");
+    editor.persist_changes();
+    assert_eq!(editor.get_text(), "caféx");
+
+    // Typing multibyte characters and moving over them keeps every position on a boundary
+    editor.add_char('ñ');
+    editor.add_char('!');
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.add_char('€');
+    assert_eq!(editor.get_text(), "caféx€ñ!");
+    assert_eq!(editor.cursor.x as usize, editor.text_position);
+    assert_eq!(editor.cursor_screen_x(), 6);
+    assert_eq!(editor.lines_map, vec!["caféx€ñ!".len()]);
+}
+
+#[test]
+fn test_deleting_multibyte_characters() {
+    let mut editor = Editor::new("añb€".to_string(), 5);
+
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "añb");
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "ab");
+    editor.persist_changes();
+    assert_eq!(editor.text_position, 1);
+
+    editor.move_cursor_to(0, 0);
+    editor.insert_str("¿");
+    editor.move_cursor_to(0, 0);
+    editor.delete_char(KeyCode::Delete);
+    editor.persist_changes();
+    assert_eq!(editor.get_text(), "ab");
+    assert_eq!(editor.lines_map, vec![2]);
+}
+
+#[test]
+fn test_vertical_movement_through_lines_of_different_lengths() {
+    // Line starts: 0, 12, 15
+    let mut editor = Editor::new("Hello world\nHi\nGood morning".to_string(), 5);
+    editor.move_cursor_to(0, 9);
+
+    editor.move_cursor_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 2));
+    assert_eq!(editor.text_position, 14);
+
+    editor.move_cursor_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 9));
+    assert_eq!(editor.text_position, 24);
+
+    // The last line stays put
+    editor.move_cursor_down();
+    assert_eq!(editor.text_position, 24);
+
+    editor.move_cursor_up();
+    assert_eq!(editor.text_position, 14);
+    editor.move_cursor_up();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 9));
+    assert_eq!(editor.text_position, 9);
+
+    // The first line stays put
+    editor.move_cursor_up();
+    assert_eq!(editor.text_position, 9);
+}
+
+#[test]
+fn test_vertical_movement_into_a_medium_line_clamps_the_column() {
+    let mut editor = Editor::new("a long first line\nab\nabcde\n".to_string(), 5);
+    editor.move_cursor_to(0, 15);
+
+    editor.move_cursor_down();
+    editor.move_cursor_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 5));
+    assert_eq!(editor.text_position, 26);
+
+    // The empty line after the trailing newline can be reached
+    editor.move_cursor_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (3, 0));
+    assert_eq!(editor.text_position, 27);
+}
+
+#[test]
+fn test_home_and_end_on_the_current_line() {
+    let mut editor = Editor::new("first\nsecond line\nthird".to_string(), 5);
+    editor.move_cursor_to(1, 3);
+
+    editor.move_cursor_to_line_end();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 11));
+    assert_eq!(editor.text_position, 17);
+    assert_eq!(&editor.get_text()[editor.text_position..editor.text_position + 1], "\n");
+
+    editor.move_cursor_to_line_start();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 0));
+    assert_eq!(editor.text_position, 6);
+
+    // Home at column 0 is a no-op
+    editor.move_cursor_to_line_start();
+    assert_eq!(editor.text_position, 6);
+
+    // The remembered column follows End
+    editor.move_cursor_to_line_end();
+    editor.move_cursor_up();
+    assert_eq!(editor.cursor.x, 5);
+}
+
+#[test]
+fn test_page_up_and_page_down() {
+    let text = (0..30).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    editor.viewport_height = 10;
+    editor.move_cursor_to(0, 3);
+
+    editor.page_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (10, 3));
+    editor.page_down();
+    assert_eq!(editor.cursor.y, 20);
+
+    // Clamped to the last line
+    editor.page_down();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (29, 3));
+    assert_eq!(editor.text_position, editor.line_start_position(29) + 3);
+
+    editor.page_up();
+    assert_eq!(editor.cursor.y, 19);
+    editor.page_up();
+    editor.page_up();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 3));
+    assert_eq!(editor.text_position, 3);
+}
+
+#[test]
+fn test_save_writes_the_document_to_its_file() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_save_{}.txt", std::process::id()));
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.file_path = Some(path.clone());
+
+    // Pending typing is flushed before writing
+    editor.add_char('!');
+    editor.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "Hello!");
+
+    editor.insert_final_newline = true;
+    editor.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "Hello!\n");
+    assert_eq!(editor.get_text(), "Hello!");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_save_without_a_file_is_an_error() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+
+    assert_eq!(editor.save().unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_modified_flag_follows_edits_and_saves() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_modified_{}.txt", std::process::id()));
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.file_path = Some(path.clone());
+    assert!(!editor.modified);
+
+    // Moving around is not a change
+    editor.move_cursor_left();
+    editor.move_to_document_end();
+    assert!(!editor.modified);
+
+    editor.add_char('!');
+    assert!(editor.modified);
+    editor.save().unwrap();
+    assert!(!editor.modified);
+
+    editor.delete_char(KeyCode::Backspace);
+    assert!(editor.modified);
+    editor.save().unwrap();
+
+    editor.add_new_line();
+    assert!(editor.modified);
+    editor.save().unwrap();
+
+    editor.delete_word(KeyCode::Backspace);
+    assert!(editor.modified);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_scroll_to_cursor_keeps_the_cursor_in_the_viewport() {
+    let text = (0..50).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    editor.viewport_height = 10;
+    editor.move_to_document_start();
+    editor.scroll_to_cursor();
+    assert_eq!(editor.row_offset, 0);
+
+    // Still inside the window, no scrolling
+    editor.move_cursor_to(9, 0);
+    editor.scroll_to_cursor();
+    assert_eq!(editor.row_offset, 0);
+
+    // Past the bottom, the cursor line becomes the last visible one
+    editor.move_cursor_down();
+    editor.scroll_to_cursor();
+    assert_eq!(editor.row_offset, 1);
+
+    editor.move_to_document_end();
+    editor.scroll_to_cursor();
+    assert_eq!(editor.row_offset, 40);
+
+    // Above the top, the cursor line becomes the first visible one
+    editor.page_up();
+    editor.page_up();
+    editor.scroll_to_cursor();
+    assert_eq!(editor.row_offset, 29);
+}
+
+#[test]
+fn test_find_wraps_around_to_the_top() {
+    let editor = Editor::new("one two\none three".to_string(), 5);
+    assert_eq!(editor.find("one", 0), Some(0));
+    assert_eq!(editor.find("one", 1), Some(8));
+    assert_eq!(editor.find("one", 9), Some(0));
+    assert_eq!(editor.find("four", 0), None);
+    assert_eq!(editor.find("", 0), None);
+}
+
+#[test]
+fn test_incremental_search() {
+    let mut editor = Editor::new("apple\nbanana\napricot".to_string(), 5);
+    editor.move_cursor_to(1, 0);
+    editor.start_search();
+
+    // "a" matches inside "banana" first, since the search starts at the cursor
+    editor.push_search_char('a');
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 1));
+    assert_eq!(editor.search_matches.len(), 5);
+    assert_eq!(editor.current_match, Some(1));
+
+    // "ap" only matches after wrapping around
+    editor.push_search_char('p');
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 0));
+    editor.push_search_char('x');
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 0));
+    assert!(editor.search_matches.is_empty());
+    editor.pop_search_char();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 0));
+
+    editor.confirm_search();
+    assert_eq!(editor.search_prompt, None);
+    assert_eq!(editor.text_position, 13);
+    assert_eq!(editor.search_matches.len(), 2);
+
+    // Cancelling goes back to where the search started
+    editor.start_search();
+    editor.push_search_char('l');
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 3));
+    editor.cancel_search();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 0));
+    assert!(editor.search_matches.is_empty());
+}
+
+#[test]
+fn test_find_next_and_find_prev_wrap_around() {
+    let mut editor = Editor::new("cat dog cat\ncat".to_string(), 5);
+    editor.move_to_document_start();
+
+    // No search yet
+    assert!(!editor.find_next());
+    assert!(!editor.find_prev());
+
+    editor.start_search();
+    editor.push_search_char('c');
+    editor.push_search_char('a');
+    editor.push_search_char('t');
+    editor.confirm_search();
+    assert_eq!(editor.last_search.as_deref(), Some("cat"));
+    assert_eq!(editor.last_match_position, Some(0));
+
+    assert!(editor.find_next());
+    assert_eq!(editor.text_position, 8);
+    assert!(editor.find_next());
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 0));
+    assert_eq!(editor.last_match_position, Some(12));
+    assert_eq!(editor.current_match, Some(2));
+
+    // Past the end wraps to the beginning
+    assert!(editor.find_next());
+    assert_eq!(editor.text_position, 0);
+
+    // Before the start wraps to the end
+    assert!(editor.find_prev());
+    assert_eq!(editor.text_position, 12);
+    assert!(editor.find_prev());
+    assert_eq!(editor.text_position, 8);
+}
+
+#[test]
+fn test_find_next_and_find_prev_stay_on_a_single_match() {
+    let mut editor = Editor::new("one two three".to_string(), 5);
+    editor.last_search = Some("two".to_string());
+    editor.move_to_document_start();
+
+    assert!(editor.find_next());
+    assert_eq!(editor.text_position, 4);
+    assert!(editor.find_next());
+    assert_eq!(editor.text_position, 4);
+    assert!(editor.find_prev());
+    assert_eq!(editor.text_position, 4);
+}
+
+#[test]
+fn test_replace_all() {
+    let mut editor = Editor::new("cat cat cat".to_string(), 5);
+    assert_eq!(editor.replace_all("cat", "dog"), 3);
+    assert_eq!(editor.get_text(), "dog dog dog");
+    assert_eq!(editor.text_position, 11);
+}
+
+#[test]
+fn test_replace_all_with_different_lengths_and_undo() {
+    let mut editor = Editor::new("a-b\na-b".to_string(), 5);
+    editor.move_cursor_to(1, 2);
+    assert_eq!(editor.replace_all("-", " to "), 2);
+    assert_eq!(editor.get_text(), "a to b\na to b");
+    assert_eq!(editor.lines_map, vec![6, 6]);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 5));
+    assert_eq!(editor.text_position, 12);
+
+    assert_eq!(editor.replace_all("missing", "x"), 0);
+    assert_eq!(editor.replace_all("", "x"), 0);
+
+    // The whole command is one undo step
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "a-b\na-b");
+    assert_eq!(editor.lines_map, vec![3, 3]);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 2));
+    assert!(!editor.can_undo());
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "a to b\na to b");
+    assert_eq!(editor.lines_map, vec![6, 6]);
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_typing_replaces_the_selection() {
+    let mut editor = Editor::new("abcde".to_string(), 5);
+    editor.move_cursor_to(0, 1);
+    editor.start_selection();
+    editor.move_cursor_right();
+    editor.move_cursor_right();
+    editor.move_cursor_right();
+    assert_eq!(editor.selected_range(), Some((1, 4)));
+
+    editor.add_char('X');
+    assert_eq!(editor.get_text(), "aXe");
+    assert_eq!(editor.selection_anchor, None);
+    assert_eq!(editor.text_position, 2);
+}
+
+#[test]
+fn test_backspace_and_delete_remove_only_the_selection() {
+    let mut editor = Editor::new("abcde".to_string(), 5);
+    editor.move_cursor_to(0, 4);
+    editor.start_selection();
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "abe");
+    assert_eq!((editor.cursor.x, editor.text_position), (2, 2));
+
+    editor.start_selection();
+    editor.move_cursor_left();
+    editor.delete_char(KeyCode::Delete);
+    assert_eq!(editor.get_text(), "ae");
+    assert_eq!(editor.text_position, 1);
+
+    // Without a selection the keys delete one character again
+    editor.delete_char(KeyCode::Delete);
+    assert_eq!(editor.get_text(), "a");
+
+    editor.undo_change();
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "abe");
+}
+
+#[test]
+fn test_copy_and_paste_through_the_clipboard() {
+    let mut editor = Editor::new("Hello World".to_string(), 5);
+    editor.clipboard = Box::new(MemoryClipboard::default());
+    editor.move_cursor_to(0, 6);
+    editor.start_selection();
+    editor.move_to_document_end();
+    assert!(editor.copy_selection());
+    assert_eq!(editor.clipboard.get_text(), Ok("World".to_string()));
+
+    editor.clear_selection();
+    editor.move_to_document_start();
+    editor.paste_from_clipboard();
+    assert_eq!(editor.get_text(), "WorldHello World");
+    assert_eq!(editor.text_position, 5);
+
+    // A multi-line paste is one undoable change and updates the line map
+    editor.clipboard.set_text("one\r\ntwo\nthree ").unwrap();
+    editor.paste_from_clipboard();
+    assert_eq!(editor.get_text(), "Worldone\ntwo\nthree Hello World");
+    assert_eq!(editor.lines_map, vec![8, 3, 17]);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 6));
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "WorldHello World");
+}
+
+#[test]
+fn test_clipboard_errors_go_to_the_status_line() {
+    struct BrokenClipboard;
+
+    impl Clipboard for BrokenClipboard {
+        fn get_text(&mut self) -> Result<String, String> {
+            Err("no clipboard".to_string())
+        }
+
+        fn set_text(&mut self, _text: &str) -> Result<(), String> {
+            Err("no clipboard".to_string())
+        }
+    }
+
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.clipboard = Box::new(BrokenClipboard);
+    editor.paste_from_clipboard();
+    assert_eq!(editor.get_text(), "Hello");
+    assert_eq!(editor.status_text(Instant::now()), Some("no clipboard"));
+
+    // Copying still fills the register
+    editor.start_selection();
+    editor.move_cursor_left();
+    assert!(editor.copy_selection());
+    assert_eq!(editor.register, Some(Register::Inline("o".to_string())));
+}
+
+#[test]
+fn test_insert_str_adds_a_block_in_one_piece() {
+    let mut editor = Editor::new("start end".to_string(), 5);
+    editor.move_cursor_to(0, 6);
+    editor.add_char('>');
+
+    let block = (0..100).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n");
+    editor.insert_str(&block);
+
+    // The typed '>' is flushed first, then the block is added as a single change
+    assert!(editor.temporary_add_buffer.buffer.is_empty());
+    assert!(matches!(editor.text_actions.last(), Some(TextAction::Add { text, position: 7, .. }) if *text == block));
+    assert_eq!(editor.get_text(), format!("start >{}end", block));
+    assert_eq!(editor.text_position, 7 + block.len());
+    assert_eq!((editor.cursor.y, editor.cursor.x), (99, 7));
+    assert_eq!(editor.lines_map.len(), 100);
+    assert_eq!(editor.lines_map[0], 13);
+    assert_eq!(editor.lines_map[99], 10);
+}
+
+#[test]
+fn test_ctrl_delete_removes_the_word_to_the_right() {
+    let mut editor = Editor::new("foo bar".to_string(), 10);
+    editor.move_to_document_start();
+    editor.delete_word(KeyCode::Delete);
+    assert_eq!(editor.get_text(), "bar");
+    assert_eq!(editor.text_position, 0);
+
+    editor.persist_changes();
+    assert_eq!(editor.content.get_text(), "bar");
+}
+
+#[test]
+fn test_repeated_ctrl_backspace_deletes_several_words() {
+    let mut editor = Editor::new("one two three".to_string(), 50);
+    editor.delete_word(KeyCode::Backspace);
+    editor.delete_word(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "one");
+    assert_eq!(editor.text_position, 3);
+
+    // Still one pending range, undone in one step
+    assert_eq!(editor.temporary_delete_buffer.get_deletion_range(), Some((3, 13)));
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "one two three");
+}
+
+#[test]
+fn test_repeated_ctrl_delete_deletes_several_words() {
+    let mut editor = Editor::new("one two three".to_string(), 50);
+    editor.move_to_document_start();
+    editor.delete_word(KeyCode::Delete);
+    editor.delete_word(KeyCode::Delete);
+    assert_eq!(editor.get_text(), "three");
+    assert_eq!(editor.text_position, 0);
+}
+
+#[test]
+fn test_goto_line_clamps_to_the_document() {
+    let mut editor = Editor::new("first line\nsecond\nthird line".to_string(), 5);
+    editor.move_cursor_to(0, 8);
+
+    editor.goto_line(2);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 6));
+    assert_eq!(editor.text_position, 17);
+
+    // The column is remembered across the shorter line
+    editor.goto_line(3);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 8));
+
+    editor.goto_line(42);
+    assert_eq!(editor.cursor.y, 2);
+    editor.goto_line(0);
+    assert_eq!((editor.cursor.y, editor.text_position), (0, 8));
+}
+
+#[test]
+fn test_goto_line_prompt() {
+    let mut editor = Editor::new("a\nb\nc".to_string(), 5);
+    editor.start_goto_line();
+    editor.push_goto_digit('2');
+    editor.push_goto_digit('x');
+    assert_eq!(editor.goto_prompt.as_deref(), Some("2"));
+    editor.confirm_goto_line();
+    assert_eq!(editor.goto_prompt, None);
+    assert_eq!(editor.cursor.y, 1);
+
+    // An empty or cancelled prompt leaves the cursor alone
+    editor.start_goto_line();
+    editor.confirm_goto_line();
+    assert_eq!(editor.cursor.y, 1);
+    editor.start_goto_line();
+    editor.push_goto_digit('1');
+    editor.pop_goto_digit();
+    editor.push_goto_digit('3');
+    editor.cancel_goto_line();
+    assert_eq!(editor.cursor.y, 1);
+}
+
+#[test]
+fn test_insert_tab_literal_or_expanded() {
+    let mut editor = Editor::new("a".to_string(), 10);
+    editor.set_tab_width(4).unwrap();
+    editor.insert_tab();
+    assert_eq!(editor.get_text(), "a\t");
+    assert_eq!((editor.cursor.x, editor.cursor_screen_x()), (2, 4));
+
+    editor.expand_tabs = true;
+    editor.add_char('b');
+    editor.insert_tab();
+    assert_eq!(editor.get_text(), "a\tb   ");
+    assert_eq!((editor.cursor.x, editor.cursor_screen_x()), (6, 8));
+
+    // Already on a tab stop, a whole tab width is inserted
+    editor.insert_tab();
+    assert_eq!(editor.cursor_screen_x(), 12);
+}
+
+#[test]
+fn test_new_line_keeps_the_indentation() {
+    let mut editor = Editor::new("    foo".to_string(), 5);
+    editor.add_new_line();
+    assert_eq!(editor.get_text(), "    foo\n    ");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 4));
+    assert_eq!(editor.text_position, 12);
+    assert_eq!(editor.lines_map, vec![7, 4]);
+
+    editor.add_char('b');
+    assert_eq!(editor.get_text(), "    foo\n    b");
+
+    // Splitting inside the indentation only carries the part before the cursor
+    let mut editor = Editor::new("\t\tbar".to_string(), 5);
+    editor.move_cursor_to(0, 1);
+    editor.add_new_line();
+    assert_eq!(editor.get_text(), "\t\n\t\tbar");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 1));
+}
+
+#[test]
+fn test_new_line_without_auto_indent() {
+    let mut editor = Editor::new("    foo".to_string(), 5);
+    editor.auto_indent = false;
+    editor.add_new_line();
+    assert_eq!(editor.get_text(), "    foo\n");
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 0));
+}
+
+#[test]
+fn test_auto_persist_keeps_the_cursor() {
+    let mut editor = Editor::new("Hello World".to_string(), 10);
+    let idle = Duration::from_millis(1000);
+    let start = Instant::now();
+    editor.move_cursor_to(0, 5);
+    editor.add_char(',');
+    editor.register_keystroke(start);
+
+    assert!(!editor.auto_persist_if_idle(start + Duration::from_millis(999)));
+    assert_eq!(editor.temporary_add_buffer.buffer, ",");
+
+    assert!(editor.auto_persist_if_idle(start + idle));
+    assert!(editor.temporary_add_buffer.buffer.is_empty());
+    assert_eq!(editor.content.get_text(), "Hello, World");
+    assert_eq!((editor.text_position, editor.cursor.x), (6, 6));
+
+    // Typing goes on from the same place
+    editor.add_char('!');
+    assert_eq!(editor.get_text(), "Hello,! World");
+
+    // Pending deletions are flushed too
+    editor.delete_char(KeyCode::Delete);
+    editor.register_keystroke(start);
+    assert!(editor.auto_persist_if_idle(start + idle));
+    assert!(editor.temporary_delete_buffer.is_empty());
+    assert_eq!(editor.content.get_text(), "Hello,!World");
+    assert_eq!(editor.text_position, 7);
+
+    assert!(!editor.auto_persist_if_idle(start + idle * 2));
+}
+
+#[test]
+fn test_get_text_applies_both_pending_buffers() {
+    let mut editor = Editor::new("Hello World".to_string(), 20);
+
+    // A pending deletion of "World" and pending typing at the start of the text
+    for _ in 0..5 {
+        editor.temporary_delete_buffer.add_char("Hello World", 6, KeyCode::Delete).unwrap();
+    }
+    editor.temporary_add_buffer.update_position(0);
+    editor.temporary_add_buffer.add_char('>').unwrap();
+    assert_eq!(editor.get_text(), ">Hello ");
+
+    // Typing after the deleted range is shifted left by its length
+    editor.temporary_add_buffer.clear(11);
+    editor.temporary_add_buffer.add_char('!').unwrap();
+    assert_eq!(editor.get_text(), "Hello !");
+
+    // The composed text matches what persisting both buffers produces
+    let expected = editor.get_text();
+    editor.persist_delete_buffer();
+    editor.persist_add_buffer(true);
+    assert_eq!(editor.content.get_text(), expected);
+}
+
+#[test]
+fn test_move_cursor_to_visual_position_clamps() {
+    let mut editor = Editor::new("\tab\nlonger line\nx".to_string(), 5);
+    editor.set_tab_width(4).unwrap();
+
+    editor.move_cursor_to_visual_position(0, 5);
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (0, 2, 2));
+
+    // Past the end of a short line
+    editor.move_cursor_to_visual_position(0, 40);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 3));
+
+    // Below the last line
+    editor.move_cursor_to_visual_position(9, 3);
+    assert_eq!((editor.cursor.y, editor.cursor.x), (2, 1));
+    assert_eq!(editor.text_position, editor.get_text().len());
+}
+
+#[test]
+fn test_read_only_blocks_edits_but_not_navigation() {
+    let mut editor = Editor::new("Hello\nWorld".to_string(), 5);
+    editor.read_only = true;
+
+    editor.add_char('!');
+    editor.add_new_line();
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_word(KeyCode::Backspace);
+    editor.insert_str("text");
+    editor.delete_range(0, 3);
+    assert_eq!(editor.replace_all("o", "0"), 0);
+    assert_eq!(editor.get_text(), "Hello\nWorld");
+    assert!(!editor.modified);
+    assert!(editor.status_text(Instant::now()).unwrap().starts_with("Read-only"));
+
+    editor.move_to_document_start();
+    editor.move_cursor_down();
+    assert_eq!(editor.cursor.y, 1);
+    assert_eq!(editor.find_all("o"), 2);
+}
+
+#[test]
+fn test_typing_accented_letters_persists_after_max_length_characters() {
+    let mut editor = Editor::new(String::new(), 5);
+    for c in "café".chars() {
+        editor.add_char(c);
+    }
+    assert_eq!(editor.temporary_add_buffer.buffer, "café");
+    assert_eq!(editor.content.get_text(), "");
+
+    editor.add_char('é');
+    assert!(editor.temporary_add_buffer.buffer.is_empty());
+    assert_eq!(editor.content.get_text(), "caféé");
+    assert_eq!(editor.text_position, 7);
+}
+
+#[test]
+fn test_find_matching_bracket_with_nesting() {
+    let editor = Editor::new("fn f(a: [u8; 2]) { g((1), \"é\") }".to_string(), 5);
+    let text = editor.get_text();
+    let at = |needle: &str| text.find(needle).unwrap();
+
+    assert_eq!(editor.find_matching_bracket(at("(a")), Some(at(") {")));
+    assert_eq!(editor.find_matching_bracket(at(") {")), Some(at("(a")));
+    assert_eq!(editor.find_matching_bracket(at("[")), Some(at("]")));
+    assert_eq!(editor.find_matching_bracket(at("{")), Some(text.len() - 1));
+    assert_eq!(editor.find_matching_bracket(text.len() - 1), Some(at("{")));
+    assert_eq!(editor.find_matching_bracket(at("((")), Some(at("\") }") + 1));
+
+    // Not a bracket
+    assert_eq!(editor.find_matching_bracket(0), None);
+}
+
+#[test]
+fn test_jump_to_matching_bracket() {
+    let mut editor = Editor::new("if (a\n  && b) {".to_string(), 5);
+    editor.move_cursor_to(0, 3);
+    editor.jump_to_matching_bracket();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 6));
+    editor.jump_to_matching_bracket();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (0, 3));
+
+    // An unmatched bracket leaves the cursor where it is
+    editor.move_to_document_end();
+    editor.move_cursor_left();
+    editor.jump_to_matching_bracket();
+    assert_eq!((editor.cursor.y, editor.cursor.x), (1, 8));
+}
+
+#[test]
+fn test_stats_include_pending_typing() {
+    let mut editor = Editor::new("one two".to_string(), 10);
+    editor.add_char(' ');
+    editor.add_char('3');
+    assert_eq!(
+        editor.stats(),
+        EditorStats { chars: 9, words: 3, lines: 1, bytes: 9 }
+    );
+}
+
+#[test]
+fn test_left_and_right_wrap_across_lines() {
+    let mut editor = Editor::new("ab\ncd".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+
+    editor.move_cursor_right();
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (1, 0, 3));
+    editor.move_cursor_right();
+    editor.move_cursor_right();
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (1, 2, 5));
+
+    // The end of the document is a hard stop
+    editor.move_cursor_right();
+    assert_eq!(editor.text_position, 5);
+
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (0, 2, 2));
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (0, 0, 0));
+}
+
+#[test]
+fn test_right_wraps_after_typing_at_line_end() {
+    let mut editor = Editor::new("ab\ncd".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+    editor.add_char('!');
+    editor.move_cursor_right();
+    assert_eq!((editor.cursor.y, editor.cursor.x, editor.text_position), (1, 0, 4));
+    assert_eq!(editor.get_text(), "ab!\ncd");
+}
+
+#[test]
+fn test_from_file_reads_the_file_or_reports_the_error() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_from_file_{}.txt", std::process::id()));
+    fs::write(&path, "loaded\ntext").unwrap();
+
+    let editor = Editor::from_file(&path, 5).unwrap();
+    assert_eq!(editor.get_text(), "loaded\ntext");
+    assert_eq!(editor.file_path.as_deref(), Some(path.as_path()));
+    assert!(!editor.modified);
+    fs::remove_file(&path).unwrap();
+
+    let error = Editor::from_file(&path, 5).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_crlf_line_endings_are_normalized_and_restored_on_save() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_crlf_{}.txt", std::process::id()));
+    fs::write(&path, "a\r\nb\r\n").unwrap();
+
+    let mut editor = Editor::from_file(&path, 5).unwrap();
+    assert_eq!(editor.line_ending, LineEnding::CrLf);
+    assert_eq!(editor.get_text(), "a\nb\n");
+    assert_eq!(editor.lines_map, vec![1, 1, 0]);
+
+    editor.move_cursor_to_position(1);
+    editor.add_char('x');
+    editor.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "ax\r\nb\r\n");
+
+    editor.toggle_line_ending();
+    editor.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "ax\nb\n");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_save_trims_trailing_whitespace_when_enabled() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_trim_{}.txt", std::process::id()));
+    let mut editor = Editor::new("foo   \nbar\t\n".to_string(), 5);
+    editor.file_path = Some(path.clone());
+    editor.trim_trailing_whitespace_on_save = true;
+
+    editor.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "foo\nbar\n");
+    assert_eq!(editor.get_text(), "foo   \nbar\t\n");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_snapshot_includes_pending_typing_and_ignores_later_edits() {
+    let mut editor = Editor::new("hello".to_string(), 50);
+    editor.add_char('!');
+
+    let snapshot = editor.snapshot();
+    assert_eq!(snapshot.get_text(), "hello!");
+
+    editor.add_char('?');
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_char(KeyCode::Backspace);
+    assert_eq!(editor.get_text(), "hell");
+    assert_eq!(snapshot.get_text(), "hello!");
+}
+
+#[test]
+fn test_select_all_then_delete_empties_the_document() {
+    let mut editor = Editor::new("first line\nsecond".to_string(), 5);
+    editor.move_cursor_to_position(3);
+    editor.add_char('x');
+
+    editor.select_all();
+    assert_eq!(editor.selected_range(), Some((0, editor.get_text().len())));
+
+    editor.delete_char(KeyCode::Delete);
+    assert_eq!(editor.get_text(), "");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (0, 0));
+    assert_eq!(editor.selected_range(), None);
+}
+
+#[test]
+fn test_duplicate_line_copies_the_line_below() {
+    let mut editor = Editor::new("one\ntwo\nthree".to_string(), 5);
+    editor.move_cursor_to(1, 2);
+    editor.duplicate_line();
+    assert_eq!(editor.get_text(), "one\ntwo\ntwo\nthree");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (2, 2));
+    assert_eq!(editor.lines_map, vec![3, 3, 3, 5]);
+
+    // The last line has no trailing newline
+    editor.move_cursor_to(3, 5);
+    editor.duplicate_line();
+    assert_eq!(editor.get_text(), "one\ntwo\ntwo\nthree\nthree");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (5, 4));
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "one\ntwo\ntwo\nthree");
+}
+
+#[test]
+fn test_delete_line_removes_the_line_and_its_newline() {
+    let mut editor = Editor::new("one\ntwo\nthree".to_string(), 5);
+    editor.move_cursor_to(1, 2);
+    editor.delete_line();
+    assert_eq!(editor.get_text(), "one\nthree");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (2, 1));
+
+    // On the last line the newline before it goes instead
+    editor.delete_line();
+    assert_eq!(editor.get_text(), "one");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (2, 0));
+    assert_eq!(editor.lines_map, vec![3]);
+
+    editor.delete_line();
+    assert_eq!(editor.get_text(), "");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (0, 0));
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "one");
+}
+
+#[test]
+fn test_move_line_up_and_down_swap_with_the_neighbour() {
+    let mut editor = Editor::new("one\ntwo\nthree".to_string(), 5);
+    editor.move_cursor_to(2, 1);
+    editor.move_line_up();
+    assert_eq!(editor.get_text(), "one\nthree\ntwo");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (1, 1));
+    assert_eq!(editor.lines_map, vec![3, 5, 3]);
+
+    editor.move_line_up();
+    editor.move_line_up();
+    assert_eq!(editor.get_text(), "three\none\ntwo");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (1, 0));
+
+    editor.move_cursor_to(1, 3);
+    editor.move_line_down();
+    editor.move_line_down();
+    assert_eq!(editor.get_text(), "three\ntwo\none");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (3, 2));
+}
+
+#[test]
+fn test_move_line_keeps_the_trailing_newline_in_place() {
+    let mut editor = Editor::new("a\nb\n".to_string(), 5);
+    editor.move_cursor_to(1, 0);
+    editor.move_line_up();
+    assert_eq!(editor.get_text(), "b\na\n");
+
+    editor.move_line_down();
+    editor.move_line_down();
+    assert_eq!(editor.get_text(), "a\n\nb");
+    assert_eq!(editor.cursor.y, 2);
+}
+
+#[test]
+fn test_undo_puts_a_moved_line_back_in_one_step() {
+    let mut editor = Editor::new("one\ntwo\nthree".to_string(), 5);
+    editor.move_cursor_to(2, 1);
+    editor.move_line_up();
+    assert_eq!(editor.get_text(), "one\nthree\ntwo");
+    assert_eq!(editor.text_actions.len(), 1);
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "one\ntwo\nthree");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (1, 2));
+    assert_eq!(editor.lines_map, vec![3, 3, 5]);
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "one\nthree\ntwo");
+    assert_eq!(editor.lines_map, vec![3, 5, 3]);
+}
+
+#[test]
+fn test_join_lines_replaces_the_newline_and_indentation_with_a_space() {
+    let mut editor = Editor::new("foo\n   bar\nbaz".to_string(), 5);
+    editor.move_cursor_to(0, 1);
+    editor.join_lines();
+    assert_eq!(editor.get_text(), "foo bar\nbaz");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (3, 0));
+    assert_eq!(editor.lines_map, vec![7, 3]);
+
+    // Nothing to join on the last line
+    editor.move_cursor_to(1, 0);
+    editor.join_lines();
+    assert_eq!(editor.get_text(), "foo bar\nbaz");
+}
+
+#[test]
+fn test_indent_and_unindent_selection() {
+    let mut editor = Editor::new("one\n\n  two\nthree".to_string(), 5);
+    editor.move_cursor_to(0, 1);
+    editor.start_selection();
+    editor.move_cursor_to(3, 0);
+
+    // The selection ends at the start of "three", so that line is not indented
+    editor.indent_selection();
+    assert_eq!(editor.get_text(), "    one\n\n      two\nthree");
+    assert_eq!(editor.selected_range(), Some((0, 18)));
+
+    editor.unindent_selection();
+    assert_eq!(editor.get_text(), "one\n\n  two\nthree");
+    editor.unindent_selection();
+    assert_eq!(editor.get_text(), "one\n\ntwo\nthree");
+    assert_eq!(editor.lines_map, vec![3, 0, 3, 5]);
+}
+
+#[test]
+fn test_unindent_without_selection_keeps_text_without_indentation() {
+    let mut editor = Editor::new("\tab\ncd".to_string(), 5);
+    editor.move_cursor_to(0, 2);
+    editor.unindent_selection();
+    assert_eq!(editor.get_text(), "ab\ncd");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (1, 0));
+
+    editor.unindent_selection();
+    assert_eq!(editor.get_text(), "ab\ncd");
+}
+
+#[test]
+fn test_replay_applies_the_recorded_actions_at_the_cursor() {
+    let mut editor = Editor::new("one\ntwo\nthree".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+
+    // Prefix the line with "- " and move down to the next one
+    editor.start_recording();
+    editor.add_char('-');
+    editor.add_char('x');
+    editor.delete_char(KeyCode::Backspace);
+    editor.add_char(' ');
+    editor.move_cursor_down();
+    editor.move_cursor_left();
+    editor.move_cursor_left();
+    editor.stop_recording();
+    assert_eq!(editor.recorded.len(), 7);
+
+    editor.replay();
+    editor.replay();
+    assert_eq!(editor.get_text(), "- one\n- two\n- three");
+    assert_eq!(editor.recorded.len(), 7);
+}
+
+#[test]
+fn test_backspace_at_the_start_of_the_document_changes_nothing() {
+    let mut editor = Editor::new("abc\ndef".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+
+    assert!(!editor.delete_char(KeyCode::Backspace));
+    assert_eq!(editor.text_position, 0);
+    assert_eq!((editor.cursor.x, editor.cursor.y), (0, 0));
+    assert_eq!(editor.get_text(), "abc\ndef");
+    assert!(!editor.modified);
+
+    // The no-op keeps the redo history, the search matches and an empty macro
+    editor.insert_str("!");
+    editor.undo_change();
+    editor.find_all("def");
+    editor.start_recording();
+    editor.move_cursor_to(0, 0);
+    assert!(editor.can_redo());
+
+    assert!(!editor.delete_char(KeyCode::Backspace));
+    assert!(editor.can_redo());
+    assert!(!editor.search_matches.is_empty());
+    assert!(editor.recorded.is_empty());
+
+    // Delete at the end of the document is a no-op too
+    editor.move_cursor_to(1, 3);
+    assert!(!editor.delete_char(KeyCode::Delete));
+    assert!(editor.can_redo());
+
+    editor.move_cursor_to(0, 0);
+    assert!(editor.delete_char(KeyCode::Delete));
+    assert_eq!(editor.get_text(), "bc\ndef");
+    assert!(!editor.can_redo());
+}
+
+#[test]
+fn test_move_cursor_right_stops_at_the_end_of_multibyte_text() {
+    let mut editor = Editor::new("héllo".to_string(), 5);
+    editor.move_cursor_to(0, 0);
+
+    // The cursor is a byte offset, so it ends at the byte length after one step per character
+    for _ in 0..5 {
+        editor.move_cursor_right();
+    }
+    assert_eq!(editor.text_position, 6);
+    editor.move_cursor_right();
+    assert_eq!(editor.text_position, 6);
+    assert_eq!(editor.cursor.x, 6);
+}
+
+#[test]
+fn test_status_message_expires_after_the_timeout() {
+    let mut editor = Editor::new("text".to_string(), 5);
+    editor.set_status("Hello");
+    let (_, set_at) = editor.status_message.clone().unwrap();
+
+    assert_eq!(editor.status_text(set_at), Some("Hello"));
+    assert!(!editor.expire_status_message(set_at));
+
+    let later = set_at + STATUS_MESSAGE_TIMEOUT;
+    assert_eq!(editor.status_text(later), None);
+    assert!(editor.expire_status_message(later));
+    assert_eq!(editor.status_message, None);
+}
+
+#[test]
+fn test_save_and_failed_search_report_in_the_status_area() {
+    let path = std::env::temp_dir().join(format!("rust_text_editor_status_{}.txt", std::process::id()));
+    let mut editor = Editor::new("abc".to_string(), 5);
+    editor.file_path = Some(path.clone());
+
+    editor.save().unwrap();
+    assert_eq!(editor.status_text(Instant::now()), Some("Saved 3 bytes"));
+    fs::remove_file(&path).unwrap();
+
+    editor.last_search = Some("xyz".to_string());
+    assert!(!editor.find_next());
+    assert_eq!(editor.status_text(Instant::now()), Some("Pattern not found"));
+}
+
+#[test]
+fn test_resize_keeps_the_cursor_line_visible() {
+    let text = (0..100).map(|line| format!("row{}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    editor.move_cursor_to(50, 0);
+    editor.resize(40);
+    assert_eq!(editor.row_offset, 11);
+
+    editor.resize(10);
+    assert_eq!(editor.viewport_height, 10);
+    assert_eq!(editor.row_offset, 41);
+
+    // Growing again keeps the offset, the cursor line is still on screen
+    editor.resize(40);
+    assert_eq!(editor.row_offset, 41);
+}
+
+#[test]
+fn test_gap_buffer_backend_edits_like_the_piece_table() {
+    fn edit<T: TextTrait>(editor: &mut Editor<T>) -> Vec<String> {
+        let mut texts = Vec::new();
+        for c in "fn main() {".chars() {
+            editor.add_char(c);
+        }
+        editor.add_new_line();
+        editor.add_char('}');
+        texts.push(editor.get_text());
+
+        editor.move_cursor_up();
+        editor.delete_word(KeyCode::Backspace);
+        editor.delete_char(KeyCode::Backspace);
+        texts.push(editor.get_text());
+
+        editor.undo_change();
+        texts.push(editor.get_text());
+        editor.redo_change();
+        texts.push(editor.get_text());
+        texts
+    }
+
+    let mut piece_table = Editor::new("// héllo\n".to_string(), 3);
+    let mut gap_buffer: Editor<GapBuffer> = Editor::with_backend("// héllo\n".to_string(), 3);
+    assert_eq!(edit(&mut gap_buffer), edit(&mut piece_table));
+    assert_eq!(
+        (gap_buffer.cursor.x, gap_buffer.cursor.y),
+        (piece_table.cursor.x, piece_table.cursor.y)
+    );
+    assert_eq!(gap_buffer.snapshot().get_text(), piece_table.snapshot().get_text());
+}
+
+#[test]
+fn test_on_change_reports_every_committed_edit() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let original = "fn main() {}".to_string();
+    let mut editor = Editor::new(original.clone(), 4);
+    editor.clipboard = Box::new(MemoryClipboard::default());
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&changes);
+    editor.on_change = Some(Box::new(move |action: &TextAction| {
+        log.borrow_mut().push(match action {
+            TextAction::Add { text, position, .. } => (*position, 0, text.clone()),
+            TextAction::Delete { text, position, .. } => (*position, text.len(), String::new()),
+            TextAction::Group(_) => unreachable!("on_change only sees single changes"),
+        })
+    }));
+
+    editor.move_cursor_left();
+    for c in "let x;".chars() {
+        editor.add_char(c);
+    }
+    editor.add_new_line();
+    editor.delete_char(KeyCode::Backspace);
+    editor.delete_word(KeyCode::Backspace);
+    editor.clipboard.set_text("é").unwrap();
+    editor.paste_from_clipboard();
+    editor.undo_change();
+    editor.persist_changes();
+
+    // Replaying the reported changes on the original text gives the current text
+    let mut replayed = original;
+    for (position, removed, inserted) in changes.borrow().iter() {
+        replayed.replace_range(*position..*position + *removed, inserted);
+    }
+    assert_eq!(replayed, editor.get_text());
+    assert_eq!(
+        *changes.borrow(),
+        vec![
+            (11, 0, "let ".to_string()),
+            (15, 0, "x;".to_string()),
+            (17, 0, "\n".to_string()),
+            // The backspace and the word deletion were batched in the delete buffer
+            (16, 2, String::new()),
+            (16, 0, "é".to_string()),
+            (16, 2, String::new()),
+        ]
+    );
+
+    // Typing is only reported once the add buffer is flushed
+    editor.add_char('!');
+    assert_eq!(changes.borrow().len(), 6);
+    editor.persist_changes();
+    assert_eq!(changes.borrow().last(), Some(&(16, 0, "!".to_string())));
+}
+
+#[test]
+fn test_diff_since_load_patches_the_loaded_text_into_the_current_text() {
+    let original = "first line\nsecond line\nthird line".to_string();
+    let mut editor = Editor::new(original.clone(), 4);
+    assert!(editor.diff_since_load().is_empty());
+
+    editor.move_cursor_to(1, 0);
+    editor.delete_line();
+    editor.delete_range(5, 10);
+    editor.insert_str(" row");
+    editor.move_cursor_to(1, 10);
+    for c in "!?".chars() {
+        editor.add_char(c);
+    }
+
+    let edits = editor.diff_since_load();
+    let mut patched = original;
+    for edit in &edits {
+        edit.apply(&mut patched);
+    }
+    assert_eq!(patched, editor.get_text());
+    assert_eq!(editor.get_text(), "first row\nthird line!?");
+    // The edits are ordered and never overlap
+    assert!(edits.windows(2).all(|pair| pair[0].offset + pair[0].inserted.len() <= pair[1].offset));
+}
+
+#[test]
+fn test_diff_since_load_is_empty_when_edits_cancel_out() {
+    let mut editor = Editor::new("abc".to_string(), 4);
+    editor.delete_range(1, 2);
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "abc");
+    assert!(editor.diff_since_load().is_empty());
+
+    editor.move_cursor_to(0, 3);
+    editor.delete_char(KeyCode::Backspace);
+    editor.add_char('c');
+    assert_eq!(editor.get_text(), "abc");
+    assert!(editor.diff_since_load().is_empty());
+}
+
+#[test]
+fn test_undo_after_enter_removes_the_new_line() {
+    let mut editor = Editor::new("ab".to_string(), 5);
+    editor.add_char('c');
+    editor.move_cursor_to_line_start();
+    editor.add_new_line();
+    assert_eq!(editor.get_text(), "\nabc");
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "abc");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (0, 0));
+
+    // The earlier edit is still undone at the right offsets
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "ab");
+}
+
+#[test]
+fn test_redo_after_undo_then_enter_is_a_no_op() {
+    let mut editor = Editor::new("ab".to_string(), 5);
+    editor.add_char('c');
+    editor.undo_change();
+    assert!(editor.can_redo());
+
+    editor.move_cursor_to_line_start();
+    editor.add_new_line();
+    assert!(!editor.can_redo());
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "\nab");
+}
+
+#[test]
+fn test_one_undo_removes_the_new_line_and_its_indentation() {
+    let mut editor = Editor::new("    let x = 1;".to_string(), 5);
+    editor.add_new_line();
+    assert_eq!(editor.get_text(), "    let x = 1;\n    ");
+
+    editor.undo_change();
+    assert_eq!(editor.get_text(), "    let x = 1;");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (14, 0));
+
+    editor.redo_change();
+    assert_eq!(editor.get_text(), "    let x = 1;\n    ");
+    assert_eq!((editor.cursor.x, editor.cursor.y), (4, 1));
+}
+
+#[test]
+fn test_write_lines_to_applies_the_pending_buffers() {
+    let mut editor = Editor::new("first\nsecond line\nthird\nfourth".to_string(), 50);
+    let check = |editor: &Editor| {
+        let text = editor.get_text();
+        let lines: Vec<&str> = text.split('\n').collect();
+        for start in 0..=lines.len() {
+            for end in start..=lines.len() + 1 {
+                let mut out = Vec::new();
+                editor.write_lines_to(&mut out, start..end).unwrap();
+                let mut expected = lines[start.min(lines.len())..end.min(lines.len())].join("\n");
+                if end < lines.len() && start < end {
+                    expected.push('\n');
+                }
+                assert_eq!(String::from_utf8(out).unwrap(), expected, "lines {}..{}", start, end);
+            }
+        }
+        assert_eq!(editor.text_length(), text.len());
+        assert_eq!(editor.stats(), EditorStats::from_text(&text));
+    };
+    check(&editor);
+
+    // A pending deletion across a line break, then typing with a new line where it was
+    editor.move_cursor_to(1, 2);
+    for _ in 0..4 {
+        editor.delete_char(KeyCode::Backspace);
+    }
+    assert!(!editor.temporary_delete_buffer.is_empty());
+    check(&editor);
+
+    editor.add_char('é');
+    editor.add_char('\n');
+    editor.add_char('x');
+    check(&editor);
 }
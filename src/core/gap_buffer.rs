@@ -0,0 +1,472 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::prelude::TextTrait;
+
+/// Initial size of the gap, and the least it grows by when an insertion does not fit.
+const MIN_GAP: usize = 64;
+
+/// A text buffer that keeps a single gap of free space at the last edit position.
+///
+/// The text lives in one byte vector split into the part before the gap and the part
+/// after it. Insertions and deletions at the gap are cheap, moving the gap costs the
+/// distance it travels. It implements the same `TextTrait` as `PieceTable`, so the
+/// two backends can be compared on the same workloads. Like the piece table, every
+/// position is a byte offset and edits that would split a multibyte character are refused.
+#[derive(Debug, Clone)]
+pub struct GapBuffer {
+    buffer: Vec<u8>,
+    gap_start: usize,
+    gap_end: usize,
+}
+
+impl TextTrait for GapBuffer {
+    /// Creates a new `GapBuffer` holding the given text, with the gap at the end.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let gb = GapBuffer::new("hello");
+    /// assert_eq!(gb.get_text(), "hello");
+    /// ```
+    fn new(text: &str) -> Self {
+        let mut buffer = Vec::with_capacity(text.len() + MIN_GAP);
+        buffer.extend_from_slice(text.as_bytes());
+        buffer.resize(text.len() + MIN_GAP, 0);
+
+        GapBuffer {
+            buffer,
+            gap_start: text.len(),
+            gap_end: text.len() + MIN_GAP,
+        }
+    }
+
+    /// Inserts new text at the specified byte offset, moving the gap there first.
+    ///
+    /// # Returns
+    /// * `Ok(())` if insertion was successful.
+    /// * `Err(String)` with an error message if the position is beyond the text
+    ///   or inside a multibyte character.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut gb = GapBuffer::new("abc");
+    /// gb.add_text("X", 1).unwrap();
+    /// assert_eq!(gb.get_text(), "aXbc");
+    /// ```
+    fn add_text(&mut self, text: &str, position: usize) -> Result<(), String> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let total_len = self.total_length();
+        if position > total_len {
+            return Err(format!(
+                "Position {} is beyond text length {}",
+                position, total_len
+            ));
+        }
+
+        if !self.is_char_boundary(position) {
+            return Err(format!(
+                "Position {} is inside a multibyte character",
+                position
+            ));
+        }
+
+        self.move_gap(position);
+        self.ensure_gap(text.len());
+        self.buffer[self.gap_start..self.gap_start + text.len()].copy_from_slice(text.as_bytes());
+        self.gap_start += text.len();
+        Ok(())
+    }
+
+    /// Returns the text before the gap followed by the text after it.
+    fn get_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Deletes the text in `start..end` by moving the gap to `start` and widening it over the range.
+    ///
+    /// # Returns
+    /// * `Ok(())` if deletion was successful
+    /// * `Err(String)` with error message if parameters are invalid, including
+    ///   indices that fall inside a multibyte character
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut gb = GapBuffer::new("abcdef");
+    /// gb.delete_text(2, 4).unwrap();
+    /// assert_eq!(gb.get_text(), "abef");
+    /// ```
+    fn delete_text(&mut self, start: usize, end: usize) -> Result<(), String> {
+        let total_len = self.total_length();
+
+        if start > total_len {
+            return Err(format!(
+                "Start index {} is beyond text length {}",
+                start, total_len
+            ));
+        }
+
+        if end > total_len {
+            return Err(format!(
+                "End index {} is beyond text length {}",
+                end, total_len
+            ));
+        }
+
+        if start > end {
+            return Err(format!(
+                "Start index {} cannot be greater than end index {}",
+                start, end
+            ));
+        }
+
+        if !self.is_char_boundary(start) || !self.is_char_boundary(end) {
+            return Err(format!(
+                "Range {}..{} splits a multibyte character",
+                start, end
+            ));
+        }
+
+        if start == end {
+            return Ok(());
+        }
+
+        self.move_gap(start);
+        self.gap_end += end - start;
+        Ok(())
+    }
+
+    /// Replaces the text in `start..end` with `text`, validating the range before changing anything.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut gb = GapBuffer::new("hello world");
+    /// gb.replace_text(6, 11, "there").unwrap();
+    /// assert_eq!(gb.get_text(), "hello there");
+    /// ```
+    fn replace_text(&mut self, start: usize, end: usize, text: &str) -> Result<(), String> {
+        self.delete_text(start, end)?;
+        self.add_text(text, start)
+    }
+
+    /// Returns the text in `start..end`, joining the two sides when the range spans the gap.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut gb = GapBuffer::new("abcdef");
+    /// gb.add_text("XY", 3).unwrap();
+    /// assert_eq!(gb.get_text_range(2, 6).unwrap(), "cXYd");
+    /// ```
+    fn get_text_range(&self, start: usize, end: usize) -> Result<String, String> {
+        self.check_range(start, end)?;
+
+        // Only the requested bytes are copied and checked, not the whole text
+        let (before_gap, after_gap) = self.range_bytes(start, end);
+        let mut bytes = Vec::with_capacity(end - start);
+        bytes.extend_from_slice(before_gap);
+        bytes.extend_from_slice(after_gap);
+        Ok(String::from_utf8(bytes).expect("a range between char boundaries is valid UTF-8"))
+    }
+
+    /// Writes the text in `start..end` to `w`, the part before the gap and then the part after it.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut gb = GapBuffer::new("abcdef");
+    /// gb.add_text("XY", 3).unwrap();
+    /// let mut out = Vec::new();
+    /// gb.write_range_to(&mut out, 2, 6).unwrap();
+    /// assert_eq!(out, b"cXYd");
+    /// ```
+    fn write_range_to<W: Write>(&self, w: &mut W, start: usize, end: usize) -> io::Result<()> {
+        self.check_range(start, end)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let (before_gap, after_gap) = self.range_bytes(start, end);
+        w.write_all(before_gap)?;
+        w.write_all(after_gap)
+    }
+
+    /// Returns the character starting at byte offset `position`, or `None` if `position`
+    /// is at or past the end of the text or inside a multibyte character.
+    fn char_at(&self, position: usize) -> Option<char> {
+        if position >= self.total_length() || !self.is_char_boundary(position) {
+            return None;
+        }
+
+        // The gap never splits a character, so all of its bytes are on the same side
+        let side = if position < self.gap_start {
+            &self.buffer[position..self.gap_start]
+        } else {
+            &self.buffer[position + self.gap_len()..]
+        };
+        let width = match side[0] {
+            byte if byte < 0x80 => 1,
+            byte if byte < 0xE0 => 2,
+            byte if byte < 0xF0 => 3,
+            _ => 4,
+        };
+        std::str::from_utf8(side.get(..width)?).ok()?.chars().next()
+    }
+
+    /// Returns the length of the text in bytes, not counting the gap.
+    fn total_length(&self) -> usize {
+        self.buffer.len() - self.gap_len()
+    }
+}
+
+impl fmt::Display for GapBuffer {
+    /// Writes the text on both sides of the gap.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = self.as_str_pair();
+        f.write_str(before)?;
+        f.write_str(after)
+    }
+}
+
+impl GapBuffer {
+    /// Returns the number of free bytes currently in the gap.
+    pub fn gap_len(&self) -> usize {
+        self.gap_end - self.gap_start
+    }
+
+    /// Returns `true` if the byte offset `position` is the start or end of a character.
+    /// Positions beyond the end of the text are not boundaries.
+    pub fn is_char_boundary(&self, position: usize) -> bool {
+        let total_len = self.total_length();
+        if position >= total_len {
+            return position == total_len;
+        }
+
+        let byte = if position < self.gap_start {
+            self.buffer[position]
+        } else {
+            self.buffer[position + self.gap_len()]
+        };
+        // UTF-8 continuation bytes look like 0b10xx_xxxx
+        byte & 0xC0 != 0x80
+    }
+
+    /// Checks that `start..end` lies within the text and on character boundaries.
+    fn check_range(&self, start: usize, end: usize) -> Result<(), String> {
+        let total_len = self.total_length();
+
+        if start > total_len {
+            return Err(format!(
+                "Start index {} is beyond text length {}",
+                start, total_len
+            ));
+        }
+
+        if end > total_len {
+            return Err(format!(
+                "End index {} is beyond text length {}",
+                end, total_len
+            ));
+        }
+
+        if start > end {
+            return Err(format!(
+                "Start index {} cannot be greater than end index {}",
+                start, end
+            ));
+        }
+
+        if !self.is_char_boundary(start) || !self.is_char_boundary(end) {
+            return Err(format!(
+                "Range {}..{} splits a multibyte character",
+                start, end
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the bytes of `start..end` that lie before the gap and after it.
+    fn range_bytes(&self, start: usize, end: usize) -> (&[u8], &[u8]) {
+        let gap_len = self.gap_len();
+        let before_gap = &self.buffer[start.min(self.gap_start)..end.min(self.gap_start)];
+        let after_gap = &self.buffer[start.max(self.gap_start) + gap_len..end.max(self.gap_start) + gap_len];
+        (before_gap, after_gap)
+    }
+
+    /// Returns the text before and after the gap. Both halves are valid UTF-8 because
+    /// the gap only ever stops on character boundaries.
+    fn as_str_pair(&self) -> (&str, &str) {
+        let before = std::str::from_utf8(&self.buffer[..self.gap_start])
+            .expect("text before the gap is valid UTF-8");
+        let after = std::str::from_utf8(&self.buffer[self.gap_end..])
+            .expect("text after the gap is valid UTF-8");
+        (before, after)
+    }
+
+    /// Moves the gap so it starts at the byte offset `position` of the text.
+    fn move_gap(&mut self, position: usize) {
+        if position < self.gap_start {
+            let moved = self.gap_start - position;
+            self.buffer.copy_within(position..self.gap_start, self.gap_end - moved);
+            self.gap_start = position;
+            self.gap_end -= moved;
+        } else if position > self.gap_start {
+            let moved = position - self.gap_start;
+            self.buffer.copy_within(self.gap_end..self.gap_end + moved, self.gap_start);
+            self.gap_start = position;
+            self.gap_end += moved;
+        }
+    }
+
+    /// Grows the gap so at least `needed` bytes fit in it, doubling the buffer when it has to grow.
+    fn ensure_gap(&mut self, needed: usize) {
+        if self.gap_len() >= needed {
+            return;
+        }
+
+        let extra = (needed - self.gap_len()).max(self.buffer.len()).max(MIN_GAP);
+        let after_len = self.buffer.len() - self.gap_end;
+        self.buffer.resize(self.buffer.len() + extra, 0);
+        let new_gap_end = self.buffer.len() - after_len;
+        self.buffer.copy_within(self.gap_end..self.gap_end + after_len, new_gap_end);
+        self.gap_end = new_gap_end;
+    }
+}
+
+
+#[cfg(test)]
+use crate::core::piece_table::PieceTable;
+
+/// Runs an edit scenario against a backend and returns the text after every step,
+/// so the gap buffer can be checked step by step against the piece table.
+#[cfg(test)]
+fn replay<T: TextTrait>(initial: &str, steps: &[(&str, usize, usize)]) -> Vec<Result<String, String>> {
+    let mut text = T::new(initial);
+    steps
+        .iter()
+        .map(|&(insert, start, end)| {
+            let result = if insert.is_empty() {
+                text.delete_text(start, end)
+            } else if start == end {
+                text.add_text(insert, start)
+            } else {
+                text.replace_text(start, end, insert)
+            };
+            result.map(|_| text.get_text())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn assert_parity(initial: &str, steps: &[(&str, usize, usize)]) {
+    assert_eq!(
+        replay::<GapBuffer>(initial, steps),
+        replay::<PieceTable>(initial, steps)
+    );
+}
+
+#[test]
+fn test_gap_buffer_initialization() {
+    let gb = GapBuffer::new("Hello, world!");
+    assert_eq!(gb.get_text(), "Hello, world!");
+    assert_eq!(gb.total_length(), 13);
+    assert_eq!(gb.gap_len(), MIN_GAP);
+
+    let gb = GapBuffer::new("");
+    assert_eq!(gb.get_text(), "");
+    assert_eq!(gb.total_length(), 0);
+}
+
+#[test]
+fn test_parity_insertions() {
+    // test_get_text
+    assert_parity("Hello world", &[("!", 11, 11), ("Say: ", 0, 0), (" beautiful", 10, 10)]);
+    // test_get_text_with_alphabet_and_inserts
+    assert_parity("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &[("123", 3, 3), ("456", 9, 9), ("789", 16, 16)]);
+    // test_add_text_at_beginning / test_add_text_at_end / in the middle
+    assert_parity("world!", &[("Hello, ", 0, 0)]);
+    assert_parity("Hello", &[(", world!", 5, 5)]);
+    assert_parity("Hello world", &[("beautiful ", 5, 5)]);
+
+    let steps = replay::<GapBuffer>("Hello world", &[("!", 11, 11), ("Say: ", 0, 0), (" beautiful", 10, 10)]);
+    assert_eq!(steps.last().unwrap().as_deref(), Ok("Say: Hello beautiful world!"));
+}
+
+#[test]
+fn test_parity_deletions() {
+    // test_delete_single_piece
+    assert_parity("ABCXXXXDEF", &[("", 3, 7)]);
+    assert_parity("DEFXXXXGHI", &[("ABC", 0, 0), ("", 6, 10)]);
+    // test_delete_mid_piece_keeps_the_right_hand_slice
+    assert_parity("abcdef", &[("", 2, 4)]);
+    // test_delete_text_to_the_end_of_a_piece / at the start of a piece
+    assert_parity("DEFGHIXXXX", &[("ABC", 0, 0), ("", 9, 13)]);
+    assert_parity("XXXXABCDEF", &[("", 0, 4)]);
+    assert_parity("XXXXDEFGHI", &[("ABC", 0, 0), ("", 3, 7)]);
+    // test_delete_across_multiple_pieces
+    assert_parity("ABCDEFGHIJ", &[("123", 2, 2), ("XYZ", 8, 8), ("", 3, 11)]);
+
+    let steps = replay::<GapBuffer>("ABCDEFGHIJ", &[("123", 2, 2), ("XYZ", 8, 8), ("", 3, 11)]);
+    assert_eq!(steps.last().unwrap().as_deref(), Ok("AB1FGHIJ"));
+}
+
+#[test]
+fn test_parity_errors_and_multibyte_characters() {
+    // test_add_text_after_multibyte_character
+    assert_parity("café", &[("x", 4, 4), ("x", 5, 5)]);
+    // test_delete_text_rejects_splitting_multibyte_character
+    assert_parity("naïve", &[("", 2, 3), ("", 3, 5), ("", 2, 4)]);
+    // test_add_text_to_an_empty_table
+    assert_parity("", &[("", 0, 0), ("", 0, 1), ("x", 1, 1), ("hello", 0, 0)]);
+    // test_replace_text_validates_before_changing_anything
+    assert_parity("añb", &[("x", 2, 3), ("x", 3, 2), ("x", 0, 10)]);
+    // test_is_char_boundary_across_pieces
+    assert_parity("añb", &[("€", 1, 1), ("", 2, 3), ("", 1, 4)]);
+}
+
+#[test]
+fn test_parity_replace_text() {
+    // test_replace_text_with_shorter_equal_and_longer_text
+    assert_parity(
+        "one two three",
+        &[("2", 4, 7), ("ONE", 0, 3), ("thirty three", 6, 11), ("!", 3, 3), ("", 5, 7)],
+    );
+}
+
+#[test]
+fn test_gap_grows_for_long_insertions() {
+    let mut gb = GapBuffer::new("ab");
+    let long = "x".repeat(MIN_GAP * 3);
+    gb.add_text(&long, 1).unwrap();
+    assert_eq!(gb.get_text(), format!("a{}b", long));
+
+    // Typing character by character at moving positions keeps both halves intact
+    let mut pt = PieceTable::new(&gb.get_text());
+    for (i, c) in "héllo wörld".chars().enumerate() {
+        let position = (i * 7) % gb.total_length();
+        let position = (position..).find(|&p| gb.is_char_boundary(p)).unwrap();
+        let s = c.to_string();
+        gb.add_text(&s, position).unwrap();
+        pt.add_text(&s, position).unwrap();
+    }
+    assert_eq!(gb.get_text(), pt.get_text());
+    assert_eq!(gb.total_length(), pt.total_length());
+}
+
+#[test]
+fn test_get_text_range_and_char_at_across_the_gap() {
+    let mut gb = GapBuffer::new("añb");
+    gb.add_text("€", 1).unwrap();
+    let pt = PieceTable::new("a€ñb");
+
+    for start in 0..=gb.total_length() {
+        assert_eq!(gb.char_at(start), pt.char_at(start));
+        for end in 0..=gb.total_length() {
+            assert_eq!(gb.get_text_range(start, end), pt.get_text_range(start, end));
+        }
+    }
+}
@@ -1,11 +1,19 @@
-use crate::prelude::TextTrait;
+use std::cell::OnceCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::prelude::{TextEdit, TextTrait};
 //https://docs.rs/crossterm/latest/crossterm/
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PieceTable {
     original_buffer: String,
     add_buffer: String,
     pieces: Vec<Piece>,
+    /// Prefix sums of the piece lengths: `piece_ends[i]` is the text offset where piece `i` ends.
+    /// Built lazily on the first lookup and reset whenever `pieces` changes.
+    piece_ends: OnceCell<Vec<usize>>,
 }
 
 #[derive(Debug, Clone)]
@@ -29,24 +37,30 @@ impl TextTrait for PieceTable {
     ///
     /// # Returns
     /// A new `PieceTable` instance containing the provided text as the original buffer.
+    /// An empty text gives a table with no pieces at all.
     ///
     /// # Example
     /// ```
+    /// # use rust_text_editor::prelude::*;
     /// let pt = PieceTable::new("hello");
     /// assert_eq!(pt.get_text(), "hello");
     /// ```
     fn new(text: &str) -> Self {
         let original_buffer = text.to_string();
-        let pieces: Vec<Piece> = vec![Piece {
-            buffer_type: BufferType::Original,
-            start: 0,
-            length: original_buffer.len(),
-        }];
+        let mut pieces: Vec<Piece> = Vec::new();
+        if !original_buffer.is_empty() {
+            pieces.push(Piece {
+                buffer_type: BufferType::Original,
+                start: 0,
+                length: original_buffer.len(),
+            });
+        }
 
         PieceTable {
             original_buffer,
             add_buffer: String::new(),
             pieces,
+            piece_ends: OnceCell::new(),
         }
     }
 
@@ -58,14 +72,16 @@ impl TextTrait for PieceTable {
     ///
     /// # Arguments
     /// * `text` - The text to insert.
-    /// * `position` - The position (0-based) at which to insert the text.
+    /// * `position` - The byte offset (0-based) at which to insert the text.
     ///
     /// # Returns
     /// * `Ok(())` if insertion was successful.
-    /// * `Err(String)` with an error message if the position is invalid.
+    /// * `Err(String)` with an error message if the position is beyond the text
+    ///   or inside a multibyte character.
     ///
     /// # Example
     /// ```
+    /// # use rust_text_editor::prelude::*;
     /// let mut pt = PieceTable::new("abc");
     /// pt.add_text("X", 1).unwrap();
     /// assert_eq!(pt.get_text(), "aXbc");
@@ -83,6 +99,13 @@ impl TextTrait for PieceTable {
             ));
         }
 
+        if !self.is_char_boundary(position) {
+            return Err(format!(
+                "Position {} is inside a multibyte character",
+                position
+            ));
+        }
+
         // Add the new text to the add buffer and create a piece for it
         let new_piece_start_position = self.add_buffer.len();
         self.add_buffer.push_str(text);
@@ -94,22 +117,17 @@ impl TextTrait for PieceTable {
                 start: new_piece_start_position,
                 length: text.len(),
             });
+            self.piece_ends.take();
+            debug_assert!(self.validate().is_ok(), "{:?}", self.validate());
             return Ok(());
         }
 
-        // Find where to insert the new piece
-        let mut current_pos = 0;
-        let mut insert_idx = self.pieces.len(); // Default to end if not found
-        let mut split_offset = 0;
-
-        for (index, piece) in self.pieces.iter().enumerate() {
-            // Find the piece where the insertion should happen
-            if position <= current_pos + piece.length {
-                insert_idx = index;
-                split_offset = position - current_pos;
-                break;
-            }
-            current_pos += piece.length;
+        // Find where to insert the new piece. At a boundary between two pieces the insertion goes
+        // at the end of the left one, so typing right after added text can extend its piece
+        let (mut insert_idx, mut split_offset) = self.position_to_piece(position);
+        if split_offset == 0 && insert_idx > 0 {
+            insert_idx -= 1;
+            split_offset = self.pieces[insert_idx].length;
         }
 
         if insert_idx == self.pieces.len() {
@@ -119,6 +137,13 @@ impl TextTrait for PieceTable {
                 start: new_piece_start_position,
                 length: text.len(),
             });
+        } else if split_offset == self.pieces[insert_idx].length
+            && self.pieces[insert_idx].buffer_type == BufferType::Added
+            && self.pieces[insert_idx].start + self.pieces[insert_idx].length == new_piece_start_position
+        {
+            // Typing right after text that was just added: the new text directly follows this piece
+            // in the add buffer, so the piece grows instead of a new one being inserted
+            self.pieces[insert_idx].length += text.len();
         } else {
             // Insert in the middle - need to split an existing piece
             let piece = self.pieces[insert_idx].clone();
@@ -161,40 +186,28 @@ impl TextTrait for PieceTable {
             }
         }
 
+        self.piece_ends.take();
+        debug_assert!(self.validate().is_ok(), "{:?}", self.validate());
         Ok(())
     }
 
     /// Returns the full text represented by the piece table as a `String`.
     ///
-    /// This method reconstructs the current state of the text by iterating
-    /// through all pieces and concatenating their corresponding slices from
-    /// the original and added buffers.
+    /// This is a convenience wrapper over the `Display` implementation, which
+    /// concatenates the slice of every piece from the original and added buffers.
     ///
     /// # Returns
     /// A `String` containing the current text.
     ///
     /// # Example
     /// ```
+    /// # use rust_text_editor::prelude::*;
     /// let mut pt = PieceTable::new("abc");
     /// pt.add_text("X", 1).unwrap();
     /// assert_eq!(pt.get_text(), "aXbc");
     /// ```
     fn get_text(&self) -> String {
-        let mut result = String::new();
-
-        // Iterate over each piece and append its text to the result
-        for piece in self.pieces.iter() {
-            match piece.buffer_type {
-                BufferType::Original => {
-                    PieceTable::get_text_from_buffer(&self.original_buffer, &mut result, piece);
-                }
-                BufferType::Added => {
-                    PieceTable::get_text_from_buffer(&self.add_buffer, &mut result, piece);
-                }
-            };
-        }
-
-        result
+        self.to_string()
     }
 
     /// Deletes a range of text from the piece table using start and end indices.
@@ -211,7 +224,8 @@ impl TextTrait for PieceTable {
     ///
     /// # Returns
     /// * `Ok(())` if deletion was successful
-    /// * `Err(String)` with error message if parameters are invalid
+    /// * `Err(String)` with error message if parameters are invalid, including
+    ///   indices that fall inside a multibyte character
     ///
     /// # Note
     /// This uses the standard range convention where `start` is inclusive and `end` is exclusive,
@@ -219,6 +233,7 @@ impl TextTrait for PieceTable {
     ///
     /// # Example
     /// ```
+    /// # use rust_text_editor::prelude::*;
     /// let mut pt = PieceTable::new("abcdef");
     /// pt.delete_text(2, 4).unwrap();
     /// assert_eq!(pt.get_text(), "abef");
@@ -248,41 +263,28 @@ impl TextTrait for PieceTable {
             ));
         }
 
+        if !self.is_char_boundary(start) || !self.is_char_boundary(end) {
+            return Err(format!(
+                "Range {}..{} splits a multibyte character",
+                start, end
+            ));
+        }
+
         // Handle trivial case - nothing to delete (empty range)
         if start == end {
             return Ok(());
         }
 
-        // Find pieces affected by the deletion by walking through the piece sequence
-        let mut current_pos = 0; // Current position in the logical text
-        let mut start_piece_idx = None; // Index of piece containing deletion start
-        let mut end_piece_idx = None; // Index of piece containing deletion end
-        let mut start_offset = 0; // Offset within start piece where deletion begins
-        let mut end_offset = 0; // Offset within end piece where deletion ends
-
-        for (i, piece) in self.pieces.iter().enumerate() {
-            let piece_end = current_pos + piece.length;
-
-            // Find the piece containing the start position
-            if start_piece_idx.is_none() && start >= current_pos && start < piece_end {
-                start_piece_idx = Some(i);
-                start_offset = start - current_pos;
-            }
-
-            // Find the piece containing the end position
-            // Note: end can equal piece_end (deletion ends at piece boundary)
-            if end > current_pos && end <= piece_end {
-                end_piece_idx = Some(i);
-                end_offset = end - current_pos;
-                break;
-            }
-
-            current_pos = piece_end;
+        // Find the pieces affected by the deletion. The start piece is the one holding the first
+        // deleted character, the end piece the one holding the last, so a deletion ending at a
+        // piece boundary ends at the end of the left piece
+        let (start_idx, start_offset) = self.position_to_piece(start);
+        let (mut end_idx, mut end_offset) = self.position_to_piece(end);
+        if end_offset == 0 && end_idx > 0 {
+            end_idx -= 1;
+            end_offset = self.pieces[end_idx].length;
         }
 
-        let start_idx = start_piece_idx.ok_or("Could not find start piece")?;
-        let end_idx = end_piece_idx.unwrap_or(self.pieces.len() - 1);
-
         // Build new piece sequence without the deleted content
         let mut new_pieces = Vec::new();
 
@@ -318,39 +320,519 @@ impl TextTrait for PieceTable {
 
         // Replace the old piece sequence with the new one
         self.pieces = new_pieces;
+        self.piece_ends.take();
+
+        debug_assert!(self.validate().is_ok(), "{:?}", self.validate());
         Ok(())
     }
+
+    /// Replaces the text between `start` (inclusive) and `end` (exclusive) with `text`.
+    ///
+    /// The range is validated like `delete_text` before anything changes, so on error
+    /// the table is left untouched. Afterwards `text` starts at `start`.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the replacement was successful.
+    /// * `Err(String)` if the range is invalid.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("hello world");
+    /// pt.replace_text(6, 11, "there").unwrap();
+    /// assert_eq!(pt.get_text(), "hello there");
+    /// ```
+    fn replace_text(&mut self, start: usize, end: usize, text: &str) -> Result<(), String> {
+        self.delete_text(start, end)?;
+        // `start` was a valid boundary before the deletion and nothing before it moved
+        self.add_text(text, start)
+    }
+
+    /// Returns the text between the byte offsets `start` (inclusive) and `end` (exclusive).
+    ///
+    /// Only the pieces overlapping the range are read, so this is cheaper than
+    /// `get_text` when only part of the document is needed.
+    ///
+    /// # Returns
+    /// * `Ok(String)` with the text in the range.
+    /// * `Err(String)` if the range is invalid, using the same checks as `delete_text`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("abcdef");
+    /// pt.add_text("XY", 3).unwrap();
+    /// assert_eq!(pt.get_text_range(2, 6).unwrap(), "cXYd");
+    /// ```
+    fn get_text_range(&self, start: usize, end: usize) -> Result<String, String> {
+        self.check_range(start, end)?;
+        Ok(self.range_slices(start, end).collect())
+    }
+
+    /// Writes the text in `start..end` to `w` one piece at a time.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("abcdef");
+    /// pt.add_text("XY", 3).unwrap();
+    /// let mut out = Vec::new();
+    /// pt.write_range_to(&mut out, 2, 6).unwrap();
+    /// assert_eq!(out, b"cXYd");
+    /// ```
+    fn write_range_to<W: Write>(&self, w: &mut W, start: usize, end: usize) -> io::Result<()> {
+        self.check_range(start, end)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        for slice in self.range_slices(start, end) {
+            w.write_all(slice.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the character starting at byte offset `position`, or `None` if `position`
+    /// is at or past the end of the text or inside a multibyte character.
+    /// The piece is found with `position_to_piece`, so the whole text is never built.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let pt = PieceTable::new("añb");
+    /// assert_eq!(pt.char_at(1), Some('ñ'));
+    /// assert_eq!(pt.char_at(2), None);
+    /// assert_eq!(pt.char_at(3), Some('b'));
+    /// assert_eq!(pt.char_at(4), None);
+    /// ```
+    fn char_at(&self, position: usize) -> Option<char> {
+        if position >= self.total_length() {
+            return None;
+        }
+
+        let (index, offset) = self.position_to_piece(position);
+        let piece = &self.pieces[index];
+        let buffer = match piece.buffer_type {
+            BufferType::Original => &self.original_buffer,
+            BufferType::Added => &self.add_buffer,
+        };
+        // A piece always holds whole characters, so slicing it can't split one
+        buffer[piece.start..piece.start + piece.length]
+            .get(offset..)?
+            .chars()
+            .next()
+    }
+
+    /// Calculates the total length of text represented by all pieces, in bytes.
+    /// Same as `total_length_bytes`; every position in the table is a byte offset.
+    fn total_length(&self) -> usize {
+        self.total_length_bytes()
+    }
 }
 
-/// Calculates the number of characters to delete given a start and end position (inclusive).
-///
-/// # Arguments
-///
-/// * `start` - The starting index of the deletion (inclusive).
-/// * `end` - The ending index of the deletion (inclusive).
-///
-/// # Returns
-///
-/// The number of characters to delete.
-///
-/// # Example
-///
-/// ```
-/// let size = get_delete_size(3, 5);
-/// assert_eq!(size, 3); // Deletes positions 3, 4, and 5
-/// ```
-fn get_delete_size(start: usize, end: usize) -> usize {
-    end - start + 1
+impl fmt::Display for PieceTable {
+    /// Writes the slice of every piece in order, so the text can be streamed to a
+    /// writer without building the whole document in a `String` first.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("abc");
+    /// pt.add_text("X", 1).unwrap();
+    /// assert_eq!(format!("[{}]", pt), "[aXbc]");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for slice in self.piece_slices() {
+            f.write_str(slice)?;
+        }
+        Ok(())
+    }
 }
 
 impl PieceTable {
-    pub(crate) fn get_text_from_buffer(buffer: &str, result: &mut String, piece: &Piece) {
-        result.push_str(&buffer[piece.start..(piece.start + piece.length)].to_string())
+    /// Returns the number of lines, i.e. the number of `\n` plus one.
+    /// A text ending in `\n` has an empty last line, and an empty text has one empty line.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// assert_eq!(PieceTable::new("a\nb").line_count(), 2);
+    /// assert_eq!(PieceTable::new("a\nb\n").line_count(), 3);
+    /// ```
+    pub fn line_count(&self) -> usize {
+        self.piece_slices()
+            .map(|slice| slice.bytes().filter(|&byte| byte == b'\n').count())
+            .sum::<usize>()
+            + 1
+    }
+
+    /// Returns line `n` (0-based) without its `\n`, or `None` if the text has fewer lines.
+    /// Only the pieces up to the end of that line are read.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("first\nthird\n");
+    /// pt.add_text("second\n", 6).unwrap();
+    /// assert_eq!(pt.get_line(1), Some("second".to_string()));
+    /// assert_eq!(pt.get_line(3), Some(String::new()));
+    /// assert_eq!(pt.get_line(4), None);
+    /// ```
+    pub fn get_line(&self, n: usize) -> Option<String> {
+        let mut line_index = 0;
+        let mut line = String::new();
+
+        for slice in self.piece_slices() {
+            for part in slice.split_inclusive('\n') {
+                if line_index == n {
+                    match part.strip_suffix('\n') {
+                        Some(rest) => {
+                            line.push_str(rest);
+                            return Some(line);
+                        }
+                        None => line.push_str(part),
+                    }
+                } else if part.ends_with('\n') {
+                    line_index += 1;
+                }
+            }
+        }
+
+        // The last line has no newline after it
+        (line_index == n).then_some(line)
+    }
+
+    /// Writes the text to `w` one piece at a time, without building the whole text in a `String`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("abc");
+    /// pt.add_text("X", 1).unwrap();
+    /// let mut out = Vec::new();
+    /// pt.write_to(&mut out).unwrap();
+    /// assert_eq!(out, b"aXbc");
+    /// ```
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for slice in self.piece_slices() {
+            w.write_all(slice.as_bytes())?;
+        }
+        Ok(())
     }
 
-    /// Calculates the total length of text represented by all pieces
-    pub fn total_length(&self) -> usize {
-        self.pieces.iter().map(|p| p.length).sum()
+    /// Writes the lines in `lines` (0-based) to `w`, each followed by its `\n` except the last line
+    /// of the text. Reading stops at the end of the range, so only the pieces before it are visited.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let pt = PieceTable::new("one\ntwo\nthree\nfour");
+    /// let mut out = Vec::new();
+    /// pt.write_lines_to(&mut out, 1..3).unwrap();
+    /// assert_eq!(out, b"two\nthree\n");
+    /// ```
+    pub fn write_lines_to<W: Write>(&self, w: &mut W, lines: Range<usize>) -> io::Result<()> {
+        let mut line_index = 0;
+
+        for slice in self.piece_slices() {
+            for part in slice.split_inclusive('\n') {
+                if line_index >= lines.end {
+                    return Ok(());
+                }
+                if line_index >= lines.start {
+                    w.write_all(part.as_bytes())?;
+                }
+                if part.ends_with('\n') {
+                    line_index += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `start..end` lies within the text and on character boundaries.
+    fn check_range(&self, start: usize, end: usize) -> Result<(), String> {
+        let total_len = self.total_length();
+
+        if start > total_len {
+            return Err(format!(
+                "Start index {} is beyond text length {}",
+                start, total_len
+            ));
+        }
+
+        if end > total_len {
+            return Err(format!(
+                "End index {} is beyond text length {}",
+                end, total_len
+            ));
+        }
+
+        if start > end {
+            return Err(format!(
+                "Start index {} cannot be greater than end index {}",
+                start, end
+            ));
+        }
+
+        if !self.is_char_boundary(start) || !self.is_char_boundary(end) {
+            return Err(format!(
+                "Range {}..{} splits a multibyte character",
+                start, end
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the parts of the pieces that fall within `start..end`, in document order.
+    /// The pieces after the range are not visited.
+    fn range_slices(&self, start: usize, end: usize) -> impl Iterator<Item = &str> {
+        self.pieces
+            .iter()
+            .scan(0, |piece_start, piece| {
+                let range = (*piece_start, *piece_start + piece.length);
+                *piece_start += piece.length;
+                Some((range, piece))
+            })
+            .take_while(move |&((piece_start, _), _)| piece_start < end)
+            .filter(move |&((_, piece_end), _)| piece_end > start)
+            .map(move |((piece_start, piece_end), piece)| {
+                // Clamp the range to this piece and translate it into buffer offsets
+                let from = start.max(piece_start) - piece_start + piece.start;
+                let to = end.min(piece_end) - piece_start + piece.start;
+                let buffer = match piece.buffer_type {
+                    BufferType::Original => &self.original_buffer,
+                    BufferType::Added => &self.add_buffer,
+                };
+                &buffer[from..to]
+            })
+    }
+
+    /// Returns the text of each piece in document order.
+    fn piece_slices(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().map(|piece| {
+            let buffer = match piece.buffer_type {
+                BufferType::Original => &self.original_buffer,
+                BufferType::Added => &self.add_buffer,
+            };
+            &buffer[piece.start..piece.start + piece.length]
+        })
+    }
+
+    /// Returns the edits that turn the original text, the one the table was created with,
+    /// into the current text.
+    ///
+    /// Insertions only ever go to the add buffer, so the original pieces appear in the same
+    /// order as in the original text. The gaps between them are the deletions and the added
+    /// pieces are the insertions. A deletion and the insertions at the same place become one
+    /// edit, so there is at most one edit per gap. Text that an edit removes and inserts again,
+    /// like a deleted `c` that was typed back, is trimmed off both ends of the edit, and edits
+    /// with nothing left are dropped, so the patch never repeats unchanged text.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("one two three");
+    /// pt.replace_text(4, 7, "2").unwrap();
+    /// pt.add_text("!", 11).unwrap();
+    /// assert_eq!(
+    ///     pt.diff_from_original(),
+    ///     vec![
+    ///         TextEdit { offset: 4, removed_len: 3, inserted: "2".to_string() },
+    ///         TextEdit { offset: 11, removed_len: 0, inserted: "!".to_string() },
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff_from_original(&self) -> Vec<TextEdit> {
+        let mut edits = Vec::new();
+        let mut pending: Option<TextEdit> = None;
+        let mut original_position = 0;
+        let mut offset = 0;
+
+        for piece in &self.pieces {
+            match piece.buffer_type {
+                BufferType::Original => {
+                    if piece.start > original_position {
+                        pending
+                            .get_or_insert_with(|| TextEdit { offset, removed_len: 0, inserted: String::new() })
+                            .removed_len += piece.start - original_position;
+                    }
+                    if let Some(edit) = pending.take() {
+                        let removed = &self.original_buffer[original_position..piece.start];
+                        edits.extend(Self::trim_unchanged(edit, removed));
+                    }
+                    original_position = piece.start + piece.length;
+                }
+                BufferType::Added => {
+                    pending
+                        .get_or_insert_with(|| TextEdit { offset, removed_len: 0, inserted: String::new() })
+                        .inserted
+                        .push_str(&self.add_buffer[piece.start..piece.start + piece.length]);
+                }
+            }
+            offset += piece.length;
+        }
+
+        // Whatever is left of the original text after the last original piece was deleted
+        if original_position < self.original_buffer.len() {
+            pending
+                .get_or_insert_with(|| TextEdit { offset, removed_len: 0, inserted: String::new() })
+                .removed_len += self.original_buffer.len() - original_position;
+        }
+        if let Some(edit) = pending {
+            edits.extend(Self::trim_unchanged(edit, &self.original_buffer[original_position..]));
+        }
+        edits
+    }
+
+    /// Shrinks `edit` to the part that actually changes the text, given the `removed` text it
+    /// replaces, by skipping the characters that `removed` and `inserted` share at their start
+    /// and end. Returns `None` if the edit inserts back exactly what it removes.
+    fn trim_unchanged(edit: TextEdit, removed: &str) -> Option<TextEdit> {
+        let prefix = Self::common_length(removed.chars(), edit.inserted.chars());
+        let (removed, inserted) = (&removed[prefix..], &edit.inserted[prefix..]);
+        let suffix = Self::common_length(removed.chars().rev(), inserted.chars().rev());
+        let (removed, inserted) = (&removed[..removed.len() - suffix], &inserted[..inserted.len() - suffix]);
+
+        if removed.is_empty() && inserted.is_empty() {
+            return None;
+        }
+
+        Some(TextEdit {
+            offset: edit.offset + prefix,
+            removed_len: removed.len(),
+            inserted: inserted.to_string(),
+        })
+    }
+
+    /// Returns the length in bytes of the run of equal characters the two iterators start with.
+    fn common_length(a: impl Iterator<Item = char>, b: impl Iterator<Item = char>) -> usize {
+        a.zip(b).take_while(|(x, y)| x == y).map(|(c, _)| c.len_utf8()).sum()
+    }
+
+    /// Returns an iterator over the characters of the text, reading each piece from its
+    /// buffer in order instead of building the whole text first.
+    /// Pieces never split a multibyte character, so every `char` comes from a single piece.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("añb");
+    /// pt.add_text("é", 3).unwrap();
+    /// assert_eq!(pt.chars().collect::<Vec<_>>(), vec!['a', 'ñ', 'é', 'b']);
+    /// ```
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.piece_slices().flat_map(str::chars)
+    }
+
+    /// Returns the length of the text in bytes, read from the cached piece ends.
+    /// `TextTrait::total_length` returns the same value.
+    pub fn total_length_bytes(&self) -> usize {
+        self.piece_ends().last().copied().unwrap_or(0)
+    }
+
+    /// Returns the number of characters in the text, counted piece by piece.
+    /// Unlike the byte length this walks the text, so it costs O(n).
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let pt = PieceTable::new("héllo");
+    /// assert_eq!(pt.total_length_chars(), 5);
+    /// assert_eq!(pt.total_length_bytes(), 6);
+    /// ```
+    pub fn total_length_chars(&self) -> usize {
+        self.piece_slices().map(|slice| slice.chars().count()).sum()
+    }
+
+    /// Returns the index of the piece holding the character at byte offset `position`
+    /// and the offset of `position` within that piece, using a binary search over the
+    /// cached piece ends. A position on a boundary between two pieces belongs to the right one.
+    /// The end of the text maps to the end of the last piece, and an empty table to `(0, 0)`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut pt = PieceTable::new("abcdef");
+    /// pt.add_text("XY", 3).unwrap();
+    /// assert_eq!(pt.position_to_piece(2), (0, 2));
+    /// assert_eq!(pt.position_to_piece(3), (1, 0));
+    /// assert_eq!(pt.position_to_piece(6), (2, 1));
+    /// assert_eq!(pt.position_to_piece(8), (2, 3));
+    /// ```
+    pub fn position_to_piece(&self, position: usize) -> (usize, usize) {
+        let piece_ends = self.piece_ends();
+        let index = piece_ends.partition_point(|&end| end <= position);
+
+        if index == piece_ends.len() {
+            return match self.pieces.last() {
+                Some(last) => (index - 1, last.length),
+                None => (0, 0),
+            };
+        }
+
+        let piece_start = if index == 0 { 0 } else { piece_ends[index - 1] };
+        (index, position - piece_start)
+    }
+
+    /// Returns the cached prefix sums of the piece lengths, building them if a mutation reset them.
+    fn piece_ends(&self) -> &[usize] {
+        self.piece_ends.get_or_init(|| {
+            self.pieces
+                .iter()
+                .scan(0, |end, piece| {
+                    *end += piece.length;
+                    Some(*end)
+                })
+                .collect()
+        })
+    }
+
+    /// Returns `true` if the byte offset `position` is the start or end of a character,
+    /// i.e. text can be inserted or deleted there without splitting a multibyte character.
+    /// Positions beyond the end of the text are not boundaries.
+    pub fn is_char_boundary(&self, position: usize) -> bool {
+        if position > self.total_length() {
+            return false;
+        }
+
+        let (index, offset) = self.position_to_piece(position);
+        match self.pieces.get(index) {
+            Some(piece) => {
+                let buffer = match piece.buffer_type {
+                    BufferType::Original => &self.original_buffer,
+                    BufferType::Added => &self.add_buffer,
+                };
+                buffer.is_char_boundary(piece.start + offset)
+            }
+            None => true,
+        }
+    }
+
+    /// Checks that every piece references a valid range of its buffer.
+    ///
+    /// # Returns
+    /// * `Ok(())` if every piece's `start + length` is within its buffer's bounds.
+    /// * `Err(String)` describing the first invalid piece otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, piece) in self.pieces.iter().enumerate() {
+            let buffer = match piece.buffer_type {
+                BufferType::Original => &self.original_buffer,
+                BufferType::Added => &self.add_buffer,
+            };
+
+            let end = piece.start + piece.length;
+            if end > buffer.len() {
+                return Err(format!(
+                    "Piece {} ({:?}) ends at {} but its buffer has length {}",
+                    index,
+                    piece.buffer_type,
+                    end,
+                    buffer.len()
+                ));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -529,20 +1011,21 @@ fn test_multiple_middle_insertions() {
     assert_eq!(piece_table.pieces.len(), 5);
 
     // Insert another in the middle (after "Say: Hello beautiful", position 20)
+    // The text follows " beautiful" in the add buffer, so that piece grows
     let result = piece_table.add_text(" amazing", 20);
     assert!(result.is_ok());
     assert_eq!(piece_table.add_buffer, "!Say:  beautiful amazing");
-    assert_eq!(piece_table.pieces.len(), 6);
+    assert_eq!(piece_table.pieces.len(), 5);
 
     // Insert yet another in the middle (after "Say: Hello beautiful amazing", position 28)
     let result = piece_table.add_text(" and cool", 28);
     assert!(result.is_ok());
     assert_eq!(piece_table.add_buffer, "!Say:  beautiful amazing and cool");
-    assert_eq!(piece_table.pieces.len(), 7);
+    assert_eq!(piece_table.pieces.len(), 5);
 
     // Check the pieces
     // The expected sequence is:
-    // [Say: ] [Hello ] [beautiful] [ amazing] [ and cool] [world] [!]
+    // [Say: ] [Hello ] [beautiful amazing and cool] [world] [!]
     let p = &piece_table.pieces;
     assert_eq!(p[0].buffer_type, BufferType::Added); // Say:
     assert_eq!(p[0].start, 1);
@@ -552,25 +1035,22 @@ fn test_multiple_middle_insertions() {
     assert_eq!(p[1].start, 0);
     assert_eq!(p[1].length, 5);
 
-    assert_eq!(p[2].buffer_type, BufferType::Added); // beautiful
+    assert_eq!(p[2].buffer_type, BufferType::Added); // beautiful amazing and cool
     assert_eq!(p[2].start, 6);
-    assert_eq!(p[2].length, 10);
+    assert_eq!(p[2].length, 27);
 
-    assert_eq!(p[3].buffer_type, BufferType::Added); // amazing
-    assert_eq!(p[3].start, 16);
-    assert_eq!(p[3].length, 8);
-
-    assert_eq!(p[4].buffer_type, BufferType::Added); // and cool
-    assert_eq!(p[4].start, 24);
-    assert_eq!(p[4].length, 9);
+    assert_eq!(p[3].buffer_type, BufferType::Original); // world
+    assert_eq!(p[3].start, 5);
+    assert_eq!(p[3].length, 6);
 
-    assert_eq!(p[5].buffer_type, BufferType::Original); // world
-    assert_eq!(p[5].start, 5);
-    assert_eq!(p[5].length, 6);
+    assert_eq!(p[4].buffer_type, BufferType::Added); // !
+    assert_eq!(p[4].start, 0);
+    assert_eq!(p[4].length, 1);
 
-    assert_eq!(p[6].buffer_type, BufferType::Added); // !
-    assert_eq!(p[6].start, 0);
-    assert_eq!(p[6].length, 1);
+    assert_eq!(
+        piece_table.get_text(),
+        "Say: Hello beautiful amazing and cool world!"
+    );
 }
 
 #[test]
@@ -743,6 +1223,21 @@ fn test_delete_single_piece() {
     assert_eq!(text, "ABCDEFGHI");
 }
 
+#[test]
+fn test_delete_mid_piece_keeps_the_right_hand_slice() {
+    // Only [2, 4) is removed, the character at `end` stays in the right-hand slice
+    let mut piece_table = PieceTable::new("abcdef");
+    piece_table.delete_text(2, 4).unwrap();
+    assert_eq!(piece_table.get_text(), "abef");
+    assert_eq!(piece_table.total_length(), 4);
+
+    // Same inside an added piece
+    let mut piece_table = PieceTable::new("");
+    piece_table.add_text("abcdef", 0).unwrap();
+    piece_table.delete_text(2, 4).unwrap();
+    assert_eq!(piece_table.get_text(), "abef");
+}
+
 #[test]
 fn test_delete_text_to_the_end_of_a_piece() {
     // Test deletion from the end of text
@@ -863,3 +1358,385 @@ fn test_add_text_across_multiple_pieces() {
     assert_eq!(p[4].start, 5);
     assert_eq!(p[4].length, 5);
 }
+
+#[test]
+fn test_validate_healthy_table() {
+    let mut piece_table = PieceTable::new("Hello world");
+    piece_table.add_text("!", 11).unwrap();
+    piece_table.add_text("Say: ", 0).unwrap();
+    piece_table.delete_text(2, 8).unwrap();
+
+    assert!(piece_table.validate().is_ok());
+}
+
+#[test]
+fn test_validate_detects_corrupted_original_piece() {
+    let mut piece_table = PieceTable::new("Hello");
+    piece_table.pieces[0].length = 10;
+
+    assert!(piece_table.validate().is_err());
+}
+
+#[test]
+fn test_validate_detects_added_piece_beyond_add_buffer() {
+    let mut piece_table = PieceTable::new("Hello");
+    piece_table.add_text(" world", 5).unwrap();
+    piece_table.pieces[1].start = 3;
+
+    let result = piece_table.validate();
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("Added"));
+}
+
+#[test]
+fn test_add_text_after_multibyte_character() {
+    let mut pt = PieceTable::new("café");
+
+    // "é" takes two bytes, so the end of the text is byte 5
+    assert!(pt.add_text("x", 4).is_err());
+    assert_eq!(pt.get_text(), "café");
+
+    pt.add_text("x", 5).unwrap();
+    assert_eq!(pt.get_text(), "caféx");
+}
+
+#[test]
+fn test_delete_text_rejects_splitting_multibyte_character() {
+    let mut pt = PieceTable::new("naïve");
+
+    assert!(pt.delete_text(2, 3).is_err());
+    assert!(pt.delete_text(3, 5).is_err());
+    assert_eq!(pt.get_text(), "naïve");
+
+    pt.delete_text(2, 4).unwrap();
+    assert_eq!(pt.get_text(), "nave");
+}
+
+#[test]
+fn test_is_char_boundary_across_pieces() {
+    let mut pt = PieceTable::new("añb");
+    pt.add_text("€", 1).unwrap();
+    assert_eq!(pt.get_text(), "a€ñb");
+
+    let boundaries: Vec<usize> = (0..=pt.total_length())
+        .filter(|&position| pt.is_char_boundary(position))
+        .collect();
+    assert_eq!(boundaries, vec![0, 1, 4, 6, 7]);
+    assert!(!pt.is_char_boundary(8));
+}
+
+#[test]
+fn test_get_text_range_within_and_across_pieces() {
+    let mut pt = PieceTable::new("Hello world");
+    pt.add_text(", big", 5).unwrap();
+    assert_eq!(pt.get_text(), "Hello, big world");
+
+    // Inside a single piece
+    assert_eq!(pt.get_text_range(1, 4).unwrap(), "ell");
+    // Starting and ending mid-piece across three pieces
+    assert_eq!(pt.get_text_range(3, 13).unwrap(), "lo, big wo");
+    // Whole text and empty ranges
+    assert_eq!(pt.get_text_range(0, pt.total_length()).unwrap(), pt.get_text());
+    assert_eq!(pt.get_text_range(7, 7).unwrap(), "");
+}
+
+#[test]
+fn test_get_text_range_validates_bounds() {
+    let pt = PieceTable::new("añb");
+
+    assert!(pt.get_text_range(0, 5).is_err());
+    assert!(pt.get_text_range(5, 6).is_err());
+    assert!(pt.get_text_range(3, 1).is_err());
+    assert!(pt.get_text_range(0, 2).is_err()); // Splits "ñ"
+    assert_eq!(pt.get_text_range(1, 3).unwrap(), "ñ");
+}
+
+#[test]
+fn test_sequential_typing_extends_a_single_piece() {
+    let mut pt = PieceTable::new("");
+    for (position, c) in "abcdef".chars().enumerate() {
+        pt.add_text(&c.to_string(), position).unwrap();
+    }
+
+    assert_eq!(pt.get_text(), "abcdef");
+    assert_eq!(pt.pieces.len(), 1);
+    assert_eq!(pt.pieces[0].buffer_type, BufferType::Added);
+    assert_eq!(pt.pieces[0].length, 6);
+}
+
+#[test]
+fn test_non_contiguous_insertions_are_not_coalesced() {
+    let mut pt = PieceTable::new("Hello");
+    pt.add_text("a", 5).unwrap();
+    pt.add_text("b", 6).unwrap();
+    assert_eq!(pt.pieces.len(), 2);
+
+    // Inserting elsewhere and then after "ab" again cannot reuse the "ab" piece
+    pt.add_text("X", 0).unwrap();
+    pt.add_text("c", 8).unwrap();
+    assert_eq!(pt.get_text(), "XHelloabc");
+    assert_eq!(pt.pieces.len(), 4);
+}
+
+#[test]
+fn test_add_text_to_an_empty_table() {
+    let mut piece_table = PieceTable::new("");
+    assert!(piece_table.pieces.is_empty());
+    assert_eq!(piece_table.get_text(), "");
+    assert!(piece_table.delete_text(0, 0).is_ok());
+    assert!(piece_table.delete_text(0, 1).is_err());
+
+    piece_table.add_text("hello", 0).unwrap();
+    assert_eq!(piece_table.pieces.len(), 1);
+    assert_eq!(piece_table.pieces[0].buffer_type, BufferType::Added);
+    assert_eq!(piece_table.pieces[0].length, 5);
+    assert_eq!(piece_table.get_text(), "hello");
+
+    assert!(PieceTable::new("").add_text("x", 1).is_err());
+}
+
+#[test]
+fn test_replace_text_with_shorter_equal_and_longer_text() {
+    let mut pt = PieceTable::new("one two three");
+    pt.replace_text(4, 7, "2").unwrap();
+    assert_eq!(pt.get_text(), "one 2 three");
+
+    pt.replace_text(0, 3, "ONE").unwrap();
+    assert_eq!(pt.get_text(), "ONE 2 three");
+
+    pt.replace_text(6, 11, "thirty three").unwrap();
+    assert_eq!(pt.get_text(), "ONE 2 thirty three");
+
+    // An empty range inserts, empty text deletes
+    pt.replace_text(3, 3, "!").unwrap();
+    pt.replace_text(5, 7, "").unwrap();
+    assert_eq!(pt.get_text(), "ONE! thirty three");
+}
+
+#[test]
+fn test_replace_text_validates_before_changing_anything() {
+    let mut pt = PieceTable::new("añb");
+    assert!(pt.replace_text(2, 3, "x").is_err());
+    assert!(pt.replace_text(3, 2, "x").is_err());
+    assert!(pt.replace_text(0, 10, "x").is_err());
+    assert_eq!(pt.get_text(), "añb");
+}
+
+#[test]
+fn test_line_count_and_get_line() {
+    let pt = PieceTable::new("");
+    assert_eq!(pt.line_count(), 1);
+    assert_eq!(pt.get_line(0), Some(String::new()));
+    assert_eq!(pt.get_line(1), None);
+
+    // A line split over several pieces
+    let mut pt = PieceTable::new("one\nthree");
+    pt.add_text("tw", 4).unwrap();
+    pt.add_text("o\n", 6).unwrap();
+    pt.add_text("\n", 13).unwrap();
+    assert_eq!(pt.get_text(), "one\ntwo\nthree\n");
+    assert_eq!(pt.line_count(), 4);
+    assert_eq!(pt.get_line(0), Some("one".to_string()));
+    assert_eq!(pt.get_line(1), Some("two".to_string()));
+    assert_eq!(pt.get_line(2), Some("three".to_string()));
+    assert_eq!(pt.get_line(3), Some(String::new()));
+    assert_eq!(pt.get_line(4), None);
+
+    for (index, line) in pt.get_text().split('\n').enumerate() {
+        assert_eq!(pt.get_line(index).as_deref(), Some(line));
+    }
+}
+
+#[test]
+fn test_position_to_piece_follows_mutations() {
+    let mut pt = PieceTable::new("hello world");
+    assert_eq!(pt.position_to_piece(0), (0, 0));
+    assert_eq!(pt.position_to_piece(11), (0, 11));
+
+    pt.add_text(",", 5).unwrap();
+    assert_eq!(pt.total_length(), 12);
+    assert_eq!(pt.position_to_piece(4), (0, 4));
+    assert_eq!(pt.position_to_piece(5), (1, 0));
+    assert_eq!(pt.position_to_piece(6), (2, 0));
+    assert_eq!(pt.position_to_piece(12), (2, 6));
+
+    pt.delete_text(0, 6).unwrap();
+    assert_eq!(pt.get_text(), " world");
+    assert_eq!(pt.total_length(), 6);
+    assert_eq!(pt.position_to_piece(3), (0, 3));
+
+    assert_eq!(PieceTable::new("").position_to_piece(0), (0, 0));
+}
+
+#[test]
+fn test_char_at_across_pieces() {
+    let mut pt = PieceTable::new("héllo");
+    pt.add_text("€", 3).unwrap();
+    assert_eq!(pt.get_text(), "hé€llo");
+
+    let expected: Vec<(usize, char)> = pt.get_text().char_indices().collect();
+    for (position, c) in expected {
+        assert_eq!(pt.char_at(position), Some(c));
+    }
+    assert_eq!(pt.char_at(2), None); // inside 'é'
+    assert_eq!(pt.char_at(4), None); // inside '€'
+    assert_eq!(pt.char_at(pt.total_length()), None);
+    assert_eq!(PieceTable::new("").char_at(0), None);
+}
+
+#[test]
+fn test_display_matches_get_text() {
+    let mut pt = PieceTable::new("hello world");
+    pt.add_text("big ", 6).unwrap();
+    pt.delete_text(0, 1).unwrap();
+
+    assert_eq!(pt.to_string(), "ello big world");
+    assert_eq!(pt.to_string(), pt.get_text());
+    assert_eq!(PieceTable::new("").to_string(), "");
+}
+
+#[test]
+fn test_clone_is_independent_of_the_original() {
+    let mut pt = PieceTable::new("hello");
+    pt.add_text(" world", 5).unwrap();
+    let snapshot = pt.clone();
+
+    pt.delete_text(0, 6).unwrap();
+    pt.add_text("!", 5).unwrap();
+
+    assert_eq!(pt.get_text(), "world!");
+    assert_eq!(snapshot.get_text(), "hello world");
+    assert_eq!(snapshot.total_length(), 11);
+}
+
+#[test]
+fn test_chars_matches_get_text_across_pieces() {
+    let mut pt = PieceTable::new("héllo wörld");
+    pt.add_text("→ ", 0).unwrap();
+    pt.delete_text(9, 10).unwrap();
+    pt.add_text("ß", 9).unwrap();
+
+    assert_eq!(pt.chars().collect::<String>(), pt.get_text());
+    assert_eq!(pt.chars().count(), pt.get_text().chars().count());
+    assert_eq!(PieceTable::new("").chars().next(), None);
+}
+
+#[test]
+fn test_write_lines_to_spans_pieces() {
+    let mut pt = PieceTable::new("one\nthree\nfour");
+    pt.add_text("tw", 4).unwrap();
+    pt.add_text("o\n", 6).unwrap();
+
+    let mut out = Vec::new();
+    pt.write_lines_to(&mut out, 1..3).unwrap();
+    assert_eq!(out, b"two\nthree\n");
+
+    out.clear();
+    pt.write_lines_to(&mut out, 3..10).unwrap();
+    assert_eq!(out, b"four");
+
+    out.clear();
+    pt.write_to(&mut out).unwrap();
+    assert_eq!(out, pt.get_text().as_bytes());
+}
+
+#[test]
+fn test_diff_from_original_skips_text_that_was_restored() {
+    // Deleting a character and typing it back is no change at all
+    let mut pt = PieceTable::new("abc");
+    pt.delete_text(2, 3).unwrap();
+    pt.add_text("c", 2).unwrap();
+    assert_eq!(pt.get_text(), "abc");
+    assert!(pt.diff_from_original().is_empty());
+
+    // Replacing a word with a similar one only reports the characters that differ
+    let mut pt = PieceTable::new("the café is open");
+    pt.replace_text(4, 9, "cafés").unwrap();
+    assert_eq!(
+        pt.diff_from_original(),
+        vec![TextEdit { offset: 9, removed_len: 0, inserted: "s".to_string() }]
+    );
+}
+
+#[test]
+fn test_total_length_counts_bytes_and_chars() {
+    let mut pt = PieceTable::new("héllo");
+    assert_eq!(pt.total_length_chars(), 5);
+    assert_eq!(pt.total_length_bytes(), 6);
+
+    pt.add_text("ñ", 6).unwrap();
+    assert_eq!(pt.total_length_chars(), 6);
+    assert_eq!(pt.total_length_bytes(), 8);
+    assert_eq!(pt.total_length(), pt.total_length_bytes());
+}
+
+/// One step of the random edit sequences below. Positions are seeds, mapped into the
+/// current text so every step hits a valid char boundary.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum Edit {
+    Insert { at: usize, text: String },
+    Delete { at: usize, len: usize },
+}
+
+/// Returns the char boundary at or before `seed % (len + 1)` in `text`.
+#[cfg(test)]
+fn boundary_from_seed(text: &str, seed: usize) -> usize {
+    let mut position = seed % (text.len() + 1);
+    while !text.is_char_boundary(position) {
+        position -= 1;
+    }
+    position
+}
+
+#[cfg(test)]
+use proptest::prelude::*;
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn test_random_edits_match_a_string_oracle(
+        initial in "[a-cé€\n ]{0,20}",
+        edits in proptest::collection::vec(
+            prop_oneof![
+                (any::<usize>(), "[x-zñ😀\n]{1,4}")
+                    .prop_map(|(at, text)| Edit::Insert { at, text }),
+                (any::<usize>(), 0usize..8)
+                    .prop_map(|(at, len)| Edit::Delete { at, len }),
+            ],
+            1..40,
+        ),
+    ) {
+        let mut table = PieceTable::new(&initial);
+        let mut oracle = initial.clone();
+
+        for edit in edits {
+            match edit {
+                Edit::Insert { at, text } => {
+                    let position = boundary_from_seed(&oracle, at);
+                    table.add_text(&text, position).unwrap();
+                    oracle.insert_str(position, &text);
+                }
+                Edit::Delete { at, len } => {
+                    let start = boundary_from_seed(&oracle, at);
+                    let end = boundary_from_seed(&oracle, start + len.min(oracle.len() - start));
+                    let end = end.max(start);
+                    table.delete_text(start, end).unwrap();
+                    oracle.replace_range(start..end, "");
+                }
+            }
+
+            prop_assert_eq!(table.get_text(), oracle.clone());
+            prop_assert_eq!(table.total_length(), oracle.len());
+            prop_assert!(table.validate().is_ok());
+
+            let edits = table.diff_from_original();
+            let mut patched = initial.clone();
+            for edit in &edits {
+                prop_assert!(edit.removed_len > 0 || !edit.inserted.is_empty());
+                edit.apply(&mut patched);
+            }
+            prop_assert_eq!(patched, oracle.clone());
+        }
+    }
+}
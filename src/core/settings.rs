@@ -0,0 +1,126 @@
+use std::{fs, path::Path};
+
+/// Per-project editor settings read from a simple `key = value` config file.
+/// Keys that are not present in the file are left as `None` so the editor keeps its defaults.
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    pub tab_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub max_line_length: Option<usize>,
+    pub auto_persist_ms: Option<u64>,
+    pub auto_indent: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+impl Settings {
+    /// Reads and parses the settings file at `path`.
+    pub fn load(path: &Path) -> Result<Settings, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+        Settings::parse(&text)
+    }
+
+    /// Parses settings from `key = value` lines.
+    ///
+    /// Blank lines and lines starting with `#` or `;` are skipped, and unknown keys are ignored.
+    ///
+    /// # Returns
+    /// * `Err(String)` if a line has no `=` or a known key has an invalid value.
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("Line {} is not a key = value pair", index + 1))?;
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "tab_width" => settings.tab_width = Some(parse_number(&key, value)?),
+                "expand_tabs" => settings.expand_tabs = Some(parse_bool(&key, value)?),
+                "insert_final_newline" => {
+                    settings.insert_final_newline = Some(parse_bool(&key, value)?)
+                }
+                "max_line_length" => settings.max_line_length = Some(parse_number(&key, value)?),
+                "auto_persist_ms" => {
+                    settings.auto_persist_ms = Some(parse_number(&key, value)? as u64)
+                }
+                "auto_indent" => settings.auto_indent = Some(parse_bool(&key, value)?),
+                "trim_trailing_whitespace" => {
+                    settings.trim_trailing_whitespace = Some(parse_bool(&key, value)?)
+                }
+                _ => {} // Unknown keys are ignored
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+fn parse_number(key: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {}: {}", key, value))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("Invalid boolean for {}: {}", key, value)),
+    }
+}
+
+#[test]
+fn test_parse_sample_config() {
+    let config = "\
+# Project settings
+tab_width = 2
+expand_tabs = true
+
+; final newline on save
+insert_final_newline=false
+max_line_length = 100
+auto_persist_ms = 1500
+auto_indent = off
+trim_trailing_whitespace = yes
+";
+
+    let settings = Settings::parse(config).unwrap();
+    assert_eq!(
+        settings,
+        Settings {
+            tab_width: Some(2),
+            expand_tabs: Some(true),
+            insert_final_newline: Some(false),
+            max_line_length: Some(100),
+            auto_persist_ms: Some(1500),
+            auto_indent: Some(false),
+            trim_trailing_whitespace: Some(true),
+        }
+    );
+}
+
+#[test]
+fn test_parse_ignores_unknown_keys() {
+    let settings = Settings::parse("charset = utf-8\ntab_width = 8\nindent_style = tab").unwrap();
+
+    assert_eq!(settings.tab_width, Some(8));
+    assert_eq!(settings.expand_tabs, None);
+    assert_eq!(settings.insert_final_newline, None);
+    assert_eq!(settings.max_line_length, None);
+}
+
+#[test]
+fn test_parse_rejects_invalid_values() {
+    assert!(Settings::parse("tab_width = wide").is_err());
+    assert!(Settings::parse("expand_tabs = maybe").is_err());
+    assert!(Settings::parse("just some text").is_err());
+}
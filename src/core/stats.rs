@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+
+/// Counts shown in the status line while writing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EditorStats {
+    pub chars: usize,
+    pub words: usize,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+impl EditorStats {
+    /// Counts the characters, words, lines and bytes of `text`.
+    /// Words are separated by runs of whitespace, and an empty text still has one line.
+    pub fn from_text(text: &str) -> Self {
+        let mut counter = StatsCounter::default();
+        counter.count(text.as_bytes());
+        counter.finish()
+    }
+}
+
+/// Counts `EditorStats` for text written to it in parts, so a document can be counted one piece
+/// at a time instead of building it in a `String`. A character split between two writes is
+/// decoded once its last byte arrives.
+#[derive(Debug, Default)]
+pub struct StatsCounter {
+    stats: EditorStats,
+    newlines: usize,
+    in_word: bool,
+    code_point: u32,
+    missing_bytes: u32,
+}
+
+impl StatsCounter {
+    /// Returns the counts of everything written so far.
+    pub fn finish(self) -> EditorStats {
+        EditorStats {
+            lines: self.newlines + 1,
+            ..self.stats
+        }
+    }
+
+    fn count(&mut self, bytes: &[u8]) {
+        self.stats.bytes += bytes.len();
+
+        for &byte in bytes {
+            match byte {
+                0x00..=0x7F => self.count_char(byte as char),
+                // A continuation byte adds the next six bits of the character being decoded
+                0x80..=0xBF => {
+                    if self.missing_bytes == 0 {
+                        continue;
+                    }
+                    self.code_point = (self.code_point << 6) | (byte & 0x3F) as u32;
+                    self.missing_bytes -= 1;
+                    if self.missing_bytes == 0 {
+                        self.count_char(char::from_u32(self.code_point).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                }
+                0xC0..=0xDF => (self.code_point, self.missing_bytes) = ((byte & 0x1F) as u32, 1),
+                0xE0..=0xEF => (self.code_point, self.missing_bytes) = ((byte & 0x0F) as u32, 2),
+                _ => (self.code_point, self.missing_bytes) = ((byte & 0x07) as u32, 3),
+            }
+        }
+    }
+
+    fn count_char(&mut self, c: char) {
+        self.stats.chars += 1;
+        if c == '\n' {
+            self.newlines += 1;
+        }
+
+        let in_word = !c.is_whitespace();
+        if in_word && !self.in_word {
+            self.stats.words += 1;
+        }
+        self.in_word = in_word;
+    }
+}
+
+impl Write for StatsCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_stats_from_text() {
+    assert_eq!(
+        EditorStats::from_text(""),
+        EditorStats { chars: 0, words: 0, lines: 1, bytes: 0 }
+    );
+    assert_eq!(EditorStats::from_text("a   b").words, 2);
+    assert_eq!(
+        EditorStats::from_text("  héllo wörld\n\tagain\n"),
+        EditorStats { chars: 21, words: 3, lines: 3, bytes: 23 }
+    );
+}
+
+#[test]
+fn test_stats_counter_joins_characters_split_between_writes() {
+    let text = "añ b\u{3000}€x\n";
+    let mut counter = StatsCounter::default();
+    for byte in text.as_bytes() {
+        counter.write_all(&[*byte]).unwrap();
+    }
+
+    // U+3000 is a multibyte space, so it separates words too
+    assert_eq!(counter.finish(), EditorStats::from_text(text));
+    assert_eq!(EditorStats::from_text(text), EditorStats { chars: 8, words: 3, lines: 2, bytes: 13 });
+}
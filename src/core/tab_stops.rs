@@ -0,0 +1,121 @@
+/// Returns the visual column of the byte `column` in `line`, expanding every tab
+/// to the next multiple of `tab_width`.
+pub fn visual_column(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut visual = 0;
+    for (index, c) in line.char_indices() {
+        if index >= column {
+            break;
+        }
+
+        if c == '\t' {
+            visual += tab_width - visual % tab_width;
+        } else {
+            visual += 1;
+        }
+    }
+
+    visual
+}
+
+/// Returns the byte column of the character drawn at the visual column `visual` in `line`,
+/// the inverse of `visual_column`. A column inside a tab maps to the tab itself, and a column
+/// past the end of the line maps to the end of the line.
+pub fn column_at_visual(line: &str, visual: usize, tab_width: usize) -> usize {
+    let mut current = 0;
+    for (index, c) in line.char_indices() {
+        let width = if c == '\t' { tab_width - current % tab_width } else { 1 };
+        if visual < current + width {
+            return index;
+        }
+        current += width;
+    }
+
+    line.len()
+}
+
+/// Returns `line` with every tab replaced by spaces up to the next tab stop,
+/// so the rendered text lines up with `visual_column`.
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - expanded.chars().count() % tab_width;
+            expanded.push_str(&" ".repeat(spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+
+    expanded
+}
+
+/// Like `expand_tabs`, but draws every space as `·` and every tab as `→` followed by spaces
+/// up to the next tab stop. Each glyph takes one column, so the visual columns do not change.
+pub fn expand_tabs_visible(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - width % tab_width;
+                expanded.push('→');
+                expanded.push_str(&" ".repeat(spaces - 1));
+                width += spaces;
+            }
+            ' ' => {
+                expanded.push('·');
+                width += 1;
+            }
+            c => {
+                expanded.push(c);
+                width += 1;
+            }
+        }
+    }
+
+    expanded
+}
+
+#[test]
+fn test_visual_column_expands_tabs_to_tab_stops() {
+    let line = "\tab\tc";
+
+    assert_eq!(visual_column(line, 0, 4), 0);
+    assert_eq!(visual_column(line, 1, 4), 4);
+    assert_eq!(visual_column(line, 3, 4), 6);
+    assert_eq!(visual_column(line, 4, 4), 8);
+    assert_eq!(visual_column(line, 5, 4), 9);
+
+    assert_eq!(visual_column(line, 4, 8), 16);
+}
+
+#[test]
+fn test_expand_tabs() {
+    assert_eq!(expand_tabs("\tab\tc", 4), "    ab  c");
+    assert_eq!(expand_tabs("\tab\tc", 2), "  ab  c");
+    assert_eq!(expand_tabs("no tabs", 4), "no tabs");
+}
+
+#[test]
+fn test_column_at_visual_inverts_visual_column() {
+    let line = "\tab\tc";
+
+    assert_eq!(column_at_visual(line, 0, 4), 0);
+    assert_eq!(column_at_visual(line, 3, 4), 0);
+    assert_eq!(column_at_visual(line, 4, 4), 1);
+    assert_eq!(column_at_visual(line, 6, 4), 3);
+    assert_eq!(column_at_visual(line, 7, 4), 3);
+    assert_eq!(column_at_visual(line, 8, 4), 4);
+    assert_eq!(column_at_visual(line, 9, 4), 5);
+    assert_eq!(column_at_visual(line, 50, 4), 5);
+    assert_eq!(column_at_visual("añb", 2, 4), 3);
+}
+
+#[test]
+fn test_expand_tabs_visible_keeps_the_columns() {
+    let line = "a b\tc\t";
+    let visible = expand_tabs_visible(line, 4);
+
+    assert_eq!(visible, "a·b→c→  ");
+    assert_eq!(visible.chars().count(), expand_tabs(line, 4).chars().count());
+}
@@ -0,0 +1,29 @@
+/// One step of a patch: `removed_len` bytes at `offset` are replaced by `inserted`.
+///
+/// The edits of a patch are ordered by offset, and each `offset` is a byte offset into the
+/// text as it is after the earlier edits were applied, so applying them in order with
+/// `apply` turns the old text into the new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub offset: usize,
+    pub removed_len: usize,
+    pub inserted: String,
+}
+
+impl TextEdit {
+    /// Applies the edit to `text`.
+    ///
+    /// # Panics
+    /// If the removed range is out of bounds or does not lie on char boundaries, like `String::replace_range`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_text_editor::prelude::*;
+    /// let mut text = "hello world".to_string();
+    /// TextEdit { offset: 6, removed_len: 5, inserted: "there".to_string() }.apply(&mut text);
+    /// assert_eq!(text, "hello there");
+    /// ```
+    pub fn apply(&self, text: &mut String) {
+        text.replace_range(self.offset..self.offset + self.removed_len, &self.inserted);
+    }
+}
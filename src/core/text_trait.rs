@@ -1,6 +1,23 @@
+use std::io::{self, Write};
+
+/// Storage backend for the editor's text.
+///
+/// Every position and length is a byte offset into the UTF-8 text, and implementations
+/// refuse edits that would split a multibyte character.
 pub trait TextTrait {
     fn new(text: &str) -> Self;
     fn add_text(&mut self, text: &str, position: usize) -> Result<(), String>;
     fn get_text(&self) -> String;
     fn delete_text(&mut self, start: usize, end: usize) -> Result<(), String>;
+    fn replace_text(&mut self, start: usize, end: usize, text: &str) -> Result<(), String>;
+    /// Returns the text in `start..end`, with the same range checks as `delete_text`.
+    fn get_text_range(&self, start: usize, end: usize) -> Result<String, String>;
+    /// Writes the text in `start..end` to `w` without building it in a `String`, with the same range checks
+    /// as `get_text_range`. An invalid range is an `InvalidInput` error.
+    fn write_range_to<W: Write>(&self, w: &mut W, start: usize, end: usize) -> io::Result<()>;
+    /// Returns the character starting at `position`, or `None` past the end or inside a character.
+    fn char_at(&self, position: usize) -> Option<char>;
+    /// Returns the length of the text in bytes, not characters, i.e. the largest valid position.
+    /// It should be cheap, since the editor calls it on every cursor clamp.
+    fn total_length(&self) -> usize;
 }
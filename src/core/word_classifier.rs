@@ -0,0 +1,25 @@
+/// Decides which characters make up a word for word motions and word deletions.
+/// Characters that are neither word characters nor whitespace are treated as punctuation.
+pub trait WordClassifier {
+    fn is_word_char(&self, c: char) -> bool;
+}
+
+/// Treats letters, digits and `_` as word characters, like vim's default `iskeyword`.
+pub struct DefaultWordClassifier;
+
+impl WordClassifier for DefaultWordClassifier {
+    fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+}
+
+#[test]
+fn test_default_word_classifier() {
+    let classifier = DefaultWordClassifier;
+
+    assert!(classifier.is_word_char('a'));
+    assert!(classifier.is_word_char('9'));
+    assert!(classifier.is_word_char('_'));
+    assert!(!classifier.is_word_char('-'));
+    assert!(!classifier.is_word_char(' '));
+}
@@ -0,0 +1,221 @@
+use crate::core::word_classifier::WordClassifier;
+#[cfg(test)]
+use crate::core::word_classifier::DefaultWordClassifier;
+
+/// The kind of character used to find word boundaries.
+/// A word is a run of characters of the same class that is not whitespace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char, classifier: &dyn WordClassifier) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if classifier.is_word_char(c) {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Returns the character starting at byte `position`, if any.
+fn char_at(text: &str, position: usize) -> Option<char> {
+    text.get(position..).and_then(|t| t.chars().next())
+}
+
+/// Returns the character ending right before byte `position`, if any.
+fn char_before(text: &str, position: usize) -> Option<char> {
+    text.get(..position).and_then(|t| t.chars().next_back())
+}
+
+/// Returns the byte offset of the start of the next word after `position` (vim `w`).
+/// Returns the text length when there is no next word.
+pub fn next_word_start(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let mut position = position;
+
+    // Skip the rest of the current word
+    if let Some(c) = char_at(text, position) {
+        let class = char_class(c, classifier);
+        if class != CharClass::Whitespace {
+            while let Some(c) = char_at(text, position) {
+                if char_class(c, classifier) != class {
+                    break;
+                }
+                position += c.len_utf8();
+            }
+        }
+    }
+
+    // Skip the whitespace between the words
+    while let Some(c) = char_at(text, position) {
+        if char_class(c, classifier) != CharClass::Whitespace {
+            break;
+        }
+        position += c.len_utf8();
+    }
+
+    position.min(text.len())
+}
+
+/// Returns the byte offset of the start of the word before `position` (vim `b`).
+/// If `position` is inside a word, this is the start of that word.
+pub fn previous_word_start(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let mut position = position.min(text.len());
+
+    // Skip the whitespace before the cursor
+    while let Some(c) = char_before(text, position) {
+        if char_class(c, classifier) != CharClass::Whitespace {
+            break;
+        }
+        position -= c.len_utf8();
+    }
+
+    // Move back to the first character of the word
+    if let Some(c) = char_before(text, position) {
+        let class = char_class(c, classifier);
+        while let Some(c) = char_before(text, position) {
+            if char_class(c, classifier) != class {
+                break;
+            }
+            position -= c.len_utf8();
+        }
+    }
+
+    position
+}
+
+/// Returns the byte offset of the last character of the word at or after `position` (vim `e`).
+/// When the cursor is already on the end of a word, this moves to the end of the next word.
+pub fn word_end(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let mut position = position;
+
+    // Always move at least one character, like vim does
+    if let Some(c) = char_at(text, position) {
+        position += c.len_utf8();
+    }
+
+    // Skip the whitespace before the word
+    while let Some(c) = char_at(text, position) {
+        if char_class(c, classifier) != CharClass::Whitespace {
+            break;
+        }
+        position += c.len_utf8();
+    }
+
+    // Move to the last character of the word
+    if let Some(c) = char_at(text, position) {
+        let class = char_class(c, classifier);
+        while let Some(current) = char_at(text, position) {
+            match char_at(text, position + current.len_utf8()) {
+                Some(next) if char_class(next, classifier) == class => position += current.len_utf8(),
+                _ => break,
+            }
+        }
+    }
+
+    match char_at(text, position) {
+        Some(_) => position,
+        // Past the end of the text, stay on the last character
+        None => char_before(text, text.len()).map_or(0, |c| text.len() - c.len_utf8()),
+    }
+}
+
+/// Returns the byte offset just past the run of characters of the same class that starts at `position`,
+/// which is the range vim's `cw` changes. A whitespace run stops at the end of the line.
+pub fn run_end(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let mut end = position;
+
+    if let Some(first) = char_at(text, position) {
+        let class = char_class(first, classifier);
+        while let Some(c) = char_at(text, end) {
+            if char_class(c, classifier) != class || c == '\n' {
+                break;
+            }
+            end += c.len_utf8();
+        }
+    }
+
+    end
+}
+
+/// Returns the byte offset of the start of the run of characters of the same class that ends at `position`,
+/// the mirror of `run_end`. A whitespace run stops at the start of the line.
+pub fn run_start(text: &str, position: usize, classifier: &dyn WordClassifier) -> usize {
+    let mut start = position.min(text.len());
+
+    if let Some(last) = char_before(text, start) {
+        let class = char_class(last, classifier);
+        while let Some(c) = char_before(text, start) {
+            if char_class(c, classifier) != class || c == '\n' {
+                break;
+            }
+            start -= c.len_utf8();
+        }
+    }
+
+    start
+}
+
+#[test]
+fn test_next_word_start() {
+    let text = "foo.bar(  baz)  qux";
+
+    assert_eq!(next_word_start(text, 0, &DefaultWordClassifier), 3); // "foo" -> "."
+    assert_eq!(next_word_start(text, 3, &DefaultWordClassifier), 4); // "." -> "bar"
+    assert_eq!(next_word_start(text, 4, &DefaultWordClassifier), 7); // "bar" -> "("
+    assert_eq!(next_word_start(text, 7, &DefaultWordClassifier), 10); // "(" skips the spaces -> "baz"
+    assert_eq!(next_word_start(text, 13, &DefaultWordClassifier), 16); // ")" -> "qux"
+    assert_eq!(next_word_start(text, 16, &DefaultWordClassifier), text.len()); // No next word
+}
+
+#[test]
+fn test_previous_word_start() {
+    let text = "foo.bar(  baz)  qux";
+
+    assert_eq!(previous_word_start(text, text.len(), &DefaultWordClassifier), 16); // Inside "qux"
+    assert_eq!(previous_word_start(text, 16, &DefaultWordClassifier), 13); // Skips the spaces -> ")"
+    assert_eq!(previous_word_start(text, 13, &DefaultWordClassifier), 10); // ")" -> "baz"
+    assert_eq!(previous_word_start(text, 10, &DefaultWordClassifier), 7); // Skips the spaces -> "("
+    assert_eq!(previous_word_start(text, 5, &DefaultWordClassifier), 4); // Middle of "bar"
+    assert_eq!(previous_word_start(text, 4, &DefaultWordClassifier), 3); // "bar" -> "."
+    assert_eq!(previous_word_start(text, 0, &DefaultWordClassifier), 0);
+}
+
+#[test]
+fn test_word_end() {
+    let text = "foo.bar(  baz)  qux";
+
+    assert_eq!(word_end(text, 0, &DefaultWordClassifier), 2); // End of "foo"
+    assert_eq!(word_end(text, 2, &DefaultWordClassifier), 3); // Already at the end -> "."
+    assert_eq!(word_end(text, 4, &DefaultWordClassifier), 6); // End of "bar"
+    assert_eq!(word_end(text, 7, &DefaultWordClassifier), 12); // "(" skips the spaces -> end of "baz"
+    assert_eq!(word_end(text, 14, &DefaultWordClassifier), 18); // Whitespace -> end of "qux"
+    assert_eq!(word_end(text, 18, &DefaultWordClassifier), 18); // Stays on the last character
+}
+
+#[test]
+fn test_run_end() {
+    let text = "foo.bar(  baz)\n  qux";
+
+    assert_eq!(run_end(text, 0, &DefaultWordClassifier), 3); // Start of "foo"
+    assert_eq!(run_end(text, 1, &DefaultWordClassifier), 3); // Middle of "foo"
+    assert_eq!(run_end(text, 3, &DefaultWordClassifier), 4); // "."
+    assert_eq!(run_end(text, 8, &DefaultWordClassifier), 10); // The spaces before "baz"
+    assert_eq!(run_end(text, 14, &DefaultWordClassifier), 14); // Stops at the newline
+    assert_eq!(run_end(text, text.len(), &DefaultWordClassifier), text.len());
+}
+
+#[test]
+fn test_run_start() {
+    let text = "foo.bar(  baz)\n  qux";
+
+    assert_eq!(run_start(text, 3, &DefaultWordClassifier), 0); // End of "foo"
+    assert_eq!(run_start(text, 2, &DefaultWordClassifier), 0); // Middle of "foo"
+    assert_eq!(run_start(text, 4, &DefaultWordClassifier), 3); // "."
+    assert_eq!(run_start(text, 10, &DefaultWordClassifier), 8); // The spaces before "baz"
+    assert_eq!(run_start(text, 15, &DefaultWordClassifier), 15); // Stops at the newline
+    assert_eq!(run_start(text, 0, &DefaultWordClassifier), 0);
+}
@@ -0,0 +1,10 @@
+/// The most recent logical edit, recorded relative to the cursor so it can be
+/// repeated at another location (vim `.`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditOp {
+    /// Text inserted at the cursor. `position` is where the insertion happened,
+    /// used to coalesce consecutive persists of the same typing session.
+    Insert { text: String, position: usize },
+    /// A deleted range, given as the number of bytes removed before and after the cursor.
+    Delete { before_cursor: usize, after_cursor: usize },
+}
@@ -0,0 +1,49 @@
+/// The line terminator a document is written with on save.
+/// Lines are always separated by `\n` while editing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    /// `\n`, used on Linux and macOS
+    Lf,
+    /// `\r\n`, used on Windows
+    CrLf,
+}
+
+impl LineEnding {
+    /// Returns the line ending used by most lines of `text`, or `Lf` when there are no line breaks.
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf { LineEnding::CrLf } else { LineEnding::Lf }
+    }
+
+    /// Returns the characters that end a line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Returns the short name shown in the status area.
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    /// Returns the other line ending.
+    pub fn toggle(self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::CrLf,
+            LineEnding::CrLf => LineEnding::Lf,
+        }
+    }
+}
+
+#[test]
+fn test_detect_picks_the_dominant_line_ending() {
+    assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+    assert_eq!(LineEnding::detect("a\nb\nc\r\n"), LineEnding::Lf);
+    assert_eq!(LineEnding::detect("no line break"), LineEnding::Lf);
+}
@@ -0,0 +1,24 @@
+/// How the line-number gutter numbers each line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineNumberMode {
+    /// No gutter is rendered
+    Off,
+    /// 1-based line numbers
+    Absolute,
+    /// Distance from the cursor line, with the cursor line shown as 0
+    Relative,
+    /// Distance from the cursor line, with the cursor line showing its absolute number
+    Hybrid,
+}
+
+impl LineNumberMode {
+    /// Returns the mode that follows this one when cycling through the modes.
+    pub fn next(self) -> Self {
+        match self {
+            LineNumberMode::Off => LineNumberMode::Absolute,
+            LineNumberMode::Absolute => LineNumberMode::Relative,
+            LineNumberMode::Relative => LineNumberMode::Hybrid,
+            LineNumberMode::Hybrid => LineNumberMode::Off,
+        }
+    }
+}
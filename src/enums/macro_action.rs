@@ -0,0 +1,17 @@
+/// A single command captured while a macro is being recorded, replayed in order
+/// from wherever the cursor is when the macro runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroAction {
+    /// A character typed at the cursor
+    InsertChar(char),
+    /// Enter, including any auto-indentation it adds
+    NewLine,
+    /// Backspace, deleting the character before the cursor
+    Backspace,
+    /// Delete, deleting the character after the cursor
+    Delete,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+}
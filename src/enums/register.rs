@@ -0,0 +1,8 @@
+/// Text yanked into the editor's register, remembering how it should be pasted back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Register {
+    /// Characters spliced in at the cursor.
+    Inline(String),
+    /// Whole lines, stored without their trailing newline and pasted as new lines (vim `yy`).
+    Linewise(String),
+}
@@ -1,4 +1,8 @@
+/// A change applied to the piece table, recorded so it can be undone and redone.
+/// `cursor` is the text position of the cursor before the change, restored by undo.
 pub enum TextAction {
-    Add { text: String, position: usize },
-    Delete { text: String, position: usize },
-}
\ No newline at end of file
+    Add { text: String, position: usize, cursor: usize },
+    Delete { text: String, position: usize, cursor: usize },
+    /// Changes made by one command, applied in order and undone together as a single step.
+    Group(Vec<TextAction>),
+}
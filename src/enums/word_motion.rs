@@ -0,0 +1,10 @@
+/// Vim-style word motions used to move the cursor or to build the range of an operator like `dw`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordMotion {
+    /// `w`: to the start of the next word
+    NextWordStart,
+    /// `b`: to the start of the current or previous word
+    PreviousWordStart,
+    /// `e`: to the last character of the current or next word
+    WordEnd,
+}
@@ -0,0 +1,63 @@
+pub mod core {
+    pub mod clipboard;
+    pub mod editor;
+    pub mod gap_buffer;
+    pub mod piece_table;
+    pub mod position;
+    pub mod settings;
+    pub mod stats;
+    pub mod tab_stops;
+    pub mod text_edit;
+    pub mod text_trait;
+    pub mod word_classifier;
+    pub mod word_motion;
+}
+pub mod buffer {
+    pub mod temporary_buffer_add;
+    pub mod temporary_buffer_deletion;
+}
+pub mod ui {
+    pub mod output_manager;
+    pub mod cleanup;
+    pub mod highlighter;
+    pub mod split_view;
+    pub mod theme;
+}
+pub mod enums {
+    pub mod edit_op;
+    pub mod enum_add_result;
+    pub mod line_ending;
+    pub mod line_number_mode;
+    pub mod macro_action;
+    pub mod register;
+    pub mod text_action;
+    pub mod word_motion;
+}
+
+pub mod prelude {
+    pub use crate::buffer::temporary_buffer_add::*;
+    pub use crate::buffer::temporary_buffer_deletion::*;
+    pub use crate::core::clipboard::*;
+    pub use crate::core::editor::*;
+    pub use crate::core::gap_buffer::*;
+    pub use crate::core::piece_table::*;
+    pub use crate::core::position::*;
+    pub use crate::core::settings::*;
+    pub use crate::core::stats::*;
+    pub use crate::core::text_edit::*;
+    pub use crate::core::text_trait::*;
+    pub use crate::core::word_classifier::*;
+    pub use crate::enums::edit_op::*;
+    pub use crate::enums::enum_add_result::*;
+    pub use crate::enums::line_ending::*;
+    pub use crate::enums::line_number_mode::*;
+    pub use crate::enums::macro_action::*;
+    pub use crate::enums::register::*;
+    pub use crate::ui::cleanup::*;
+    pub use crate::ui::highlighter::*;
+    pub use crate::ui::output_manager::*;
+    pub use crate::ui::split_view::*;
+    pub use crate::ui::theme::*;
+    pub use crate::enums::text_action::*;
+    pub use crate::enums::word_motion::*;
+}
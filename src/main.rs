@@ -1,43 +1,18 @@
 use clap::Parser;
 use crossterm::{
-    event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
-    terminal,
+    event::{
+        poll, read, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute, terminal,
+};
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
-use std::{fs, io, path::PathBuf, time::Duration};
-
-mod core {
-    pub mod editor;
-    pub mod piece_table;
-    pub mod position;
-    pub mod text_trait;
-}
-mod buffer {
-    pub mod temporary_buffer_add;
-    pub mod temporary_buffer_deletion;
-}
-mod ui {
-    pub mod output_manager;
-    pub mod cleanup;
-}
-mod enums {
-    pub mod enum_add_result;
-    pub mod text_action;
-}
-
-mod prelude {
-    pub use crate::buffer::temporary_buffer_add::*;
-    pub use crate::buffer::temporary_buffer_deletion::*;
-    pub use crate::core::editor::*;
-    pub use crate::core::piece_table::*;
-    pub use crate::core::position::*;
-    pub use crate::core::text_trait::*;
-    pub use crate::enums::enum_add_result::*;
-    pub use crate::ui::cleanup::*;
-    pub use crate::ui::output_manager::*;
-    pub use crate::enums::text_action::*;
-}
 
-use prelude::*;
+use rust_text_editor::prelude::*;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -53,36 +28,164 @@ struct Args {
     /// Load text from file
     #[arg(long, value_name = "PATH")]
     file: Option<PathBuf>,
+
+    /// Load editor settings (tab_width, expand_tabs, insert_final_newline, max_line_length, auto_persist_ms, auto_indent, trim_trailing_whitespace) from a key=value file
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Character shown on the rows below the end of the document instead of the default '~'
+    #[arg(long, value_name = "CHAR")]
+    eob_marker: Option<char>,
+
+    /// Leave the rows below the end of the document empty instead of marking them
+    #[arg(long, conflicts_with = "eob_marker")]
+    no_eob_marker: bool,
+
+    /// Show the temporary add and delete buffers in the status area
+    #[arg(long)]
+    debug_buffers: bool,
+
+    /// Show 1-based line numbers in a gutter on the left
+    #[arg(long)]
+    line_numbers: bool,
+
+    /// Open the document for viewing only, every edit is refused
+    #[arg(long)]
+    readonly: bool,
+
+    /// Characters batched in the temporary add and delete buffers before they are written to the
+    /// piece table. Larger buffers persist less often, smaller ones undo in finer steps
+    #[arg(long, value_name = "N", default_value_t = 32, value_parser = parse_buffer_size)]
+    buffer_size: usize,
+}
+
+/// Parses `--buffer-size`, rejecting 0 since an empty buffer would have to persist on every keystroke.
+fn parse_buffer_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("the buffer size must be at least 1".to_string()),
+        Ok(size) => Ok(size),
+        Err(_) => Err(format!("'{}' is not a number", value)),
+    }
 }
 
 fn main() -> io::Result<()> {
     let args = Args::parse();
-    let _clean_up = CleanUp;
-    terminal::enable_raw_mode()?;
-    OutputManager::clear_screen()?;
+    let settings = match &args.config {
+        Some(path) => match Settings::load(path) {
+            Ok(settings) => Some(settings),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     let single_line_text = "Hello World";
     let multiple_lines_text = "Hello World\nThis is a text editor\nIt supports multiple lines\nAnd basic editing features";
 
     // Default to single if no parameter is passed
-    let initial_text = if args.single || (!args.single && !args.multi && args.file.is_none()) {
-        single_line_text.to_string()
+    let mut editor = if args.single || (!args.multi && args.file.is_none()) {
+        Editor::new(single_line_text.to_string(), args.buffer_size)
     } else if args.multi {
-        multiple_lines_text.to_string()
-    } else if let Some(path) = args.file {
-        fs::read_to_string(path).unwrap_or_else(|_| String::from("file not found"))
+        Editor::new(multiple_lines_text.to_string(), args.buffer_size)
+    } else if let Some(path) = &args.file {
+        match Editor::from_file(path, args.buffer_size) {
+            Ok(editor) => editor,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                // Start with an empty document, the first save creates the file
+                let mut editor = Editor::new(String::new(), args.buffer_size);
+                editor.file_path = Some(path.clone());
+                editor.set_status(format!("New file: {}", path.display()));
+                editor
+            }
+            Err(error) => {
+                eprintln!("Could not open {}: {}", path.display(), error);
+                std::process::exit(1);
+            }
+        }
     } else {
-        single_line_text.to_string()
+        Editor::new(single_line_text.to_string(), args.buffer_size)
     };
+    if args.no_eob_marker {
+        editor.eob_marker = None;
+    } else if args.eob_marker.is_some() {
+        editor.eob_marker = args.eob_marker;
+    }
+    editor.debug_buffers = args.debug_buffers;
+    editor.read_only = args.readonly;
+    if args.line_numbers {
+        editor.line_number_mode = LineNumberMode::Absolute;
+    }
+    if let Some(settings) = &settings {
+        if let Err(error) = editor.apply_settings(settings) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
 
-    let mut editor = Editor::new(initial_text, 5);
-    OutputManager::refresh_screen(&editor)?;
+    let (_, height) = terminal::size()?;
+    editor.resize(OutputManager::text_area_height(height) as usize);
+
+    let _clean_up = CleanUp;
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnableMouseCapture)?;
+    OutputManager::clear_screen()?;
+    let mut output_manager = OutputManager::default();
+    output_manager.refresh_screen(&editor)?;
+
+    // Poll often enough to honour a short auto-persist timeout
+    let poll_timeout = Duration::from_millis(editor.auto_persist_ms.unwrap_or(1000).clamp(10, 1000));
 
     loop {
-        if poll(Duration::from_millis(1000))? {
-            if let Event::Key(event) = read().expect("Failed to read line") {
+        if poll(poll_timeout)? {
+            let event = read()?;
+            if let Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) = event
+            {
+                // A left click moves the cursor to the clicked character
+                let (_, height) = terminal::size()?;
+                if let Some((line, visual_column)) =
+                    OutputManager::document_position_at(&editor, column, row, height)
+                {
+                    editor.clear_selection();
+                    editor.move_cursor_to_visual_position(line, visual_column);
+                    output_manager.refresh_screen(&editor)?;
+                }
+            } else if let Event::Resize(_, height) = event {
+                let text_area_height = OutputManager::text_area_height(height);
+                editor.resize(text_area_height as usize);
+                if let Some(split_view) = editor.split_view.as_mut() {
+                    split_view.scroll_to_cursor(editor.cursor.y as usize, text_area_height);
+                }
+                // The terminal may have reflowed what was on screen, so draw everything again
+                output_manager.invalidate();
+                output_manager.refresh_screen(&editor)?;
+            } else if let Event::Key(event) = event {
+                editor.register_keystroke(Instant::now());
                 let mut stop_loop = false;
                 match event {
+                    // While the search prompt is open, keys edit the query instead of the document
+                    KeyEvent { code, modifiers, .. } if editor.search_prompt.is_some() => match code {
+                        KeyCode::Esc => editor.cancel_search(),
+                        KeyCode::Enter => editor.confirm_search(),
+                        KeyCode::Backspace => editor.pop_search_char(),
+                        KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                            editor.push_search_char(c)
+                        }
+                        _ => {}
+                    },
+                    KeyEvent { code, .. } if editor.goto_prompt.is_some() => match code {
+                        KeyCode::Esc => editor.cancel_goto_line(),
+                        KeyCode::Enter => editor.confirm_goto_line(),
+                        KeyCode::Backspace => editor.pop_goto_digit(),
+                        KeyCode::Char(c) => editor.push_goto_digit(c),
+                        _ => {}
+                    },
                     KeyEvent {
                         code: key @ (KeyCode::Char('q') | KeyCode::Esc),
                         modifiers,
@@ -99,8 +202,9 @@ fn main() -> io::Result<()> {
                     } => {
                         if key == KeyCode::Delete && modifiers == KeyModifiers::CONTROL {
                             editor.delete_word(KeyCode::Delete);
-                        } else {
-                            editor.delete_char(key);
+                        } else if !editor.delete_char(key) {
+                            // Nothing was deleted, so there is nothing to redraw
+                            continue;
                         }
                     }
                     KeyEvent {
@@ -114,42 +218,226 @@ fn main() -> io::Result<()> {
                             editor.add_char(key.as_char().unwrap_or(' '));
                         }
                     }
+                    KeyEvent {
+                        code: KeyCode::F(2),
+                        modifiers,
+                        ..
+                    } => {
+                        // Shift+F2 moves between the panes of an existing split
+                        if modifiers != KeyModifiers::SHIFT {
+                            editor.toggle_split_view();
+                        } else if let Some(split_view) = editor.split_view.as_mut() {
+                            split_view.switch_pane();
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::F(3),
+                        modifiers,
+                        ..
+                    } => {
+                        if modifiers == KeyModifiers::SHIFT {
+                            editor.find_prev();
+                        } else {
+                            editor.find_next();
+                        }
+                    }
                     KeyEvent {
                         code: KeyCode::Enter,
                         ..
                     } => {
                         editor.add_new_line();
                     }
+                    KeyEvent {
+                        code: KeyCode::Tab,
+                        ..
+                    } => {
+                        if editor.selected_range().is_some() {
+                            editor.indent_selection();
+                        } else {
+                            editor.insert_tab();
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::BackTab,
+                        ..
+                    } => {
+                        editor.unindent_selection();
+                    }
+                    KeyEvent {
+                        code: direction @ (KeyCode::Up | KeyCode::Down),
+                        modifiers: KeyModifiers::CONTROL,
+                        ..
+                    } => {
+                        if direction == KeyCode::Up {
+                            editor.move_to_previous_nonblank();
+                        } else {
+                            editor.move_to_next_nonblank();
+                        }
+                    }
+                    KeyEvent {
+                        code: direction @ (KeyCode::Up | KeyCode::Down),
+                        modifiers: KeyModifiers::ALT,
+                        ..
+                    } => {
+                        editor.clear_selection();
+                        if direction == KeyCode::Up {
+                            editor.move_line_up();
+                        } else {
+                            editor.move_line_down();
+                        }
+                    }
+                    KeyEvent {
+                        code: key @ (KeyCode::Home | KeyCode::End),
+                        modifiers: KeyModifiers::CONTROL,
+                        ..
+                    } => {
+                        editor.clear_selection();
+                        if key == KeyCode::Home {
+                            editor.move_to_document_start();
+                        } else {
+                            editor.move_to_document_end();
+                        }
+                    }
+                    KeyEvent {
+                        code: key @ (KeyCode::PageUp | KeyCode::PageDown),
+                        ..
+                    } => {
+                        editor.clear_selection();
+                        if key == KeyCode::PageUp {
+                            editor.page_up();
+                        } else {
+                            editor.page_down();
+                        }
+                    }
+                    KeyEvent {
+                        code: key @ (KeyCode::Home | KeyCode::End),
+                        ..
+                    } => {
+                        editor.clear_selection();
+                        if key == KeyCode::Home {
+                            editor.move_cursor_to_line_start();
+                        } else {
+                            editor.move_cursor_to_line_end();
+                        }
+                    }
                     KeyEvent {
                         code:
                             direction @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down),
+                        modifiers,
                         ..
-                    } => match direction {
-                        KeyCode::Left => editor.move_cursor_left(),
-                        KeyCode::Right => editor.move_cursor_right(),
-                        KeyCode::Up => editor.move_cursor_up(),
-                        KeyCode::Down => editor.move_cursor_down(),
-                        _ => unreachable!(),
-                    },
+                    } => {
+                        // Shift extends the selection, any other movement clears it
+                        if modifiers == KeyModifiers::SHIFT {
+                            editor.start_selection();
+                        } else {
+                            editor.clear_selection();
+                        }
+
+                        match direction {
+                            KeyCode::Left => editor.move_cursor_left(),
+                            KeyCode::Right => editor.move_cursor_right(),
+                            KeyCode::Up => editor.move_cursor_up(),
+                            KeyCode::Down => editor.move_cursor_down(),
+                            _ => unreachable!(),
+                        }
+                    }
                     _ => {
                         if event.code == KeyCode::Char('z') && event.modifiers == KeyModifiers::CONTROL {
                             editor.undo_change();
+                        } else if (event.code == KeyCode::Char('y') && event.modifiers == KeyModifiers::CONTROL)
+                            || (matches!(event.code, KeyCode::Char('z' | 'Z'))
+                                && event.modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+                        {
+                            editor.redo_change();
+                        } else if event.code == KeyCode::Char('c') && event.modifiers == KeyModifiers::CONTROL {
+                            // Raw mode turns off the terminal's SIGINT handling, so Ctrl+C arrives here
+                            // as a key event and copies instead of quitting
+                            editor.copy_selection();
+                        } else if event.code == KeyCode::Char('d') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.clear_selection();
+                            editor.duplicate_line();
+                        } else if matches!(event.code, KeyCode::Char('k' | 'K'))
+                            && event.modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT
+                        {
+                            editor.clear_selection();
+                            editor.delete_line();
+                        } else if event.code == KeyCode::Char('a') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.select_all();
+                        } else if event.code == KeyCode::Char('v') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.paste_from_clipboard();
+                        } else if event.code == KeyCode::Char('s') && event.modifiers == KeyModifiers::CONTROL {
+                            if let Err(error) = editor.save() {
+                                editor.set_status(format!("Could not save: {}", error));
+                            }
+                        } else if event.code == KeyCode::Char('g') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.clear_selection();
+                            editor.start_goto_line();
+                        } else if event.code == KeyCode::Char('5') && event.modifiers == KeyModifiers::CONTROL {
+                            // Terminals send Ctrl+5 and Ctrl+] as the same control code
+                            editor.clear_selection();
+                            editor.jump_to_matching_bracket();
+                        } else if event.code == KeyCode::F(4) {
+                            editor.toggle_line_ending();
+                        } else if event.code == KeyCode::F(5) {
+                            if editor.recording {
+                                editor.stop_recording();
+                            } else {
+                                editor.start_recording();
+                            }
+                        } else if event.code == KeyCode::F(6) {
+                            editor.replay();
+                        } else if event.code == KeyCode::F(7) {
+                            editor.toggle_show_whitespace();
+                        } else if event.code == KeyCode::Char('f') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.start_search();
+                        } else if event.code == KeyCode::Char('r') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.repeat_last_edit();
+                        } else if event.code == KeyCode::Char('j') && event.modifiers == KeyModifiers::CONTROL {
+                            if editor.selected_range().is_some() {
+                                editor.join_selected_lines();
+                            } else {
+                                editor.join_lines();
+                            }
+                        } else if event.code == KeyCode::Char('e') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.move_to_line_end_for_insert();
+                        } else if event.code == KeyCode::Char('t') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.trim_current_line();
+                        } else if event.code == KeyCode::Char('l') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.toggle_line_number_mode();
+                        } else if event.code == KeyCode::Char('n') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.next_match();
+                        } else if event.code == KeyCode::Char('p') && event.modifiers == KeyModifiers::CONTROL {
+                            editor.previous_match();
                         } else if let KeyCode::Char(c) = event.code {
                             editor.add_char(c);
                         }
                     }
                 }
 
+                if let Some(split_view) = editor.split_view.as_mut() {
+                    let (_, height) = terminal::size()?;
+                    split_view.scroll_to_cursor(
+                        editor.cursor.y as usize,
+                        OutputManager::text_area_height(height),
+                    );
+                } else {
+                    editor.scroll_to_cursor();
+                }
+
                 if !stop_loop {
-                    OutputManager::refresh_screen(&editor)?;
+                    output_manager.refresh_screen(&editor)?;
                 } else {
                     break;
                 }
             };
         } else {
             // Timeout expired, no `Event` is available
-            // content.persist_temporary_buffer();
-            // OutputManager::refresh_screen(&content)?;
+            let now = Instant::now();
+            if editor.auto_persist_if_idle(now)
+                || editor.expire_status_message(now)
+            {
+                output_manager.refresh_screen(&editor)?;
+            }
         }
     }
 
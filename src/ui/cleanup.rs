@@ -1,7 +1,11 @@
 
-use crossterm::
-    terminal::{self}
-;
+use std::io;
+
+use crossterm::{
+    event::DisableMouseCapture,
+    execute,
+    terminal::{self},
+};
 
 use crate::ui::output_manager::OutputManager;
 
@@ -9,8 +13,16 @@ pub struct CleanUp;
 
 impl Drop for CleanUp {
     fn drop(&mut self) {
-        terminal::disable_raw_mode().expect("Could not disable raw mode");
-        OutputManager::clear_screen().expect("Could not clear screen");
+        // Panicking here while already unwinding from an error would abort, so restore the terminal on a best-effort basis
+        if let Err(error) = execute!(io::stdout(), DisableMouseCapture) {
+            eprintln!("Could not disable mouse capture: {}", error);
+        }
+        if let Err(error) = terminal::disable_raw_mode() {
+            eprintln!("Could not disable raw mode: {}", error);
+        }
+        if let Err(error) = OutputManager::clear_screen() {
+            eprintln!("Could not clear screen: {}", error);
+        }
     }
 }
 
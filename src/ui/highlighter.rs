@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use crossterm::style::Color;
+
+/// Colors parts of a line for syntax highlighting, without the editor knowing the language.
+/// `spans` returns byte ranges of `line` with their foreground colors, sorted and not overlapping.
+pub trait Highlighter {
+    fn spans(&self, line: &str) -> Vec<(Range<usize>, Color)>;
+}
+
+/// Highlights numbers and double-quoted strings, which read the same in most languages.
+/// A number has to start a word, so the digits in `row99` are left alone.
+/// A string runs to its closing quote, skipping quotes escaped with `\`, or to the end of the line.
+pub struct DefaultHighlighter;
+
+impl DefaultHighlighter {
+    pub const NUMBER_COLOR: Color = Color::Magenta;
+    pub const STRING_COLOR: Color = Color::Green;
+}
+
+impl Highlighter for DefaultHighlighter {
+    fn spans(&self, line: &str) -> Vec<(Range<usize>, Color)> {
+        let mut spans = Vec::new();
+        let mut chars = line.char_indices().peekable();
+        let mut previous: Option<char> = None;
+
+        while let Some((start, c)) = chars.next() {
+            if c == '"' {
+                let mut end = line.len();
+                let mut escaped = false;
+                for (index, c) in chars.by_ref() {
+                    if c == '"' && !escaped {
+                        end = index + 1;
+                        break;
+                    }
+                    escaped = c == '\\' && !escaped;
+                }
+                spans.push((start..end, DefaultHighlighter::STRING_COLOR));
+                previous = Some('"');
+                continue;
+            }
+
+            let starts_word = previous.is_none_or(|p| !(p.is_alphanumeric() || p == '_'));
+            if c.is_ascii_digit() && starts_word {
+                let mut end = start + 1;
+                while let Some(&(index, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '.' || c == '_') {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                spans.push((start..end, DefaultHighlighter::NUMBER_COLOR));
+                previous = Some('0');
+                continue;
+            }
+
+            previous = Some(c);
+        }
+
+        spans
+    }
+}
+
+#[test]
+fn test_default_highlighter_finds_numbers_and_strings() {
+    let spans = DefaultHighlighter.spans(r#"let x = 42 + 3.5; say("hi \"there\"", row99)"#);
+
+    assert_eq!(
+        spans,
+        vec![
+            (8..10, DefaultHighlighter::NUMBER_COLOR),
+            (13..16, DefaultHighlighter::NUMBER_COLOR),
+            (22..36, DefaultHighlighter::STRING_COLOR),
+        ]
+    );
+}
+
+#[test]
+fn test_default_highlighter_runs_an_unclosed_string_to_the_end() {
+    assert_eq!(DefaultHighlighter.spans("a \"open"), vec![(2..7, DefaultHighlighter::STRING_COLOR)]);
+    assert!(DefaultHighlighter.spans("no colors here").is_empty());
+}
@@ -1,15 +1,66 @@
-use std::io::{self, stdout, Write};
+use std::{
+    io::{self, stdout, Write},
+    ops::Range,
+    time::Instant,
+};
 
 use crossterm::{
-    cursor::{self, MoveTo, MoveToColumn, MoveToNextLine},
+    cursor::{self, MoveTo},
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
     terminal::{self, size, Clear, ClearType},
 };
 
-use crate::core::editor::Editor;
+use crate::{
+    buffer::{
+        temporary_buffer_add::TemporaryBufferAddText,
+        temporary_buffer_deletion::TemporaryBufferDeleteText,
+    },
+    core::{editor::Editor, tab_stops, text_trait::TextTrait},
+    enums::line_number_mode::LineNumberMode,
+    ui::split_view::SplitView,
+};
+
+/// Number of rows at the bottom of the terminal used by the status area.
+const STATUS_LINES: u16 = 4;
 
-pub struct OutputManager;
+/// One rendered screen: the bytes printed on each row, colors included, and where the cursor goes.
+/// Comparing two frames row by row tells which rows have to be redrawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: u16,
+    pub height: u16,
+    pub rows: Vec<Vec<u8>>,
+    pub cursor: (u16, u16),
+}
+
+impl Frame {
+    /// Creates a frame of empty rows for a terminal of the given size.
+    pub fn new(width: u16, height: u16) -> Self {
+        Frame {
+            width,
+            height,
+            rows: vec![Vec::new(); height as usize],
+            cursor: (0, 0),
+        }
+    }
+
+    /// Returns the bytes of row `index`, growing the frame if a tiny terminal puts a row past its height.
+    pub fn row(&mut self, index: u16) -> &mut Vec<u8> {
+        let index = index as usize;
+        if index >= self.rows.len() {
+            self.rows.resize(index + 1, Vec::new());
+        }
+        &mut self.rows[index]
+    }
+}
+
+/// Draws the editor to the terminal. It remembers the last frame it drew, so a refresh only
+/// rewrites the rows that changed instead of clearing the whole screen and flickering.
+#[derive(Default)]
+pub struct OutputManager {
+    previous_frame: Option<Frame>,
+}
 
 impl OutputManager {
     pub fn clear_screen() -> io::Result<()> {
@@ -20,59 +71,924 @@ impl OutputManager {
         )
     }
 
-    pub fn refresh_screen(content: &Editor) -> io::Result<()> {
-        OutputManager::clear_screen()?;
-        let mut stdout = stdout();
-        for line in content.get_text_lines() {
-            execute!(
-                stdout,
-                Print(format!("{}", line)),
-                MoveToNextLine(0), // Move to the next line
-                MoveToColumn(0),   // Ensure cursor is at column 0
-            )
-            .unwrap();
+    pub fn refresh_screen<T: TextTrait>(&mut self, content: &Editor<T>) -> io::Result<()> {
+        let (width, height) = size()?;
+        self.render_changes(&mut stdout(), content, width, height)
+    }
+
+    /// Forgets the last frame, so the next refresh clears the screen and draws everything.
+    pub fn invalidate(&mut self) {
+        self.previous_frame = None;
+    }
+
+    /// Draws the editor into `out`, rewriting only the rows that differ from the last frame.
+    /// The first frame, and any frame after the terminal size changed, is drawn in full.
+    pub fn render_changes<W: Write, T: TextTrait>(&mut self, out: &mut W, content: &Editor<T>, width: u16, height: u16) -> io::Result<()> {
+        let frame = OutputManager::build_frame(content, width, height)?;
+
+        // Forget the last frame until this one is on screen, so a failed write forces a full redraw
+        match self.previous_frame.take() {
+            Some(previous) if previous.width == width && previous.height == height => {
+                OutputManager::draw_changed_rows(out, &frame, &previous)?
+            }
+            _ => OutputManager::draw_frame(out, &frame)?,
         }
 
-        let text = content.get_text();
-        let (width, height) = size().unwrap();
+        self.previous_frame = Some(frame);
+        Ok(())
+    }
+
+    /// Draws the editor into `out` for a terminal of the given size, clearing the screen first.
+    /// Any write error is returned to the caller instead of panicking, so `CleanUp` can restore the terminal.
+    pub fn render<W: Write, T: TextTrait>(out: &mut W, content: &Editor<T>, width: u16, height: u16) -> io::Result<()> {
+        let frame = OutputManager::build_frame(content, width, height)?;
+        OutputManager::draw_frame(out, &frame)
+    }
+
+    /// Clears the screen and prints every row of `frame`.
+    fn draw_frame<W: Write>(out: &mut W, frame: &Frame) -> io::Result<()> {
+        execute!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        for (row, bytes) in (0..).zip(&frame.rows) {
+            if !bytes.is_empty() {
+                execute!(out, MoveTo(0, row))?;
+                out.write_all(bytes)?;
+            }
+        }
+
+        out.flush()?;
+        execute!(out, MoveTo(frame.cursor.0, frame.cursor.1))
+    }
+
+    /// Rewrites the rows of `frame` that differ from `previous`, clearing each one first.
+    /// The status lines are redrawn together, since a long status message wraps onto the rows below it.
+    fn draw_changed_rows<W: Write>(out: &mut W, frame: &Frame, previous: &Frame) -> io::Result<()> {
+        let status_start = frame.height.saturating_sub(STATUS_LINES) as usize;
+        let status_changed = frame.rows.get(status_start..) != previous.rows.get(status_start..);
+
+        for (row, bytes) in (0..).zip(&frame.rows) {
+            let changed = previous.rows.get(row as usize) != Some(bytes);
+            if changed || (status_changed && row as usize >= status_start) {
+                execute!(out, MoveTo(0, row), Clear(ClearType::CurrentLine))?;
+                out.write_all(bytes)?;
+            }
+        }
+
+        out.flush()?;
+        execute!(out, MoveTo(frame.cursor.0, frame.cursor.1))
+    }
+
+    /// Renders the editor into a `Frame` for a terminal of the given size.
+    pub fn build_frame<T: TextTrait>(content: &Editor<T>, width: u16, height: u16) -> io::Result<Frame> {
+        let mut frame = Frame::new(width, height);
+        // Only the visible lines are read from the document, never the whole text
+        let line_count = content.visual_line_count();
+        let gutter_width = OutputManager::gutter_width(content.line_number_mode, line_count);
+        let text_area_height = OutputManager::text_area_height(height);
+
+        let cursor_row = match &content.split_view {
+            Some(split_view) => {
+                let pane_rows = SplitView::pane_rows(text_area_height);
+                let pane_lines = split_view.pane_line_ranges(text_area_height, line_count);
+                for (rows, visible_lines) in pane_rows.into_iter().zip(pane_lines) {
+                    OutputManager::render_lines(&mut frame, content, visible_lines, rows.start, gutter_width)?;
+                }
+
+                execute!(
+                    frame.row(SplitView::divider_row(text_area_height)),
+                    SetForegroundColor(Color::DarkGrey),
+                    Print("─".repeat(width as usize)),
+                    ResetColor,
+                )?;
+
+                split_view.cursor_row(content.cursor.y as usize, text_area_height)
+            }
+            None => {
+                let visible_lines = OutputManager::visible_lines(content.row_offset, text_area_height, line_count);
+                OutputManager::render_lines(&mut frame, content, visible_lines, 0, gutter_width)?;
+                (content.cursor.y as usize).saturating_sub(content.row_offset) as u16
+            }
+        };
+
+        let stats = content.stats();
+        let cursor_screen_x = content.cursor_screen_x() + gutter_width;
+
+        // Mark the rows past the end of the document, like vim's `~`
+        if let (Some(marker), None) = (content.eob_marker, &content.split_view) {
+            let shown_lines = line_count.saturating_sub(content.row_offset);
+            for row in OutputManager::eob_marker_rows(shown_lines, text_area_height) {
+                execute!(
+                    frame.row(row),
+                    SetForegroundColor(Color::DarkGrey),
+                    Print(marker),
+                    ResetColor,
+                )?;
+            }
+        }
 
         // Draw the bottom border with ~~~~~~~~~~~~~~~~
         execute!(
-            stdout,
-            MoveTo(0, height - 5),
-            Clear(ClearType::CurrentLine),
+            frame.row(height.saturating_sub(STATUS_LINES)),
             SetForegroundColor(Color::DarkGrey),
             Print("~".repeat(width as usize)), // ~~~~~~~~~~~~~~~~
             ResetColor,
-        )
-        .unwrap();
+        )?;
+
+        // A prompt takes over the cursor line while it is being typed in
+        let prompt = match (&content.search_prompt, &content.goto_prompt) {
+            (Some(query), _) => Some(format!("Search: {}", query)),
+            (None, Some(number)) => Some(format!("Go to line: {}", number)),
+            (None, None) => None,
+        };
+        let cursor_status = match &prompt {
+            Some(prompt) => prompt.clone(),
+            None => format!(
+                "Cursor: (row: {}, col: {}){}",
+                content.cursor.y,
+                // The visual column, so tabs count up to the next tab stop
+                content.cursor_screen_x(),
+                OutputManager::search_match_status(content),
+            ),
+        };
+        frame.cursor = match &prompt {
+            Some(_) => (cursor_status.chars().count() as u16, height.saturating_sub(3)),
+            None => (cursor_screen_x, cursor_row),
+        };
 
-        // Display the text, cursor position, length, and console size
+        // Display the cursor position, length, and console size
         execute!(
-            stdout,
-            MoveTo(0, height - 4),
-            SetForegroundColor(Color::Cyan),
-            Print(format!("Text: {:?}", text)),
-            MoveTo(0, height - 3),
+            frame.row(height.saturating_sub(3)),
             SetForegroundColor(Color::Yellow),
-            Print(format!(
-                "Cursor: (row: {}, col: {})",
-                content.cursor.y, content.cursor.x
-            )),
-            MoveTo(0, height - 2),
+            Print(cursor_status),
+            ResetColor,
+        )?;
+        execute!(
+            frame.row(height.saturating_sub(2)),
             SetForegroundColor(Color::Green),
-            Print(format!("Length: {} characters", text.len())),
-            MoveTo(0, height - 1),
-            SetForegroundColor(Color::Blue),
             Print(format!(
-                "Console size: width - {} height - {}",
-                width, height
+                "Length: {} characters, {} words, {} lines, {} bytes{}{}",
+                stats.chars,
+                stats.words,
+                stats.lines,
+                stats.bytes,
+                if content.modified { " [+]" } else { "" },
+                if content.read_only { " [read-only]" } else { "" }
             )),
             ResetColor,
-            MoveTo(content.cursor.x, content.cursor.y), // Move back to your app's cursor position
+        )?;
+        execute!(
+            frame.row(height.saturating_sub(1)),
+            SetForegroundColor(Color::Blue),
+            // A status message takes the bottom line until it expires
+            Print(match content.status_text(Instant::now()) {
+                Some(message) => message.to_string(),
+                None => format!("Console size: width - {} height - {}", width, height),
+            }),
+            SetForegroundColor(Color::Magenta),
+            Print(if content.debug_buffers {
+                format!(
+                    " | {}",
+                    OutputManager::buffer_debug_status(
+                        &content.temporary_add_buffer,
+                        &content.temporary_delete_buffer
+                    )
+                )
+            } else {
+                String::new()
+            }),
+            ResetColor,
+        )?;
+
+        Ok(frame)
+    }
+
+    /// Translates a screen cell into a document `(line, visual column)`, undoing the scroll offset
+    /// (or the pane's offset when split) and the gutter. Returns `None` outside the text area.
+    /// The line may be past the end of the document, the editor clamps it.
+    pub fn document_position_at<T: TextTrait>(content: &Editor<T>, column: u16, row: u16, height: u16) -> Option<(usize, usize)> {
+        let text_area_height = OutputManager::text_area_height(height);
+        let gutter_width = OutputManager::gutter_width(content.line_number_mode, content.visual_line_count());
+        let visual_column = column.saturating_sub(gutter_width) as usize;
+
+        let line = match &content.split_view {
+            Some(split_view) => {
+                let pane_rows = SplitView::pane_rows(text_area_height);
+                let pane = pane_rows.iter().position(|rows| rows.contains(&row))?;
+                split_view.scroll_row_offsets[pane] + (row - pane_rows[pane].start) as usize
+            }
+            None if row < text_area_height => content.row_offset + row as usize,
+            None => return None,
+        };
+
+        Some((line, visual_column))
+    }
+
+    /// Returns the number of rows left for the document in a terminal of the given height.
+    pub fn text_area_height(terminal_height: u16) -> u16 {
+        terminal_height.saturating_sub(STATUS_LINES)
+    }
+
+    /// Returns the document lines shown in a text area of the given height when scrolled to `row_offset`.
+    pub fn visible_lines(row_offset: usize, text_area_height: u16, line_count: usize) -> Range<usize> {
+        let start = row_offset.min(line_count);
+        start..(start + text_area_height as usize).min(line_count)
+    }
+
+    /// Prints the document lines in `visible_lines`, with their gutter numbers, starting at screen row `first_row`.
+    fn render_lines<T: TextTrait>(
+        frame: &mut Frame,
+        content: &Editor<T>,
+        visible_lines: Range<usize>,
+        first_row: u16,
+        gutter_width: u16,
+    ) -> io::Result<()> {
+        let mut text = Vec::new();
+        content.write_lines_to(&mut text, visible_lines.clone())?;
+        let text = String::from_utf8(text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut lines = text.split('\n');
+        let text_width = frame.width.saturating_sub(gutter_width) as usize;
+
+        let gutter_numbers = OutputManager::gutter_numbers(
+            content.line_number_mode,
+            content.cursor.y as usize,
+            visible_lines.clone(),
+        );
+
+        for ((row, line_index), number) in (first_row..).zip(visible_lines).zip(gutter_numbers) {
+            let out = frame.row(row);
+            if let Some(number) = number {
+                let number_color = if line_index == content.cursor.y as usize {
+                    content.theme.current_line_number_color
+                } else {
+                    content.theme.line_number_color
+                };
+                execute!(
+                    out,
+                    SetForegroundColor(number_color),
+                    Print(format!("{:>width$} ", number, width = gutter_width as usize - 1)),
+                    ResetColor,
+                )?;
+            }
+
+            OutputManager::render_line(out, content, lines.next().unwrap_or_default(), line_index, text_width)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints a line with tabs expanded (drawn as glyphs when `show_whitespace` is on), its highlighted spans
+    /// and any text past `max_line_length` columns drawn in their theme background colors
+    /// and the editor's highlighter, if any, coloring the text. Color changes are escape sequences that
+    /// take no columns, so the cursor column still matches the printed text. The line is cut after
+    /// `max_columns` columns, so it never wraps onto the rows below.
+    fn render_line<W: Write, T: TextTrait>(
+        out: &mut W,
+        content: &Editor<T>,
+        line: &str,
+        line_index: usize,
+        max_columns: usize,
+    ) -> io::Result<()> {
+        let tab_width = content.tab_width();
+        let mut backgrounds = OutputManager::highlight_spans(content, line_index, line.len());
+        // The text past the configured line length goes last, so the selection and matches are drawn over it
+        if let Some(max_line_length) = content.max_line_length {
+            let start = tab_stops::column_at_visual(line, max_line_length, tab_width);
+            if start < line.len() {
+                backgrounds.push((start..line.len(), content.theme.long_line_color));
+            }
+        }
+        let foregrounds = content
+            .highlighter
+            .as_ref()
+            .map_or(Vec::new(), |highlighter| highlighter.spans(line));
+
+        // Split the line wherever a color starts or ends, and print each part in its colors
+        let mut boundaries = vec![0, line.len()];
+        for (range, _) in backgrounds.iter().chain(&foregrounds) {
+            boundaries.push(range.start.min(line.len()));
+            boundaries.push(range.end.min(line.len()));
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let color_at = |spans: &[(Range<usize>, Color)], position: usize| {
+            spans.iter().find(|(range, _)| range.contains(&position)).map(|&(_, color)| color)
+        };
+
+        let expand = if content.show_whitespace {
+            tab_stops::expand_tabs_visible
+        } else {
+            tab_stops::expand_tabs
+        };
+
+        let mut printed_width = 0;
+        let mut printed_columns = 0;
+        for part in boundaries.windows(2) {
+            if printed_columns >= max_columns {
+                break;
+            }
+
+            // Tabs expand relative to the start of the line, so expand whole prefixes and print the difference
+            let expanded = expand(&line[..part[1]], tab_width);
+            let text = &expanded[printed_width..];
+            printed_width = expanded.len();
+            let text = match text.char_indices().nth(max_columns - printed_columns) {
+                Some((end, _)) => &text[..end],
+                None => text,
+            };
+            printed_columns += text.chars().count();
+
+            let foreground = color_at(&foregrounds, part[0]);
+            let background = color_at(&backgrounds, part[0]);
+            if let Some(color) = foreground {
+                execute!(out, SetForegroundColor(color))?;
+            }
+            if let Some(color) = background {
+                execute!(out, SetBackgroundColor(color))?;
+            }
+            execute!(out, Print(text))?;
+            if foreground.is_some() || background.is_some() {
+                execute!(out, ResetColor)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the byte ranges of `line_index` that are highlighted, with their background colors from
+    /// the editor's theme. The current search match, other matches and the selection each get their own
+    /// color, with matches drawn over the selection. Ranges are sorted and do not overlap.
+    pub fn highlight_spans<T: TextTrait>(content: &Editor<T>, line_index: usize, line_length: usize) -> Vec<(Range<usize>, Color)> {
+        let theme = &content.theme;
+        let mut colors: Vec<Option<Color>> = vec![None; line_length];
+
+        if let Some((start, end)) = content.selected_range() {
+            let line_start = content.line_start_position(line_index);
+            let from = start.saturating_sub(line_start).min(line_length);
+            let to = end.saturating_sub(line_start).min(line_length);
+            colors[from..to].fill(Some(theme.selection_color));
+        }
+
+        let match_length = content.search_query.len();
+        for (index, &(line, offset)) in content.search_matches.iter().enumerate() {
+            if line != line_index {
+                continue;
+            }
+
+            let color = if content.current_match == Some(index) {
+                theme.current_match_color
+            } else {
+                theme.match_color
+            };
+            let end = (offset + match_length).min(line_length);
+            colors[offset.min(end)..end].fill(Some(color));
+        }
+
+        // Merge runs of the same color into spans
+        let mut spans: Vec<(Range<usize>, Color)> = Vec::new();
+        for (position, color) in colors.into_iter().enumerate() {
+            let Some(color) = color else { continue };
+            match spans.last_mut() {
+                Some((range, last_color)) if range.end == position && *last_color == color => {
+                    range.end += 1
+                }
+                _ => spans.push((position..position + 1, color)),
+            }
+        }
+
+        spans
+    }
+
+    /// Formats the position of the current search match, e.g. " | Match 2/5".
+    /// Returns an empty string when there are no search results.
+    fn search_match_status<T: TextTrait>(content: &Editor<T>) -> String {
+        match content.current_match {
+            Some(index) => format!(" | Match {}/{}", index + 1, content.search_matches.len()),
+            None if !content.search_matches.is_empty() => {
+                format!(" | {} matches", content.search_matches.len())
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Formats the state of the temporary buffers for the `--debug-buffers` overlay,
+    /// e.g. `Add: "abc" @ 5 | Delete: 2..4`.
+    pub fn buffer_debug_status(
+        add_buffer: &TemporaryBufferAddText,
+        delete_buffer: &TemporaryBufferDeleteText,
+    ) -> String {
+        let delete_range = match delete_buffer.get_deletion_range() {
+            Some((start, end)) => format!("{}..{}", start, end),
+            None => "none".to_string(),
+        };
+
+        format!(
+            "Add: {:?} @ {} | Delete: {}",
+            add_buffer.buffer, add_buffer.position, delete_range
         )
-        .unwrap();
-        stdout.flush().unwrap();
-        execute!(stdout, cursor::MoveTo(content.cursor.x, content.cursor.y))
     }
+
+    /// Returns the number printed in the gutter for each line in `visible_lines`,
+    /// or `None` for every line when line numbers are off.
+    /// `cursor_line` is used by the relative and hybrid modes.
+    pub fn gutter_numbers(
+        mode: LineNumberMode,
+        cursor_line: usize,
+        visible_lines: Range<usize>,
+    ) -> Vec<Option<usize>> {
+        visible_lines
+            .map(|line| match mode {
+                LineNumberMode::Off => None,
+                LineNumberMode::Absolute => Some(line + 1),
+                LineNumberMode::Relative => Some(line.abs_diff(cursor_line)),
+                LineNumberMode::Hybrid if line == cursor_line => Some(line + 1),
+                LineNumberMode::Hybrid => Some(line.abs_diff(cursor_line)),
+            })
+            .collect()
+    }
+
+    /// Returns the width of the gutter, including one column of padding,
+    /// which is wide enough for the largest line number of the document.
+    pub fn gutter_width(mode: LineNumberMode, line_count: usize) -> u16 {
+        if mode == LineNumberMode::Off {
+            return 0;
+        }
+
+        line_count.max(1).to_string().len() as u16 + 1
+    }
+
+    /// Returns the screen rows below the last document line that should show the end-of-buffer marker,
+    /// given the number of document lines and the height of the text area.
+    pub fn eob_marker_rows(line_count: usize, text_area_height: u16) -> Range<u16> {
+        let first_row = line_count.min(text_area_height as usize) as u16;
+        first_row..text_area_height
+    }
+}
+
+#[test]
+fn test_eob_marker_rows_below_short_document() {
+    assert_eq!(OutputManager::eob_marker_rows(3, 10), 3..10);
+    assert_eq!(OutputManager::eob_marker_rows(1, 10), 1..10);
+}
+
+#[test]
+fn test_eob_marker_rows_when_document_fills_the_screen() {
+    assert!(OutputManager::eob_marker_rows(10, 10).is_empty());
+    assert!(OutputManager::eob_marker_rows(500, 10).is_empty());
+}
+
+#[test]
+fn test_gutter_numbers_for_each_mode() {
+    let visible_lines = 0..5;
+    let cursor_line = 2;
+
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Off, cursor_line, visible_lines.clone()),
+        vec![None; 5]
+    );
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Absolute, cursor_line, visible_lines.clone()),
+        vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+    );
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Relative, cursor_line, visible_lines.clone()),
+        vec![Some(2), Some(1), Some(0), Some(1), Some(2)]
+    );
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Hybrid, cursor_line, visible_lines),
+        vec![Some(2), Some(1), Some(3), Some(1), Some(2)]
+    );
+}
+
+#[test]
+fn test_gutter_numbers_for_a_scrolled_range() {
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Relative, 12, 10..14),
+        vec![Some(2), Some(1), Some(0), Some(1)]
+    );
+    assert_eq!(
+        OutputManager::gutter_numbers(LineNumberMode::Hybrid, 12, 10..14),
+        vec![Some(2), Some(1), Some(13), Some(1)]
+    );
+}
+
+#[test]
+fn test_gutter_width_scales_with_line_count() {
+    assert_eq!(OutputManager::gutter_width(LineNumberMode::Off, 1000), 0);
+    assert_eq!(OutputManager::gutter_width(LineNumberMode::Absolute, 9), 2);
+    assert_eq!(OutputManager::gutter_width(LineNumberMode::Relative, 1000), 5);
+}
+
+#[test]
+fn test_buffer_debug_status() {
+    use crossterm::event::KeyCode;
+
+    let mut add_buffer = TemporaryBufferAddText::new(5, 3);
+    let mut delete_buffer = TemporaryBufferDeleteText::new(5);
+    assert_eq!(
+        OutputManager::buffer_debug_status(&add_buffer, &delete_buffer),
+        "Add: \"\" @ 3 | Delete: none"
+    );
+
+    add_buffer.add_char('a').unwrap();
+    add_buffer.add_char('b').unwrap();
+    delete_buffer.add_char("Hello world", 8, KeyCode::Backspace).unwrap();
+    delete_buffer.add_char("Hello world", 7, KeyCode::Backspace).unwrap();
+    assert_eq!(
+        OutputManager::buffer_debug_status(&add_buffer, &delete_buffer),
+        "Add: \"ab\" @ 3 | Delete: 6..8"
+    );
+}
+
+#[cfg(test)]
+use crate::ui::highlighter::DefaultHighlighter;
+
+#[cfg(test)]
+struct FailingWriter;
+
+#[cfg(test)]
+impl Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "terminal went away"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "terminal went away"))
+    }
+}
+
+#[test]
+fn test_render_returns_write_errors() {
+    let editor = Editor::new("Hello\nWorld".to_string(), 5);
+
+    let result = OutputManager::render(&mut FailingWriter, &editor, 80, 24);
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn test_render_into_a_buffer() {
+    let editor = Editor::new("Hello\nWorld".to_string(), 5);
+    let mut out = Vec::new();
+
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let rendered = String::from_utf8(out).unwrap();
+    assert!(rendered.contains("Hello"));
+    assert!(rendered.contains("World"));
+}
+
+#[test]
+fn test_highlight_spans_color_current_match_differently() {
+    let mut editor = Editor::new("foo bar foo\nfoo".to_string(), 5);
+    editor.find_all("foo");
+    editor.next_match();
+    let theme = editor.theme;
+    assert_ne!(theme.current_match_color, theme.match_color);
+
+    let current = editor.current_match.unwrap();
+    let (current_line, _) = editor.search_matches[current];
+    let spans = OutputManager::highlight_spans(&editor, current_line, 11);
+    assert_eq!(spans.len(), 2);
+
+    let colors: Vec<Color> = spans.iter().map(|(_, color)| *color).collect();
+    assert!(colors.contains(&theme.current_match_color));
+    assert!(colors.contains(&theme.match_color));
+    assert_ne!(spans[0].1, spans[1].1);
+    assert_eq!(spans[0].0.len(), 3);
+}
+
+#[test]
+fn test_highlight_spans_for_selection() {
+    let mut editor = Editor::new("Hello\nworld".to_string(), 5);
+    editor.move_to_document_start();
+    editor.start_selection();
+    for _ in 0..8 {
+        editor.move_cursor_right();
+    }
+
+    let selection_color = editor.theme.selection_color;
+    assert_eq!(OutputManager::highlight_spans(&editor, 0, 5), vec![(0..5, selection_color)]);
+    assert_eq!(OutputManager::highlight_spans(&editor, 1, 5), vec![(0..2, selection_color)]);
+}
+
+#[test]
+fn test_render_split_view_draws_a_divider() {
+    let mut editor = Editor::new("Hello\nWorld".to_string(), 5);
+    editor.toggle_split_view();
+    let mut out = Vec::new();
+
+    OutputManager::render(&mut out, &editor, 10, 24).unwrap();
+    let rendered = String::from_utf8(out).unwrap();
+    assert!(rendered.contains(&"─".repeat(10)));
+    // Once in each pane
+    assert_eq!(rendered.matches("World").count(), 2);
+}
+
+#[test]
+fn test_render_shows_the_modified_indicator() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains("[+]"));
+
+    editor.add_char('!');
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("[+]"));
+}
+
+#[test]
+fn test_visible_lines_follow_the_row_offset() {
+    assert_eq!(OutputManager::visible_lines(0, 10, 500), 0..10);
+    assert_eq!(OutputManager::visible_lines(95, 10, 100), 95..100);
+    assert_eq!(OutputManager::visible_lines(0, 10, 3), 0..3);
+}
+
+#[test]
+fn test_render_only_draws_the_scrolled_window() {
+    let text = (0..100).map(|line| format!("row{}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    editor.viewport_height = OutputManager::text_area_height(24) as usize;
+    editor.scroll_to_cursor();
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+
+    assert_eq!(screen.matches("row99").count(), 1);
+    assert_eq!(screen.matches("row80").count(), 1);
+    assert_eq!(screen.matches("row79").count(), 0);
+    assert_eq!(screen.matches("row50").count(), 0);
+}
+
+#[test]
+fn test_render_line_numbers_shift_the_text_and_cursor() {
+    let text = (1..=1000).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    editor.line_number_mode = LineNumberMode::Absolute;
+    editor.move_to_document_start();
+    editor.move_cursor_right();
+    assert_eq!(OutputManager::gutter_width(editor.line_number_mode, editor.visual_line_count()), 5);
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+
+    assert!(screen.contains("   1 "));
+    assert!(screen.contains("   2 "));
+    // The cursor is left after column 1 of the text, past the 5-column gutter
+    assert!(screen.ends_with(&format!("{}", MoveTo(6, 0))));
+}
+
+#[test]
+fn test_render_search_prompt() {
+    let mut editor = Editor::new("Hello World".to_string(), 5);
+    editor.start_search();
+    editor.push_search_char('W');
+    editor.push_search_char('o');
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains("Search: Wo"));
+    assert!(!screen.contains("Cursor: (row"));
+    assert!(screen.ends_with(&format!("{}", MoveTo(10, 21))));
+}
+
+#[test]
+fn test_render_goto_line_prompt() {
+    let mut editor = Editor::new("Hello World".to_string(), 5);
+    editor.start_goto_line();
+    editor.push_goto_digit('4');
+    editor.push_goto_digit('2');
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains("Go to line: 42"));
+    assert!(screen.ends_with(&format!("{}", MoveTo(14, 21))));
+}
+
+#[test]
+fn test_render_reports_the_visual_column() {
+    let mut editor = Editor::new("\tab".to_string(), 5);
+    editor.set_tab_width(4).unwrap();
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains("Cursor: (row: 0, col: 6)"));
+    assert!(screen.contains("    ab"));
+}
+
+#[test]
+fn test_document_position_at_undoes_scroll_and_gutter() {
+    let text = (0..100).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n");
+    let mut editor = Editor::new(text, 5);
+    assert_eq!(OutputManager::document_position_at(&editor, 3, 2, 24), Some((2, 3)));
+
+    editor.row_offset = 40;
+    editor.line_number_mode = LineNumberMode::Absolute;
+    assert_eq!(OutputManager::document_position_at(&editor, 7, 2, 24), Some((42, 3)));
+    assert_eq!(OutputManager::document_position_at(&editor, 1, 0, 24), Some((40, 0)));
+
+    // The status area is not part of the document
+    let text_area_height = OutputManager::text_area_height(24);
+    assert_eq!(OutputManager::document_position_at(&editor, 0, text_area_height, 24), None);
+
+    let mut split_view = SplitView::new();
+    split_view.scroll_row_offsets = [0, 90];
+    editor.split_view = Some(split_view);
+    let bottom_pane_start = SplitView::pane_rows(text_area_height)[1].start;
+    assert_eq!(
+        OutputManager::document_position_at(&editor, 4, bottom_pane_start + 1, 24),
+        Some((91, 0))
+    );
+    assert_eq!(OutputManager::document_position_at(&editor, 4, SplitView::divider_row(text_area_height), 24), None);
+}
+
+#[test]
+fn test_render_shows_the_stats() {
+    let editor = Editor::new("a   b\nc".to_string(), 5);
+
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("Length: 7 characters, 3 words, 2 lines, 7 bytes"));
+
+    // An empty document has no words but still one line
+    let editor = Editor::new(String::new(), 5);
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("Length: 0 characters, 0 words, 1 lines, 0 bytes"));
+}
+
+#[test]
+fn test_render_shows_the_status_message_instead_of_the_console_size() {
+    let mut editor = Editor::new("Hello".to_string(), 5);
+    editor.set_status("Saved 5 bytes");
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains("Saved 5 bytes"));
+    assert!(!screen.contains("Console size"));
+
+    editor.status_message = None;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("Console size"));
+}
+
+#[test]
+fn test_render_draws_default_eob_markers_below_the_document() {
+    let marker_at = |row| format!("{}{}~{}", MoveTo(0, row), SetForegroundColor(Color::DarkGrey), ResetColor);
+
+    // The rows below the two document lines are marked, the lines themselves are not
+    let mut editor = Editor::new("a\nb".to_string(), 5);
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(!screen.contains(&marker_at(1)));
+    assert!(screen.contains(&marker_at(2)));
+    assert!(screen.contains(&marker_at(OutputManager::text_area_height(24) - 1)));
+
+    // Without a marker those rows stay empty
+    editor.eob_marker = None;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains(&marker_at(2)));
+
+    // Scrolled to the end of a long document, the rows past its last line get markers too
+    let mut editor = Editor::new("line\n".repeat(30), 5);
+    editor.row_offset = 25;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(!screen.contains(&marker_at(5)));
+    assert!(screen.contains(&marker_at(6)));
+}
+
+#[test]
+fn test_render_changes_only_rewrites_changed_rows() {
+    let mut editor = Editor::new("Hello\nWorld".to_string(), 5);
+    let mut output_manager = OutputManager::default();
+    let clear_all = format!("{}", Clear(ClearType::All));
+
+    // The first frame is drawn in full
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with(&clear_all));
+
+    // Nothing changed, so only the cursor is placed again
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 80, 24).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}", MoveTo(5, 1)));
+
+    // Typing on the second line leaves the first one alone
+    editor.add_char('!');
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(!screen.contains(&clear_all));
+    assert!(!screen.contains(&format!("{}", MoveTo(0, 0))));
+    assert!(screen.contains(&format!("{}{}World!", MoveTo(0, 1), Clear(ClearType::CurrentLine))));
+
+    // A new terminal size redraws everything
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 100, 30).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with(&clear_all));
+}
+
+#[test]
+fn test_render_changes_clips_lines_wider_than_the_terminal() {
+    let mut editor = Editor::new("abcdefghijklmnop\nxy".to_string(), 5);
+    let mut output_manager = OutputManager::default();
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 10, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains("abcdefghij"));
+    assert!(!screen.contains("abcdefghijk"));
+
+    // A clipped line cannot wrap onto the next row, so redrawing that row alone is enough
+    editor.line_number_mode = LineNumberMode::Absolute;
+    let mut out = Vec::new();
+    output_manager.render_changes(&mut out, &editor, 10, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+    assert!(screen.contains(&format!("{}{}", MoveTo(0, 0), Clear(ClearType::CurrentLine))));
+    assert!(screen.contains("abcdefgh"));
+    assert!(!screen.contains("abcdefghi"));
+}
+
+#[test]
+fn test_status_lines_follow_the_terminal_height() {
+    let editor = Editor::new("Hello".to_string(), 5);
+    let mut output_manager = OutputManager::default();
+
+    for height in [24, 12, 40] {
+        let mut out = Vec::new();
+        output_manager.render_changes(&mut out, &editor, 80, height).unwrap();
+        let screen = String::from_utf8(out).unwrap();
+        let length_row = format!("{}{}Length:", MoveTo(0, height - 2), SetForegroundColor(Color::Green));
+        assert!(screen.contains(&length_row), "height {}", height);
+    }
+}
+
+#[test]
+fn test_render_colors_highlighter_spans_and_keeps_the_cursor_column() {
+    let mut editor = Editor::new("x = \"hi\" + 42".to_string(), 5);
+    editor.move_cursor_to_visual_position(0, 12);
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+
+    assert!(screen.contains(&format!(
+        "x = {}\"hi\"{} + {}42{}",
+        SetForegroundColor(DefaultHighlighter::STRING_COLOR),
+        ResetColor,
+        SetForegroundColor(DefaultHighlighter::NUMBER_COLOR),
+        ResetColor
+    )));
+    assert!(screen.ends_with(&format!("{}", MoveTo(12, 0))));
+
+    editor.highlighter = None;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("x = \"hi\" + 42"));
+}
+
+#[test]
+fn test_render_marks_the_text_past_max_line_length() {
+    let mut editor = Editor::new("abcdefgh\n\t\tz\nshort".to_string(), 5);
+    editor.highlighter = None;
+    editor.max_line_length = Some(6);
+    let long_line = SetBackgroundColor(editor.theme.long_line_color);
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+
+    assert!(screen.contains(&format!("abcdef{}gh{}", long_line, ResetColor)));
+    // A tab crossing the limit is marked as a whole
+    assert!(screen.contains(&format!("    {}    z{}", long_line, ResetColor)));
+    assert_eq!(screen.matches(&format!("{}", long_line)).count(), 2);
+
+    editor.max_line_length = None;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains(&format!("{}", long_line)));
+}
+
+#[test]
+fn test_render_shows_whitespace_glyphs_without_moving_the_cursor() {
+    let mut editor = Editor::new("\tab cd ".to_string(), 5);
+    editor.highlighter = None;
+    editor.show_whitespace = true;
+    let mut out = Vec::new();
+    OutputManager::render(&mut out, &editor, 80, 24).unwrap();
+    let screen = String::from_utf8(out).unwrap();
+
+    assert!(screen.contains("→   ab·cd·"));
+    // The cursor after the trailing space is at visual column 4 + 6
+    assert!(screen.ends_with(&format!("{}", MoveTo(10, 0))));
+    assert_eq!(editor.get_text(), "\tab cd ");
 }
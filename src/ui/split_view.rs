@@ -0,0 +1,110 @@
+use std::ops::Range;
+
+/// A horizontal split showing two regions of the same document, one above the other,
+/// separated by a one-row divider. Each pane scrolls on its own; edits go to the active pane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitView {
+    /// The first document line shown in the top and bottom pane.
+    pub scroll_row_offsets: [usize; 2],
+    /// The pane showing the cursor, 0 for the top pane and 1 for the bottom one.
+    pub active_pane: usize,
+}
+
+impl SplitView {
+    /// Creates a split with both panes scrolled to the top and the top pane active.
+    pub fn new() -> Self {
+        Self {
+            scroll_row_offsets: [0, 0],
+            active_pane: 0,
+        }
+    }
+
+    /// Returns the screen rows of the top and bottom pane for a text area of the given height.
+    /// The row between them is left for the divider.
+    pub fn pane_rows(text_area_height: u16) -> [Range<u16>; 2] {
+        let top_height = text_area_height.saturating_sub(1) / 2;
+        let bottom_start = (top_height + 1).min(text_area_height);
+        [0..top_height, bottom_start..text_area_height]
+    }
+
+    /// Returns the screen row of the divider between the two panes.
+    pub fn divider_row(text_area_height: u16) -> u16 {
+        SplitView::pane_rows(text_area_height)[0].end
+    }
+
+    /// Returns the document lines visible in the top and bottom pane.
+    pub fn pane_line_ranges(&self, text_area_height: u16, line_count: usize) -> [Range<usize>; 2] {
+        let rows = SplitView::pane_rows(text_area_height);
+        [0, 1].map(|pane| {
+            let start = self.scroll_row_offsets[pane].min(line_count);
+            let end = (start + rows[pane].len()).min(line_count);
+            start..end
+        })
+    }
+
+    /// Scrolls the active pane so `cursor_line` is visible.
+    pub fn scroll_to_cursor(&mut self, cursor_line: usize, text_area_height: u16) {
+        let pane_height = SplitView::pane_rows(text_area_height)[self.active_pane].len().max(1);
+        let offset = &mut self.scroll_row_offsets[self.active_pane];
+
+        if cursor_line < *offset {
+            *offset = cursor_line;
+        } else if cursor_line >= *offset + pane_height {
+            *offset = cursor_line + 1 - pane_height;
+        }
+    }
+
+    /// Makes the other pane active.
+    pub fn switch_pane(&mut self) {
+        self.active_pane = 1 - self.active_pane;
+    }
+
+    /// Returns the screen row of `cursor_line` in the active pane.
+    pub fn cursor_row(&self, cursor_line: usize, text_area_height: u16) -> u16 {
+        let rows = SplitView::pane_rows(text_area_height);
+        let offset = self.scroll_row_offsets[self.active_pane];
+        rows[self.active_pane].start + cursor_line.saturating_sub(offset) as u16
+    }
+}
+
+impl Default for SplitView {
+    fn default() -> Self {
+        SplitView::new()
+    }
+}
+
+#[test]
+fn test_pane_rows_leave_a_divider() {
+    assert_eq!(SplitView::pane_rows(21), [0..10, 11..21]);
+    assert_eq!(SplitView::pane_rows(20), [0..9, 10..20]);
+    assert_eq!(SplitView::divider_row(21), 10);
+}
+
+#[test]
+fn test_pane_line_ranges_with_two_scroll_offsets() {
+    let mut split_view = SplitView::new();
+    split_view.scroll_row_offsets = [0, 90];
+
+    assert_eq!(split_view.pane_line_ranges(21, 100), [0..10, 90..100]);
+
+    // The bottom pane is cut short at the end of the document
+    split_view.scroll_row_offsets = [5, 95];
+    assert_eq!(split_view.pane_line_ranges(21, 100), [5..15, 95..100]);
+
+    // Short documents fit in both panes
+    assert_eq!(SplitView::new().pane_line_ranges(21, 3), [0..3, 0..3]);
+}
+
+#[test]
+fn test_scroll_to_cursor_moves_only_the_active_pane() {
+    let mut split_view = SplitView::new();
+    split_view.switch_pane();
+
+    split_view.scroll_to_cursor(50, 21);
+    assert_eq!(split_view.scroll_row_offsets, [0, 41]);
+    assert_eq!(split_view.cursor_row(50, 21), 20);
+
+    split_view.scroll_to_cursor(30, 21);
+    assert_eq!(split_view.scroll_row_offsets, [0, 30]);
+    assert_eq!(split_view.cursor_row(30, 21), 11);
+}
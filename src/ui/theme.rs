@@ -0,0 +1,31 @@
+use crossterm::style::Color;
+
+/// Colors used to highlight parts of the text and the gutter when rendering.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Background of the selected text.
+    pub selection_color: Color,
+    /// Background of every search match except the current one.
+    pub match_color: Color,
+    /// Background of the search match the cursor jumped to.
+    pub current_match_color: Color,
+    /// Foreground of the gutter line numbers.
+    pub line_number_color: Color,
+    /// Foreground of the cursor line's number in the gutter.
+    pub current_line_number_color: Color,
+    /// Background of the text past `max_line_length` columns.
+    pub long_line_color: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            selection_color: Color::DarkBlue,
+            match_color: Color::DarkYellow,
+            current_match_color: Color::Magenta,
+            line_number_color: Color::DarkGrey,
+            current_line_number_color: Color::White,
+            long_line_color: Color::DarkRed,
+        }
+    }
+}